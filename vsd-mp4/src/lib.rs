@@ -10,6 +10,10 @@
 //!
 //! - **pssh**: Enables support for parsing `PSSH` boxes.
 //! - **sidx**: Enables support for parsing `SIDX` boxes.
+//! - **text-cea**: Enables support for extracting CEA-608/708 closed captions from video streams.
+//! - **text-dvb**: Enables support for extracting dvb bitmap subtitles from transport streams.
+//! - **text-sami**: Enables support for parsing sami subtitles.
+//! - **text-srt**: Enables support for parsing srt subtitles.
 //! - **text-ttml**: Enables support for extracting ttml subtitles.
 //! - **text-vtt**: Enables support for extracting vtt subtitles.
 
@@ -25,8 +29,25 @@ pub mod pssh;
 #[cfg_attr(docsrs, doc(cfg(feature = "sidx")))]
 pub mod sidx;
 
-#[cfg(any(feature = "text-ttml", feature = "text-vtt"))]
-#[cfg_attr(docsrs, doc(cfg(any(feature = "text-ttml", feature = "text-vtt"))))]
+#[cfg(any(
+    feature = "text-cea",
+    feature = "text-dvb",
+    feature = "text-sami",
+    feature = "text-srt",
+    feature = "text-ttml",
+    feature = "text-vtt"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(
+        feature = "text-cea",
+        feature = "text-dvb",
+        feature = "text-sami",
+        feature = "text-srt",
+        feature = "text-ttml",
+        feature = "text-vtt"
+    )))
+)]
 pub mod text;
 
 pub use error::{Error, ErrorType};
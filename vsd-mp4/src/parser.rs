@@ -21,6 +21,8 @@ pub struct Mp4Parser {
     pub headers: HashMap<usize, BoxType>,
     pub box_definitions: HashMap<usize, CallbackType>,
     pub done: bool,
+    /// Callback for box types which aren't declared.
+    pub unknown_box_definition: Option<CallbackType>,
 }
 
 impl Mp4Parser {
@@ -40,6 +42,13 @@ impl Mp4Parser {
         self
     }
 
+    /// Declare a callback for every box type which isn't declared, these boxes
+    /// are treated as Basic Box.
+    pub fn unknown_box(mut self, definition: CallbackType) -> Self {
+        self.unknown_box_definition = Some(definition);
+        self
+    }
+
     /// Stop parsing. Useful for extracting information from partial segments and
     /// avoiding an out-of-bounds error once you find what you are looking for.
     pub fn stop(&mut self) {
@@ -124,13 +133,29 @@ impl Mp4Parser {
             _ => (),
         }
 
-        let box_definition = self.box_definitions.get(&_type);
+        // Boxes smaller than their header or with overflowing size are invalid.
+        if size < reader.get_position() - start || start.checked_add(size).is_none() {
+            return Err(Error::new_decode(format!(
+                "{name} box size ({size} bytes)."
+            )));
+        }
+
+        let box_definition = self
+            .box_definitions
+            .get(&_type)
+            .or(self.unknown_box_definition.as_ref());
 
         if let Some(box_definition) = box_definition {
             let mut version = None;
             let mut flags = None;
 
-            if *self.headers.get(&_type).unwrap() == BoxType::FullBox {
+            if self.headers.get(&_type) == Some(&BoxType::FullBox) {
+                if size < reader.get_position() - start + 4 {
+                    return Err(Error::new_decode(format!(
+                        "{name} full box size ({size} bytes)."
+                    )));
+                }
+
                 if stop_on_partial && reader.get_position() + 4 > reader.get_length() {
                     self.done = true;
                     return Ok(());
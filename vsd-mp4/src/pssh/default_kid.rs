@@ -43,6 +43,325 @@ pub fn default_kid(data: &[u8]) -> Result<Option<String>> {
     Ok(default_kid.clone())
 }
 
+/// Parse all key ids used for encryption i.e. default kids from `TENC` boxes of every track
+/// and kids from `seig` sample group descriptions (`SGPD` box) of fragments (key rotation).
+pub fn encryption_kids(data: &[u8]) -> Result<Vec<String>> {
+    let kids = Arc::new(Mutex::new(Vec::new()));
+    let kids_c = kids.clone();
+    let kids_cc = kids.clone();
+
+    Mp4Parser::default()
+        .basic_box("moov", Arc::new(parser::children))
+        .basic_box("trak", Arc::new(parser::children))
+        .basic_box("mdia", Arc::new(parser::children))
+        .basic_box("minf", Arc::new(parser::children))
+        .basic_box("stbl", Arc::new(parser::children))
+        .full_box("stsd", Arc::new(parser::sample_description))
+        .basic_box("encv", Arc::new(parser::visual_sample_entry))
+        .basic_box("enca", Arc::new(parser::audio_sample_entry))
+        .basic_box("sinf", Arc::new(parser::children))
+        .basic_box("schi", Arc::new(parser::children))
+        .full_box(
+            "tenc",
+            Arc::new(move |mut _box| {
+                kids_c.lock().unwrap().push(parse_tenc(&mut _box)?);
+                Ok(())
+            }),
+        )
+        .basic_box("moof", Arc::new(parser::children))
+        .basic_box("traf", Arc::new(parser::children))
+        .full_box(
+            "sgpd",
+            Arc::new(move |mut _box| {
+                kids_cc
+                    .lock()
+                    .unwrap()
+                    .extend(parse_seig_sgpd(&mut _box)?.into_iter().flatten());
+                Ok(())
+            }),
+        )
+        .parse(data, true, false)?;
+
+    let mut unique_kids = vec![];
+
+    for kid in kids.lock().unwrap().iter().cloned() {
+        if !unique_kids.contains(&kid) {
+            unique_kids.push(kid);
+        }
+    }
+
+    Ok(unique_kids)
+}
+
+#[derive(Default)]
+struct FragmentKids {
+    tracks: Vec<Track>,
+    fragments: Vec<Fragment>,
+}
+
+struct Track {
+    default_kid: Option<String>,
+    /// Kids of `seig` sample group descriptions.
+    groups: Vec<Option<String>>,
+    id: u32,
+}
+
+struct Fragment {
+    /// Kids of fragment local `seig` sample group descriptions.
+    groups: Vec<Option<String>>,
+    /// Sample group description indexes which samples are mapped to.
+    indexes: Option<Vec<u32>>,
+    track_id: u32,
+}
+
+/// Parse key ids actually used by samples of fragments (`MOOF` boxes) i.e. kids of `seig` sample
+/// groups which samples are mapped to (`SBGP` box), otherwise default kid of their track.
+/// Tracks are looked up in `MOOV` box, so data should start with an init segment.
+pub fn fragment_kids(data: &[u8]) -> Result<Vec<String>> {
+    let state = Arc::new(Mutex::new(FragmentKids::default()));
+    let state_c = state.clone();
+    let state_cc = state.clone();
+    let state_ccc = state.clone();
+    let state_cccc = state.clone();
+    let state_ccccc = state.clone();
+
+    Mp4Parser::default()
+        .basic_box("moov", Arc::new(parser::children))
+        .basic_box("trak", Arc::new(parser::children))
+        .full_box(
+            "tkhd",
+            Arc::new(move |mut _box| {
+                // creation and modification time are u64 in version 1.
+                let skip = if _box.version == Some(1) { 16 } else { 8 };
+                _box.reader
+                    .skip(skip)
+                    .map_err(|_| Error::new_read("TKHD box times."))?;
+                let track_id = _box
+                    .reader
+                    .read_u32()
+                    .map_err(|_| Error::new_read("TKHD box track id (u32)."))?;
+                state_c.lock().unwrap().tracks.push(Track {
+                    default_kid: None,
+                    groups: vec![],
+                    id: track_id,
+                });
+                Ok(())
+            }),
+        )
+        .basic_box("mdia", Arc::new(parser::children))
+        .basic_box("minf", Arc::new(parser::children))
+        .basic_box("stbl", Arc::new(parser::children))
+        .full_box("stsd", Arc::new(parser::sample_description))
+        .basic_box("encv", Arc::new(parser::visual_sample_entry))
+        .basic_box("enca", Arc::new(parser::audio_sample_entry))
+        .basic_box("sinf", Arc::new(parser::children))
+        .basic_box("schi", Arc::new(parser::children))
+        .full_box(
+            "tenc",
+            Arc::new(move |mut _box| {
+                let kid = parse_tenc(&mut _box)?;
+
+                if let Some(track) = state_cc.lock().unwrap().tracks.last_mut() {
+                    track.default_kid = Some(kid);
+                }
+
+                Ok(())
+            }),
+        )
+        .basic_box("moof", Arc::new(parser::children))
+        .basic_box("traf", Arc::new(parser::children))
+        .full_box(
+            "tfhd",
+            Arc::new(move |mut _box| {
+                let track_id = _box
+                    .reader
+                    .read_u32()
+                    .map_err(|_| Error::new_read("TFHD box track id (u32)."))?;
+                state_ccc.lock().unwrap().fragments.push(Fragment {
+                    groups: vec![],
+                    indexes: None,
+                    track_id,
+                });
+                Ok(())
+            }),
+        )
+        .full_box(
+            "sgpd",
+            Arc::new(move |mut _box| {
+                let kids = parse_seig_sgpd(&mut _box)?;
+                let mut state = state_cccc.lock().unwrap();
+
+                // Sample group descriptions of track are found before any fragment.
+                if let Some(fragment) = state.fragments.last_mut() {
+                    fragment.groups.extend(kids);
+                } else if let Some(track) = state.tracks.last_mut() {
+                    track.groups.extend(kids);
+                }
+
+                Ok(())
+            }),
+        )
+        .full_box(
+            "sbgp",
+            Arc::new(move |mut _box| {
+                let indexes = parse_seig_sbgp(&mut _box)?;
+
+                if let (Some(indexes), Some(fragment)) =
+                    (indexes, state_ccccc.lock().unwrap().fragments.last_mut())
+                {
+                    fragment.indexes.get_or_insert_default().extend(indexes);
+                }
+
+                Ok(())
+            }),
+        )
+        .parse(data, true, false)?;
+
+    let state = state.lock().unwrap();
+    let mut kids = vec![];
+
+    for fragment in &state.fragments {
+        let Some(track) = state.tracks.iter().find(|x| x.id == fragment.track_id) else {
+            continue;
+        };
+
+        let used_kids = match &fragment.indexes {
+            // Indexes above 0x10000 refer to fragment local descriptions and
+            // zero refers to default kid of track.
+            Some(indexes) => indexes
+                .iter()
+                .map(|x| match *x {
+                    0 => track.default_kid.as_ref(),
+                    x if x > 0x10000 => fragment
+                        .groups
+                        .get((x - 0x10001) as usize)
+                        .and_then(|x| x.as_ref()),
+                    x => track.groups.get((x - 1) as usize).and_then(|x| x.as_ref()),
+                })
+                .collect::<Vec<_>>(),
+            None => vec![track.default_kid.as_ref()],
+        };
+
+        for kid in used_kids.into_iter().flatten() {
+            if !kids.contains(kid) {
+                kids.push(kid.to_owned());
+            }
+        }
+    }
+
+    Ok(kids)
+}
+
+/// Sample group description indexes of samples mapped to `seig` sample groups.
+fn parse_seig_sbgp(_box: &mut ParsedBox) -> Result<Option<Vec<u32>>> {
+    let version = _box.version.unwrap_or(0);
+    let reader = &mut _box.reader;
+
+    let grouping_type = reader
+        .read_bytes_u8(4)
+        .map_err(|_| Error::new_read("SBGP box grouping type (4 bytes)."))?;
+
+    if grouping_type != b"seig" {
+        return Ok(None);
+    }
+
+    if version == 1 {
+        reader
+            .skip(4)
+            .map_err(|_| Error::new_read("SBGP box grouping type parameter (u32)."))?;
+    }
+
+    let entry_count = reader
+        .read_u32()
+        .map_err(|_| Error::new_read("SBGP box entry count (u32)."))?;
+    let mut indexes = vec![];
+
+    for _ in 0..entry_count {
+        let sample_count = reader
+            .read_u32()
+            .map_err(|_| Error::new_read("SBGP box sample count (u32)."))?;
+        let index = reader
+            .read_u32()
+            .map_err(|_| Error::new_read("SBGP box group description index (u32)."))?;
+
+        if sample_count > 0 {
+            indexes.push(index);
+        }
+    }
+
+    Ok(Some(indexes))
+}
+
+/// Kids of every `seig` sample group description entry, `None` for unprotected entries.
+fn parse_seig_sgpd(_box: &mut ParsedBox) -> Result<Vec<Option<String>>> {
+    let version = _box.version.unwrap_or(0);
+    let reader = &mut _box.reader;
+
+    let grouping_type = reader
+        .read_bytes_u8(4)
+        .map_err(|_| Error::new_read("SGPD box grouping type (4 bytes)."))?;
+
+    if grouping_type != b"seig" {
+        return Ok(vec![]);
+    }
+
+    let mut default_length = 0;
+
+    if version == 1 {
+        default_length = reader
+            .read_u32()
+            .map_err(|_| Error::new_read("SGPD box default length (u32)."))?;
+    } else if version >= 2 {
+        reader
+            .skip(4)
+            .map_err(|_| Error::new_read("SGPD box default sample description index (u32)."))?;
+    }
+
+    let entry_count = reader
+        .read_u32()
+        .map_err(|_| Error::new_read("SGPD box entry count (u32)."))?;
+    let mut kids = vec![];
+
+    for _ in 0..entry_count {
+        let mut length = default_length;
+
+        if version == 1 && default_length == 0 {
+            length = reader
+                .read_u32()
+                .map_err(|_| Error::new_read("SGPD box description length (u32)."))?;
+        }
+
+        // reserved (u8), crypt and skip byte block (u8), is protected (u8), per sample iv size (u8)
+        let header = reader
+            .read_bytes_u8(4)
+            .map_err(|_| Error::new_read("SGPD box seig entry (4 bytes)."))?;
+        let kid = reader
+            .read_bytes_u8(16)
+            .map_err(|_| Error::new_read("SGPD box seig entry kid (16 bytes)."))?;
+        let mut read = 20;
+
+        if header[2] == 1 && header[3] == 0 {
+            let constant_iv_size = reader
+                .read_u8()
+                .map_err(|_| Error::new_read("SGPD box seig entry constant iv size (u8)."))?;
+            reader
+                .skip(constant_iv_size as u64)
+                .map_err(|_| Error::new_read("SGPD box seig entry constant iv."))?;
+            read += 1 + constant_iv_size as u32;
+        }
+
+        if length > read {
+            reader
+                .skip((length - read) as u64)
+                .map_err(|_| Error::new_read("SGPD box seig entry padding."))?;
+        }
+
+        kids.push((header[2] == 1).then(|| hex::encode(kid)));
+    }
+
+    Ok(kids)
+}
+
 fn parse_tenc(_box: &mut ParsedBox) -> Result<String> {
     let reader = &mut _box.reader;
 
@@ -2,8 +2,19 @@
 
 mod default_kid;
 mod playready;
+mod pssh_box;
 mod pssh_parser;
 mod widevine;
 
-pub use default_kid::default_kid;
+pub use default_kid::{default_kid, encryption_kids, fragment_kids};
+pub use playready::PlayReadyData;
+pub use pssh_box::{PsshBox, PsshBuilder, PsshData};
 pub use pssh_parser::{KeyId, KeyIdSystemType, Pssh};
+pub use widevine::WidevineData;
+
+/// Common (W3C `cenc`) system id in hex.
+pub const COMMAN_SYSTEM_ID: &str = "1077efecc0b24d02ace33c1e52e2fb4b";
+/// PlayReady system id in hex.
+pub const PLAYREADY_SYSTEM_ID: &str = "9a04f07998404286ab92e65be0885f95";
+/// Widevine system id in hex.
+pub const WIDEVINE_SYSTEM_ID: &str = "edef8ba979d64acea3c827dcd51d21ed";
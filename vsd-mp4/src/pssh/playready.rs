@@ -6,12 +6,24 @@
 
 */
 
-use super::{KeyId, KeyIdSystemType};
 use crate::{Error, Reader, Result};
 use base64::Engine;
 use serde::Deserialize;
 
-pub(super) fn parse(data: &[u8]) -> Result<impl IntoIterator<Item = KeyId>> {
+/// Decoded playready object present inside `pssh` box data.
+#[derive(Clone, Debug, Default)]
+pub struct PlayReadyData {
+    /// Encryption algorithm i.e. `AESCTR`, `AESCBC` or `COCKTAIL`.
+    pub algid: Option<String>,
+    /// In hex.
+    pub key_ids: Vec<String>,
+    /// License acquisition url.
+    pub la_url: Option<String>,
+    /// WRM header version.
+    pub version: Option<String>,
+}
+
+pub(super) fn parse(data: &[u8]) -> Result<PlayReadyData> {
     let mut reader = Reader::new(data, true);
     let size = reader
         .read_u32()
@@ -25,7 +37,7 @@ pub(super) fn parse(data: &[u8]) -> Result<impl IntoIterator<Item = KeyId>> {
         .read_u16()
         .map_err(|_| Error::new_read("PSSH box playready object record count (u16)."))?;
 
-    let mut kids = vec![];
+    let mut playready = PlayReadyData::default();
 
     for _ in 0..count {
         let record_type = reader
@@ -52,7 +64,13 @@ pub(super) fn parse(data: &[u8]) -> Result<impl IntoIterator<Item = KeyId>> {
                         "PSSH box playready object record data i.e. {xml}\n\n{x:#?}"
                     ))
                 })?;
-                kids.append(&mut wrm_header.kids()?);
+                playready.key_ids.append(&mut wrm_header.kids()?);
+
+                if playready.version.is_none() {
+                    playready.algid = wrm_header.algid();
+                    playready.la_url = wrm_header.la_url();
+                    playready.version = Some(wrm_header.version.clone());
+                }
             }
             2 | 3 => (),
             _ => {
@@ -69,10 +87,7 @@ pub(super) fn parse(data: &[u8]) -> Result<impl IntoIterator<Item = KeyId>> {
         ));
     }
 
-    Ok(kids.into_iter().map(|x| KeyId {
-        system_type: KeyIdSystemType::PlayReady,
-        value: x,
-    }))
+    Ok(playready)
 }
 
 #[derive(Deserialize)]
@@ -88,12 +103,16 @@ pub(super) struct WrmHeader {
 pub(super) struct Data {
     #[serde(rename = "KID")]
     kid: Option<String>,
+    #[serde(rename = "LA_URL")]
+    la_url: Option<String>,
     #[serde(rename = "PROTECTINFO")]
     protect_info: Option<ProtectInfo>,
 }
 
 #[derive(Deserialize)]
 pub(super) struct ProtectInfo {
+    #[serde(rename = "ALGID")]
+    algid: Option<String>,
     #[serde(rename = "KID")]
     kid: Option<KeyID>,
     #[serde(rename = "KIDS", default)]
@@ -102,6 +121,8 @@ pub(super) struct ProtectInfo {
 
 #[derive(Deserialize)]
 pub(super) struct KeyID {
+    #[serde(rename = "@ALGID")]
+    algid: Option<String>,
     #[serde(rename = "@VALUE")]
     value: String,
 }
@@ -113,6 +134,27 @@ pub(super) struct KeyIDs {
 }
 
 impl WrmHeader {
+    /// `ALGID` element (v4.0.0.0) or attribute of first `KID` element (v4.1.0.0 and above).
+    pub(super) fn algid(&self) -> Option<String> {
+        let protect_info = self.data.as_ref()?.protect_info.as_ref()?;
+
+        protect_info.algid.clone().or_else(|| {
+            protect_info
+                .kid
+                .iter()
+                .chain(protect_info.kids.iter().flat_map(|x| x.kids.iter()))
+                .find_map(|x| x.algid.clone())
+        })
+    }
+
+    pub(super) fn la_url(&self) -> Option<String> {
+        self.data
+            .as_ref()?
+            .la_url
+            .as_ref()
+            .map(|x| x.trim().to_owned())
+    }
+
     pub(super) fn kids(&self) -> Result<Vec<String>> {
         let mut kids = vec![];
 
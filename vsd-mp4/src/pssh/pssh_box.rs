@@ -0,0 +1,261 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://www.w3.org/TR/eme-initdata-cenc/#common-system
+    2. https://github.com/shaka-project/shaka-packager/blob/56e227267c9091a0f65b4d92d9064dda4557f3a7/packager/tools/pssh/pssh-box.py
+
+*/
+
+use super::{
+    COMMAN_SYSTEM_ID, KeyId, KeyIdSystemType, PLAYREADY_SYSTEM_ID, WIDEVINE_SYSTEM_ID,
+    playready::{self, PlayReadyData},
+    widevine::{self, WidevineData},
+};
+use crate::{Error, Result, parser::ParsedBox};
+use base64::Engine;
+
+/// Single `pssh` box.
+#[derive(Clone, Debug)]
+pub struct PsshBox {
+    /// System specific data.
+    pub data: Vec<u8>,
+    pub flags: u32,
+    /// Key ids present in box header (version 1 only), in hex.
+    pub key_ids: Vec<String>,
+    /// In hex.
+    pub system_id: String,
+    pub version: u8,
+}
+
+/// Decoded system specific data of `pssh` box.
+#[derive(Clone, Debug)]
+pub enum PsshData {
+    PlayReady(PlayReadyData),
+    Widevine(WidevineData),
+    Other(Vec<u8>),
+}
+
+impl PsshBox {
+    /// Create a new `pssh` box builder, `system_id` should be in hex.
+    pub fn builder(system_id: &str) -> PsshBuilder {
+        PsshBuilder::new(system_id)
+    }
+
+    /// Parse `pssh` box from its full box parsed by `Mp4Parser`.
+    pub(super) fn parse(_box: &mut ParsedBox) -> Result<Self> {
+        let version = _box.version.ok_or(Error::new(
+            "PSSH boxes are full boxes and must have a valid version.",
+        ))? as u8;
+        let flags = _box.flags.ok_or(Error::new(
+            "PSSH boxes are full boxes and must have a valid flag.",
+        ))?;
+
+        let system_id = hex::encode(
+            _box.reader
+                .read_bytes_u8(16)
+                .map_err(|_| Error::new_read("PSSH box system id (16 bytes)."))?,
+        );
+        let mut key_ids = vec![];
+
+        if version > 0 {
+            let num_key_ids = _box
+                .reader
+                .read_u32()
+                .map_err(|_| Error::new_read("PSSH box number of key ids (u32)."))?;
+
+            for _ in 0..num_key_ids {
+                key_ids.push(hex::encode(
+                    _box.reader
+                        .read_bytes_u8(16)
+                        .map_err(|_| Error::new_read("PSSH box key id (16 bytes)."))?,
+                ));
+            }
+        }
+
+        let data_size = _box
+            .reader
+            .read_u32()
+            .map_err(|_| Error::new_read("PSSH box data size (u32)."))?;
+        let data = _box
+            .reader
+            .read_bytes_u8(data_size as usize)
+            .map_err(|_| Error::new_read(format!("PSSH box data ({data_size} bytes).")))?;
+
+        Ok(Self {
+            data,
+            flags,
+            key_ids,
+            system_id,
+            version,
+        })
+    }
+
+    pub fn system_type(&self) -> KeyIdSystemType {
+        match self.system_id.as_str() {
+            COMMAN_SYSTEM_ID => KeyIdSystemType::Comman,
+            PLAYREADY_SYSTEM_ID => KeyIdSystemType::PlayReady,
+            WIDEVINE_SYSTEM_ID => KeyIdSystemType::WideVine,
+            x => KeyIdSystemType::Other(x.to_owned()),
+        }
+    }
+
+    /// Decode system specific data of widevine and playready `pssh` boxes.
+    pub fn decode(&self) -> Result<PsshData> {
+        Ok(match self.system_id.as_str() {
+            PLAYREADY_SYSTEM_ID => PsshData::PlayReady(playready::parse(&self.data)?),
+            WIDEVINE_SYSTEM_ID => PsshData::Widevine(widevine::parse(&self.data)?),
+            _ => PsshData::Other(self.data.clone()),
+        })
+    }
+
+    /// Key ids from box header as well as decoded system specific data.
+    pub fn all_key_ids(&self) -> Result<Vec<KeyId>> {
+        let header_system_type = match self.system_id.as_str() {
+            COMMAN_SYSTEM_ID => KeyIdSystemType::Comman,
+            x => KeyIdSystemType::Other(x.to_owned()),
+        };
+        let mut key_ids = self
+            .key_ids
+            .iter()
+            .map(|x| KeyId {
+                system_type: header_system_type.clone(),
+                value: x.to_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        let (system_type, data_key_ids) = match self.decode()? {
+            PsshData::PlayReady(x) => (KeyIdSystemType::PlayReady, x.key_ids),
+            PsshData::Widevine(x) => (KeyIdSystemType::WideVine, x.key_ids),
+            PsshData::Other(_) => return Ok(key_ids),
+        };
+
+        key_ids.extend(data_key_ids.into_iter().map(|x| KeyId {
+            system_type: system_type.clone(),
+            value: x,
+        }));
+        Ok(key_ids)
+    }
+
+    /// Serialize as full `pssh` box.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let system_id = hex::decode(&self.system_id)
+            .ok()
+            .filter(|x| x.len() == 16)
+            .ok_or(Error::new(format!(
+                "invalid PSSH box system id {} (expected 16 bytes hex).",
+                self.system_id
+            )))?;
+
+        let mut payload = vec![];
+        payload.extend((((self.version as u32) << 24) | (self.flags & 0xffffff)).to_be_bytes());
+        payload.extend(system_id);
+
+        if self.version > 0 {
+            payload.extend((self.key_ids.len() as u32).to_be_bytes());
+
+            for key_id in &self.key_ids {
+                let key_id = hex::decode(key_id.replace('-', ""))
+                    .ok()
+                    .filter(|x| x.len() == 16)
+                    .ok_or(Error::new(format!(
+                        "invalid PSSH box key id {key_id} (expected 16 bytes hex)."
+                    )))?;
+                payload.extend(key_id);
+            }
+        }
+
+        payload.extend((self.data.len() as u32).to_be_bytes());
+        payload.extend(&self.data);
+
+        let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend(b"pssh");
+        bytes.extend(payload);
+        Ok(bytes)
+    }
+
+    /// Serialize as full `pssh` box encoded in base64.
+    pub fn to_base64(&self) -> Result<String> {
+        Ok(base64::engine::general_purpose::STANDARD.encode(self.to_bytes()?))
+    }
+}
+
+/// Builder for serializing `pssh` boxes.
+#[derive(Clone, Debug, Default)]
+pub struct PsshBuilder {
+    data: Vec<u8>,
+    key_ids: Vec<String>,
+    system_id: String,
+    version: Option<u8>,
+}
+
+impl PsshBuilder {
+    /// Create a new builder, `system_id` should be in hex.
+    pub fn new(system_id: &str) -> Self {
+        Self {
+            system_id: system_id.replace('-', "").to_lowercase(),
+            ..Default::default()
+        }
+    }
+
+    /// Set system specific data.
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Add a key id (in hex) to box header.
+    pub fn key_id(mut self, key_id: &str) -> Self {
+        self.key_ids.push(key_id.replace('-', "").to_lowercase());
+        self
+    }
+
+    /// Set system specific data as encoded playready object containing a WRM header.
+    pub fn playready(self, wrm_header: &str) -> Self {
+        let record = wrm_header
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut data = ((record.len() + 10) as u32).to_le_bytes().to_vec();
+        data.extend(1_u16.to_le_bytes());
+        data.extend(1_u16.to_le_bytes());
+        data.extend((record.len() as u16).to_le_bytes());
+        data.extend(record);
+        self.data(data)
+    }
+
+    /// Set system specific data as encoded widevine protobuf message.
+    pub fn widevine(self, widevine: &WidevineData) -> Result<Self> {
+        Ok(self.data(widevine.to_bytes()?))
+    }
+
+    /// Set box version, defaults to 1 if any key id is added otherwise 0.
+    pub fn version(mut self, version: u8) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn build(self) -> Result<PsshBox> {
+        let version = self
+            .version
+            .unwrap_or(if self.key_ids.is_empty() { 0 } else { 1 });
+
+        if version > 1 {
+            return Err(Error::new(format!(
+                "unsupported PSSH box version {version}."
+            )));
+        }
+
+        let pssh = PsshBox {
+            data: self.data,
+            flags: 0,
+            key_ids: self.key_ids,
+            system_id: self.system_id,
+            version,
+        };
+
+        // Validates system id and key ids.
+        pssh.to_bytes()?;
+        Ok(pssh)
+    }
+}
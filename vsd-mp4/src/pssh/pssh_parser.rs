@@ -9,18 +9,14 @@
 
 */
 
-use super::{playready, widevine};
+use super::PsshBox;
 use crate::{
     parser,
     parser::{Mp4Parser, ParsedBox},
-    Error, Result,
+    Result,
 };
 use std::sync::{Arc, Mutex};
 
-const COMMAN_SYSTEM_ID: &str = "1077efecc0b24d02ace33c1e52e2fb4b";
-const PLAYREADY_SYSTEM_ID: &str = "9a04f07998404286ab92e65be0885f95";
-const WIDEVINE_SYSTEM_ID: &str = "edef8ba979d64acea3c827dcd51d21ed";
-
 /// Key id parsed from `pssh` box.
 #[derive(Clone)]
 pub struct KeyId {
@@ -67,6 +63,9 @@ impl std::fmt::Display for KeyIdSystemType {
 
 /// Parse `PSSH` box from mp4 files.
 pub struct Pssh {
+    /// All `pssh` boxes in order of their occurrence.
+    pub boxes: Vec<PsshBox>,
+    /// De-duplicated key ids from all boxes.
     pub key_ids: Vec<KeyId>,
     /// In hex.
     pub system_ids: Vec<String>,
@@ -75,6 +74,7 @@ pub struct Pssh {
 impl Pssh {
     pub fn new(data: &[u8]) -> Result<Self> {
         let pssh = Arc::new(Mutex::new(Self {
+            boxes: vec![],
             system_ids: vec![],
             key_ids: vec![],
         }));
@@ -99,87 +99,24 @@ impl Pssh {
         }
 
         Ok(Self {
+            boxes: pssh.boxes.clone(),
             key_ids,
             system_ids: pssh.system_ids.clone(),
         })
     }
 
     fn parse_pssh_box(&mut self, _box: &mut ParsedBox) -> Result<()> {
-        if _box.version.is_none() {
-            return Err(Error::new(
-                "PSSH boxes are full boxes and must have a valid version.",
-            ));
-        }
-
-        if _box.flags.is_none() {
-            return Err(Error::new(
-                "PSSH boxes are full boxes and must have a valid flag.",
-            ));
-        }
-
-        let _box_version = _box.version.unwrap();
-
-        if _box_version > 1 {
+        // Boxes of unrecognized versions are skipped before their payload is read.
+        if _box.version.is_some_and(|x| x > 1) {
             // println!("Unrecognized PSSH version found!");
             return Ok(());
         }
 
-        // The "reader" gives us a view on the payload of the box.  Create a new
-        // view that contains the whole box.
-        // let mut data_view = _box.reader.clone();
-        // assert!(
-        //     data_view.get_position() >= 12,
-        //     "DataView at incorrect position"
-        // );
-        // self.data = view(_box.reader.clone(), - 12, _box.size as i64);
-
-        let system_id = hex::encode(
-            _box.reader
-                .read_bytes_u8(16)
-                .map_err(|_| Error::new_read("PSSH box system id (16 bytes)."))?,
-        );
-
-        if _box_version > 0 {
-            let num_key_ids = _box
-                .reader
-                .read_u32()
-                .map_err(|_| Error::new_read("PSSH box number of key ids (u32)."))?;
-
-            for _ in 0..num_key_ids {
-                let key_id = hex::encode(
-                    _box.reader
-                        .read_bytes_u8(16)
-                        .map_err(|_| Error::new_read("PSSH box key id (16 bytes)."))?,
-                );
-                self.key_ids.push(KeyId {
-                    value: key_id,
-                    system_type: if system_id == COMMAN_SYSTEM_ID {
-                        KeyIdSystemType::Comman
-                    } else {
-                        KeyIdSystemType::Other(system_id.clone())
-                    },
-                });
-            }
-        }
-
-        let pssh_data_size = _box
-            .reader
-            .read_u32()
-            .map_err(|_| Error::new_read("PSSH box data size (u32)."))?;
-        let pssh_data = _box
-            .reader
-            .read_bytes_u8(pssh_data_size as usize)
-            .map_err(|_| {
-                Error::new_read(format!("PSSH box data ({pssh_data_size} bytes)."))
-            })?;
-
-        match system_id.as_str() {
-            PLAYREADY_SYSTEM_ID => self.key_ids.extend(playready::parse(&pssh_data)?),
-            WIDEVINE_SYSTEM_ID => self.key_ids.extend(widevine::parse(&pssh_data)?),
-            _ => (),
-        }
+        let pssh_box = PsshBox::parse(_box)?;
 
-        self.system_ids.push(system_id);
+        self.key_ids.extend(pssh_box.all_key_ids()?);
+        self.system_ids.push(pssh_box.system_id.clone());
+        self.boxes.push(pssh_box);
 
         Ok(())
     }
@@ -1,23 +1,67 @@
-use super::{KeyId, KeyIdSystemType};
 use crate::{Error, Result};
 use prost::Message;
 
 include!(concat!(env!("OUT_DIR"), "/widevine.rs"));
 
-pub(super) fn parse(data: &[u8]) -> Result<impl IntoIterator<Item = KeyId>> {
+/// Decoded widevine data present inside `pssh` box data.
+#[derive(Clone, Debug, Default)]
+pub struct WidevineData {
+    pub content_id: Option<Vec<u8>>,
+    /// In hex.
+    pub key_ids: Vec<String>,
+    /// Protection scheme i.e. `cenc`, `cbc1`, `cens` or `cbcs`.
+    pub protection_scheme: Option<String>,
+    pub provider: Option<String>,
+}
+
+impl WidevineData {
+    /// Encode as widevine protobuf message which can be used as `pssh` box data.
+    #[allow(deprecated)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut key_ids = vec![];
+
+        for key_id in &self.key_ids {
+            key_ids.push(
+                hex::decode(key_id)
+                    .map_err(|_| Error::new_decode(format!("{key_id} key id as valid hex.")))?,
+            );
+        }
+
+        let protection_scheme = match &self.protection_scheme {
+            Some(x) if x.len() == 4 => Some(u32::from_be_bytes(x.as_bytes().try_into().unwrap())),
+            Some(x) => {
+                return Err(Error::new(format!(
+                    "invalid widevine protection scheme {x} (expected 4 characters)."
+                )));
+            }
+            None => None,
+        };
+
+        Ok(WidevinePsshData {
+            content_id: self.content_id.clone(),
+            key_ids,
+            protection_scheme,
+            provider: self.provider.clone(),
+            ..Default::default()
+        }
+        .encode_to_vec())
+    }
+}
+
+#[allow(deprecated)]
+pub(super) fn parse(data: &[u8]) -> Result<WidevineData> {
     let wv = WidevinePsshData::decode(data).map_err(|x| {
         Error::new_decode(format!("PSSH box data as valid widevine data.\n\n{x:#?}"))
     })?;
 
-    // let protection_scheme = String::from_utf8(wv.protection_scheme().to_be_bytes().to_vec())
-    //     .map_err(|_| {
-    //         Error::new_decode_err(
-    //             "PSSH box widevine protection_scheme as valid utf-8 data (big endian)",
-    //         )
-    //     })?;
-
-    Ok(wv.key_ids.into_iter().map(|x| KeyId {
-        system_type: KeyIdSystemType::WideVine,
-        value: hex::encode(x),
-    }))
+    let protection_scheme = wv
+        .protection_scheme
+        .map(|x| String::from_utf8_lossy(&x.to_be_bytes()).to_string());
+
+    Ok(WidevineData {
+        content_id: wv.content_id,
+        key_ids: wv.key_ids.into_iter().map(hex::encode).collect(),
+        protection_scheme,
+        provider: wv.provider,
+    })
 }
@@ -11,13 +11,19 @@ use crate::{Error, Reader, Result};
 pub(super) struct TFHDBox {
     /// As per the spec: an integer that uniquely identifies this
     /// track over the entire life‐time of this presentation
-    pub(super) _track_id: u32,
+    #[cfg_attr(not(feature = "text-cea"), allow(dead_code))]
+    pub(super) track_id: u32,
     /// If specified via flags, this overrides the default sample
     /// duration in the Track Extends Box for this fragment
+    #[cfg_attr(not(any(feature = "text-cea", feature = "text-vtt")), allow(dead_code))]
     pub(super) default_sample_duration: Option<u32>,
     /// If specified via flags, this overrides the default sample
     /// size in the Track Extends Box for this fragment
-    pub(super) _default_sample_size: Option<u32>,
+    #[cfg_attr(
+        not(any(feature = "text-cea", feature = "text-ttml")),
+        allow(dead_code)
+    )]
+    pub(super) default_sample_size: Option<u32>,
     /// If specified via flags, this indicate the base data offset
     pub(super) _base_data_offset: Option<u64>,
 }
@@ -68,20 +74,22 @@ impl TFHDBox {
         }
 
         Ok(Self {
-            _track_id: track_id,
+            track_id,
             default_sample_duration,
-            _default_sample_size: default_sample_size,
+            default_sample_size,
             _base_data_offset: base_data_offset,
         })
     }
 }
 
+#[cfg(any(feature = "text-cea", feature = "text-vtt"))]
 pub(super) struct TFDTBox {
     /// As per the spec: the absolute decode time, measured on the media
     /// timeline, of the first sample in decode order in the track fragment
     pub(super) base_media_decode_time: u64,
 }
 
+#[cfg(any(feature = "text-cea", feature = "text-vtt"))]
 impl TFDTBox {
     /// Parses a TFDT Box.
     pub(super) fn parse(reader: &mut Reader, version: u32) -> Result<Self> {
@@ -100,6 +108,36 @@ impl TFDTBox {
     }
 }
 
+#[cfg(feature = "text-cea")]
+pub(super) struct TKHDBox {
+    /// As per the spec: an integer that uniquely identifies this track over the
+    /// entire life‐time of this presentation
+    pub(super) track_id: u32,
+}
+
+#[cfg(feature = "text-cea")]
+impl TKHDBox {
+    /// Parses a TKHD Box.
+    pub(super) fn parse(reader: &mut Reader, version: u32) -> Result<Self> {
+        if version == 1 {
+            reader.skip(16).map_err(|_| {
+                Error::new_read("TKHD box creation and modification time data (16 bytes).")
+            })?;
+        } else {
+            reader.skip(8).map_err(|_| {
+                Error::new_read("TKHD box creation and modification time data (8 bytes).")
+            })?;
+        }
+
+        Ok(Self {
+            track_id: reader
+                .read_u32()
+                .map_err(|_| Error::new_read("TKHD box track id (u32)."))?,
+        })
+    }
+}
+
+#[cfg(any(feature = "text-cea", feature = "text-vtt"))]
 pub(super) struct MDHDBox {
     /// As per the spec: an integer that specifies the time‐scale for this media;
     /// this is the number of time units that pass in one second
@@ -108,6 +146,7 @@ pub(super) struct MDHDBox {
     pub(super) _language: String,
 }
 
+#[cfg(any(feature = "text-cea", feature = "text-vtt"))]
 impl MDHDBox {
     /// Parses a MDHD Box.
     pub(super) fn parse(reader: &mut Reader, version: u32) -> Result<Self> {
@@ -162,7 +201,8 @@ pub(super) struct TRUNBox {
     /// An array of size sampleCount containing data for each sample
     pub(super) sample_data: Vec<TRUNSample>,
     /// If specified via flags, this indicate the offset of the sample in bytes.
-    pub(super) _data_offset: Option<u32>,
+    #[cfg_attr(not(feature = "text-cea"), allow(dead_code))]
+    pub(super) data_offset: Option<u32>,
 }
 
 impl TRUNBox {
@@ -242,7 +282,7 @@ impl TRUNBox {
         Ok(Self {
             _sample_count: sample_count,
             sample_data,
-            _data_offset: data_offset,
+            data_offset,
         })
     }
 }
@@ -258,3 +298,62 @@ pub(super) struct TRUNSample {
     /// be used to create the start time.
     pub(super) sample_composition_time_offset: Option<i32>,
 }
+
+#[cfg(feature = "text-ttml")]
+pub(super) struct SUBSBox {
+    /// Sizes of subsamples of every sample which has subsamples.
+    pub(super) entries: Vec<SUBSEntry>,
+}
+
+#[cfg(feature = "text-ttml")]
+impl SUBSBox {
+    /// Parses a SUBS Box.
+    pub(super) fn parse(reader: &mut Reader, version: u32) -> Result<Self> {
+        let entry_count = reader
+            .read_u32()
+            .map_err(|_| Error::new_read("SUBS box entry count (u32)."))?;
+        let mut entries = vec![];
+
+        for _ in 0..entry_count {
+            let sample_delta = reader
+                .read_u32()
+                .map_err(|_| Error::new_read("SUBS box sample delta (u32)."))?;
+            let subsample_count = reader
+                .read_u16()
+                .map_err(|_| Error::new_read("SUBS box subsample count (u16)."))?;
+            let mut subsample_sizes = vec![];
+
+            for _ in 0..subsample_count {
+                subsample_sizes.push(if version == 1 {
+                    reader
+                        .read_u32()
+                        .map_err(|_| Error::new_read("SUBS box subsample size (u32)."))?
+                } else {
+                    reader
+                        .read_u16()
+                        .map_err(|_| Error::new_read("SUBS box subsample size (u16)."))?
+                        as u32
+                });
+
+                // Skip "subsample_priority", "discardable" and "codec_specific_parameters".
+                reader
+                    .skip(6)
+                    .map_err(|_| Error::new_read("SUBS box subsample parameters (6 bytes)."))?;
+            }
+
+            entries.push(SUBSEntry {
+                sample_delta,
+                subsample_sizes,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(feature = "text-ttml")]
+pub(super) struct SUBSEntry {
+    /// Difference between number of this sample and previous sample having subsamples.
+    pub(super) sample_delta: u32,
+    pub(super) subsample_sizes: Vec<u32>,
+}
@@ -0,0 +1,387 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/cea608_data_channel.js
+    2. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/cea608_memory.js
+    3. https://en.wikipedia.org/wiki/EIA-608
+
+*/
+
+use super::{
+    Cue,
+    cea_decoder::{Grid, Pen, StyledChar, build_cue, is_empty},
+};
+
+const ROWS: usize = 15;
+const COLUMNS: usize = 32;
+
+/// Colors used by preamble address and mid-row codes, `None` is white.
+const COLORS: [Option<&str>; 7] = [
+    None,
+    Some("lime"),
+    Some("blue"),
+    Some("cyan"),
+    Some("red"),
+    Some("yellow"),
+    Some("magenta"),
+];
+
+/// Rows (1 based) set by preamble address codes, indexed by lower three bits of first byte.
+const PAC_ROWS: [usize; 8] = [11, 1, 3, 12, 14, 5, 7, 9];
+
+/// A CEA-608 field, which carries two caption channels i.e. `CC1` and `CC2` in first field,
+/// `CC3` and `CC4` in second field.
+#[derive(Default)]
+pub(super) struct Cea608Field {
+    channels: [Cea608Channel; 2],
+    /// Channel selected by last control code.
+    current: usize,
+    /// Last control code, control codes are usually sent twice for redundancy.
+    last_control: Option<(u8, u8)>,
+    /// Extended data services are being received, only present in second field.
+    xds: bool,
+}
+
+impl Cea608Field {
+    /// Decode a byte pair without parity bits.
+    pub(super) fn decode(&mut self, data1: u8, data2: u8, time: f64) {
+        // Padding.
+        if data1 == 0 && data2 == 0 {
+            return;
+        }
+
+        match data1 {
+            0x01..=0x0f => {
+                // 0x0f marks end of extended data services packet.
+                self.xds = data1 != 0x0f;
+                self.last_control = None;
+            }
+            0x10..=0x1f => {
+                self.xds = false;
+
+                if self.last_control == Some((data1, data2)) {
+                    self.last_control = None;
+                    return;
+                }
+
+                self.last_control = Some((data1, data2));
+                self.current = ((data1 & 0x08) >> 3) as usize;
+                self.channels[self.current].control(data1 & 0xf7, data2, time);
+            }
+            _ => {
+                self.last_control = None;
+
+                if self.xds {
+                    return;
+                }
+
+                let channel = &mut self.channels[self.current];
+                channel.write(basic_char(data1), time);
+
+                if data2 >= 0x20 {
+                    channel.write(basic_char(data2), time);
+                }
+            }
+        }
+    }
+
+    /// Cues of both channels, captions which are still displayed end at given time.
+    pub(super) fn finish(self, time: f64) -> [Vec<Cue>; 2] {
+        self.channels.map(|mut x| {
+            x.emit(time);
+            x.cues
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Mode {
+    PaintOn,
+    #[default]
+    PopOn,
+    /// Number of rows used by roll-up captions.
+    RollUp(usize),
+    /// Text mode data isn't used as captions.
+    Text,
+}
+
+struct Cea608Channel {
+    column: usize,
+    cues: Vec<Cue>,
+    displayed: Grid,
+    mode: Mode,
+    non_displayed: Grid,
+    pen: Pen,
+    row: usize,
+    /// Time since which displayed memory is shown.
+    since: Option<f64>,
+}
+
+impl Default for Cea608Channel {
+    fn default() -> Self {
+        Self {
+            column: 0,
+            cues: vec![],
+            displayed: empty_grid(),
+            mode: Mode::default(),
+            non_displayed: empty_grid(),
+            pen: Pen::default(),
+            row: ROWS - 1,
+            since: None,
+        }
+    }
+}
+
+impl Cea608Channel {
+    /// Handle control code, channel bit of first byte should be removed.
+    fn control(&mut self, data1: u8, data2: u8, time: f64) {
+        match (data1, data2) {
+            // Miscellaneous control codes, 0x15 is used in second field.
+            (0x14 | 0x15, 0x20..=0x2f) => self.misc_control(data2, time),
+            // Mid-row codes.
+            (0x11, 0x20..=0x2f) => {
+                let style = (data2 & 0x0e) >> 1;
+
+                if style == 7 {
+                    self.pen.italic = true;
+                } else {
+                    self.pen.color = COLORS[style as usize];
+                    self.pen.italic = false;
+                }
+
+                self.pen.underline = data2 & 0x01 != 0;
+                self.write(' ', time);
+            }
+            // Special north american characters.
+            (0x11, 0x30..=0x3f) => self.write(special_char(data2), time),
+            // Extended western european characters, which replace previously sent standard character.
+            (0x12 | 0x13, 0x20..=0x3f) => {
+                self.column = self.column.saturating_sub(1);
+                self.write(extended_char(data1, data2), time);
+            }
+            // Tab offsets.
+            (0x17, 0x21..=0x23) => {
+                self.column = (self.column + (data2 - 0x20) as usize).min(COLUMNS - 1);
+            }
+            // Preamble address codes.
+            (0x10..=0x17, 0x40..=0x7f) => self.preamble_address(data1, data2),
+            // Background and foreground attribute codes are not supported.
+            _ => (),
+        }
+    }
+
+    fn misc_control(&mut self, code: u8, time: f64) {
+        match code {
+            // Resume caption loading.
+            0x20 => self.set_mode(Mode::PopOn, time),
+            // Backspace.
+            0x21 => {
+                self.column = self.column.saturating_sub(1);
+                let (row, column) = (self.row, self.column);
+
+                if let Some(memory) = self.memory() {
+                    memory[row][column] = None;
+                }
+            }
+            // Delete to end of row.
+            0x24 => {
+                let (row, column) = (self.row, self.column);
+
+                if let Some(memory) = self.memory() {
+                    memory[row][column..].fill(None);
+                }
+            }
+            // Roll-up captions with 2, 3 or 4 rows.
+            0x25..=0x27 => self.set_mode(Mode::RollUp((code - 0x23) as usize), time),
+            // Resume direct captioning.
+            0x29 => self.set_mode(Mode::PaintOn, time),
+            // Text restart and resume text display.
+            0x2a | 0x2b => self.set_mode(Mode::Text, time),
+            // Erase displayed memory.
+            0x2c => {
+                self.emit(time);
+                self.displayed = empty_grid();
+            }
+            // Carriage return.
+            0x2d => {
+                if let Mode::RollUp(rows) = self.mode {
+                    self.emit(time);
+                    let top = (self.row + 1).saturating_sub(rows);
+                    self.displayed[top..=self.row].rotate_left(1);
+                    self.displayed[self.row] = vec![None; COLUMNS];
+                    self.show(time);
+                }
+
+                self.column = 0;
+            }
+            // Erase non-displayed memory.
+            0x2e => self.non_displayed = empty_grid(),
+            // End of caption i.e. flip memories.
+            0x2f => {
+                self.emit(time);
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.mode = Mode::PopOn;
+                self.show(time);
+            }
+            // Alarm and flash codes are ignored.
+            _ => (),
+        }
+    }
+
+    fn preamble_address(&mut self, data1: u8, data2: u8) {
+        let row = PAC_ROWS[(data1 & 0x07) as usize] + ((data2 & 0x20) >> 5) as usize - 1;
+
+        // Roll-up window moves along with base row.
+        if let Mode::RollUp(rows) = self.mode
+            && row != self.row
+        {
+            let mut displayed = empty_grid();
+
+            for i in 0..rows.min(row + 1).min(self.row + 1) {
+                displayed[row - i] = std::mem::take(&mut self.displayed[self.row - i]);
+            }
+
+            self.displayed = displayed;
+        }
+
+        self.row = row;
+        self.column = 0;
+        self.pen = Pen {
+            underline: data2 & 0x01 != 0,
+            ..Default::default()
+        };
+
+        let attribute = (data2 & 0x0e) >> 1;
+
+        if data2 & 0x10 != 0 {
+            // Indent in multiples of 4 columns.
+            self.column = attribute as usize * 4;
+        } else if attribute == 7 {
+            self.pen.italic = true;
+        } else {
+            self.pen.color = COLORS[attribute as usize];
+        }
+    }
+
+    fn set_mode(&mut self, mode: Mode, time: f64) {
+        if let Mode::RollUp(_) = mode
+            && !matches!(self.mode, Mode::RollUp(_))
+        {
+            // Switching to roll-up captions erases both memories.
+            self.emit(time);
+            self.displayed = empty_grid();
+            self.non_displayed = empty_grid();
+            self.row = ROWS - 1;
+            self.column = 0;
+        }
+
+        self.mode = mode;
+    }
+
+    /// Memory which characters are written to in current mode.
+    fn memory(&mut self) -> Option<&mut Grid> {
+        match self.mode {
+            Mode::PaintOn | Mode::RollUp(_) => Some(&mut self.displayed),
+            Mode::PopOn => Some(&mut self.non_displayed),
+            Mode::Text => None,
+        }
+    }
+
+    fn write(&mut self, character: char, time: f64) {
+        let (row, column, pen) = (self.row, self.column, self.pen);
+
+        let Some(memory) = self.memory() else {
+            return;
+        };
+
+        memory[row][column] = Some(StyledChar { character, pen });
+        self.column = (column + 1).min(COLUMNS - 1);
+
+        if self.mode != Mode::PopOn {
+            self.show(time);
+        }
+    }
+
+    /// Start showing displayed memory (if not empty) from given time.
+    fn show(&mut self, time: f64) {
+        if self.since.is_none() && !is_empty(&self.displayed) {
+            self.since = Some(time);
+        }
+    }
+
+    /// End currently shown captions at given time.
+    fn emit(&mut self, time: f64) {
+        let Some(since) = self.since.take() else {
+            return;
+        };
+
+        let rows = self
+            .displayed
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.iter().any(|y| y.is_some()))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let line = match (rows.first(), rows.last()) {
+            // Captions in upper half are positioned from top.
+            (Some(&top), _) if top < ROWS / 2 => Some((top as f32 * 100.0 / ROWS as f32).round()),
+            // Captions which are not at the bottom are positioned from bottom.
+            (_, Some(&bottom)) if bottom < ROWS - 2 => {
+                Some(((bottom + 1) as f32 * 100.0 / ROWS as f32).round())
+            }
+            _ => None,
+        };
+
+        if let Some(cue) = build_cue(&self.displayed, since, time, line) {
+            self.cues.push(cue);
+        }
+    }
+}
+
+fn empty_grid() -> Grid {
+    vec![vec![None; COLUMNS]; ROWS]
+}
+
+/// Standard characters, mostly same as ascii.
+fn basic_char(byte: u8) -> char {
+    match byte {
+        0x2a => 'á',
+        0x5c => 'é',
+        0x5e => 'í',
+        0x5f => 'ó',
+        0x60 => 'ú',
+        0x7b => 'ç',
+        0x7c => '÷',
+        0x7d => 'Ñ',
+        0x7e => 'ñ',
+        0x7f => '█',
+        _ => byte as char,
+    }
+}
+
+fn special_char(byte: u8) -> char {
+    [
+        '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+    ][(byte - 0x30) as usize]
+}
+
+fn extended_char(data1: u8, data2: u8) -> char {
+    // Spanish, miscellaneous and french characters.
+    const SET1: [char; 32] = [
+        'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '─', '©', '℠', '•', '“', '”', 'À', 'Â',
+        'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+    ];
+    // Portuguese, german and danish characters.
+    const SET2: [char; 32] = [
+        'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä',
+        'Ö', 'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+    ];
+
+    if data1 == 0x12 {
+        SET1[(data2 - 0x20) as usize]
+    } else {
+        SET2[(data2 - 0x20) as usize]
+    }
+}
@@ -0,0 +1,392 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/cea708_service.js
+    2. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/cea708_window.js
+    3. https://en.wikipedia.org/wiki/CEA-708
+
+*/
+
+use super::{
+    Cue,
+    cea_decoder::{Grid, Pen, StyledChar, build_cue, is_empty},
+};
+
+/// Number of vertical anchor positions when anchor isn't relative.
+const VERTICAL_POSITIONS: f32 = 75.0;
+
+/// A CEA-708 caption service, window attributes other than position are not supported.
+#[derive(Default)]
+pub(super) struct Cea708Service {
+    /// Window selected by last command.
+    current: usize,
+    cues: Vec<Cue>,
+    windows: [Option<Window>; 8],
+}
+
+impl Cea708Service {
+    /// Decode data of a service block.
+    pub(super) fn decode(&mut self, data: &[u8], time: f64) {
+        let mut i = 0;
+
+        while let Some(&code) = data.get(i) {
+            i += 1;
+
+            // Number of parameter bytes used by command.
+            let parameters = match code {
+                // End of text, used for flushing text in some decoders.
+                0x00 | 0x03 => 0,
+                // Backspace.
+                0x08 => {
+                    if let Some(window) = self.window() {
+                        window.column = window.column.saturating_sub(1);
+                        window.set(None);
+                    }
+                    0
+                }
+                // Form feed.
+                0x0c => {
+                    if let Some(window) = self.window() {
+                        window.emit(time);
+                        window.grid = vec![vec![]; window.rows];
+                        (window.row, window.column) = (0, 0);
+                    }
+                    0
+                }
+                // Carriage return.
+                0x0d => {
+                    if let Some(window) = self.window() {
+                        window.carriage_return(time);
+                    }
+                    0
+                }
+                // Horizontal carriage return.
+                0x0e => {
+                    if let Some(window) = self.window() {
+                        window.emit(time);
+                        window.grid[window.row].clear();
+                        window.column = 0;
+                        window.show(time);
+                    }
+                    0
+                }
+                // Extended code set.
+                0x10 => {
+                    let Some(&code) = data.get(i) else {
+                        break;
+                    };
+                    i += 1;
+
+                    match code {
+                        0x00..=0x07 => 0,
+                        0x08..=0x0f => 1,
+                        0x10..=0x17 => 2,
+                        0x18..=0x1f => 3,
+                        0x20..=0x7f => {
+                            if let Some(character) = g2_char(code) {
+                                self.write(character, time);
+                            }
+                            0
+                        }
+                        0x80..=0x87 => 4,
+                        0x88..=0x8f => 5,
+                        // Variable length commands.
+                        0x90..=0x9f => 1 + (data.get(i).unwrap_or(&0) & 0x3f) as usize,
+                        // Closed captions icon.
+                        0xa0 => {
+                            self.write('㏄', time);
+                            0
+                        }
+                        _ => 0,
+                    }
+                }
+                0x01..=0x0f => 0,
+                0x11..=0x17 => 1,
+                0x18..=0x1f => 2,
+                0x20..=0x7e => {
+                    self.write(code as char, time);
+                    0
+                }
+                0x7f => {
+                    self.write('♪', time);
+                    0
+                }
+                // Set current window.
+                0x80..=0x87 => {
+                    self.current = (code & 0x07) as usize;
+                    0
+                }
+                // Clear, display, hide, toggle and delete windows.
+                0x88..=0x8c => {
+                    let Some(&bitmap) = data.get(i) else {
+                        break;
+                    };
+
+                    for j in (0..8).filter(|x| bitmap & (1 << x) != 0) {
+                        let Some(window) = self.windows[j].as_mut() else {
+                            continue;
+                        };
+
+                        match code {
+                            0x88 => {
+                                window.emit(time);
+                                window.grid = vec![vec![]; window.rows];
+                            }
+                            0x89 => window.set_visible(true, time),
+                            0x8a => window.set_visible(false, time),
+                            0x8b => window.set_visible(!window.visible, time),
+                            _ => {
+                                window.emit(time);
+                                self.cues.append(&mut window.cues);
+                                self.windows[j] = None;
+                            }
+                        }
+                    }
+                    1
+                }
+                // Delay.
+                0x8d => 1,
+                // Delay cancel.
+                0x8e => 0,
+                // Reset.
+                0x8f => {
+                    for window in self.windows.iter_mut().flatten() {
+                        window.emit(time);
+                        self.cues.append(&mut window.cues);
+                    }
+
+                    self.windows = Default::default();
+                    0
+                }
+                // Set pen attributes.
+                0x90 => {
+                    if let (Some(attributes), Some(window)) =
+                        (data.get(i + 1).copied(), self.window())
+                    {
+                        window.pen.italic = attributes & 0x80 != 0;
+                        window.pen.underline = attributes & 0x40 != 0;
+                    }
+                    2
+                }
+                // Set pen color.
+                0x91 => {
+                    if let (Some(color), Some(window)) = (data.get(i).copied(), self.window()) {
+                        window.pen.color = pen_color(color);
+                    }
+                    3
+                }
+                // Set pen location.
+                0x92 => {
+                    if let (Some(row), Some(column), Some(window)) = (
+                        data.get(i).copied(),
+                        data.get(i + 1).copied(),
+                        self.window(),
+                    ) {
+                        window.row = ((row & 0x0f) as usize).min(window.rows - 1);
+                        window.column = (column & 0x3f) as usize;
+                    }
+                    2
+                }
+                0x93..=0x96 => 0,
+                // Set window attributes.
+                0x97 => 4,
+                // Define window.
+                0x98..=0x9f => {
+                    let Some(parameters) = data.get(i..(i + 6)) else {
+                        break;
+                    };
+
+                    self.current = (code & 0x07) as usize;
+                    self.windows[self.current]
+                        .get_or_insert_with(Window::default)
+                        .define(parameters, time);
+                    6
+                }
+                0xa0..=0xff => {
+                    self.write(char::from(code), time);
+                    0
+                }
+            };
+
+            i += parameters;
+        }
+    }
+
+    /// Cues of service, windows which are still visible end at given time.
+    pub(super) fn finish(mut self, time: f64) -> Vec<Cue> {
+        for window in self.windows.iter_mut().flatten() {
+            window.emit(time);
+        }
+
+        let mut cues = self
+            .windows
+            .into_iter()
+            .flatten()
+            .flat_map(|x| x.cues)
+            .chain(self.cues)
+            .collect::<Vec<_>>();
+        cues.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+        cues
+    }
+
+    fn window(&mut self) -> Option<&mut Window> {
+        self.windows[self.current].as_mut()
+    }
+
+    fn write(&mut self, character: char, time: f64) {
+        if let Some(window) = self.window() {
+            window.set(Some(character));
+            window.column += 1;
+            window.show(time);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Window {
+    column: usize,
+    cues: Vec<Cue>,
+    grid: Grid,
+    /// Vertical position in percentage of video height.
+    line: Option<f32>,
+    pen: Pen,
+    row: usize,
+    rows: usize,
+    /// Time since which window is shown.
+    since: Option<f64>,
+    visible: bool,
+}
+
+impl Window {
+    fn define(&mut self, parameters: &[u8], time: f64) {
+        let visible = parameters[0] & 0x20 != 0;
+        let relative = parameters[1] & 0x80 != 0;
+        let anchor_vertical = (parameters[1] & 0x7f) as f32;
+        let rows = (parameters[3] & 0x0f) as usize + 1;
+
+        let top = if relative {
+            anchor_vertical
+        } else {
+            anchor_vertical * 100.0 / VERTICAL_POSITIONS
+        };
+
+        // Windows anchored near bottom use default position.
+        self.line = (top < 80.0).then(|| top.round());
+
+        if rows != self.rows {
+            self.grid.resize(rows, vec![]);
+            self.rows = rows;
+            self.row = self.row.min(rows - 1);
+        }
+
+        self.set_visible(visible, time);
+    }
+
+    fn carriage_return(&mut self, time: f64) {
+        self.emit(time);
+
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            // Rows are scrolled up when pen is at last row.
+            self.grid.rotate_left(1);
+            self.grid[self.row].clear();
+        }
+
+        self.column = 0;
+        self.show(time);
+    }
+
+    /// Set character at pen location.
+    fn set(&mut self, character: Option<char>) {
+        let (pen, column) = (self.pen, self.column);
+        let row = &mut self.grid[self.row];
+
+        if row.len() <= column {
+            row.resize(column + 1, None);
+        }
+
+        row[column] = character.map(|character| StyledChar { character, pen });
+    }
+
+    fn set_visible(&mut self, visible: bool, time: f64) {
+        if self.visible && !visible {
+            self.emit(time);
+        }
+
+        self.visible = visible;
+        self.show(time);
+    }
+
+    /// Start showing window (if visible and not empty) from given time.
+    fn show(&mut self, time: f64) {
+        if self.visible && self.since.is_none() && !is_empty(&self.grid) {
+            self.since = Some(time);
+        }
+    }
+
+    /// End currently shown window at given time.
+    fn emit(&mut self, time: f64) {
+        let Some(since) = self.since.take() else {
+            return;
+        };
+
+        if let Some(cue) = build_cue(&self.grid, since, time, self.line) {
+            self.cues.push(cue);
+        }
+    }
+}
+
+/// Map 2 bit red, green and blue components of pen color to webvtt color class.
+fn pen_color(color: u8) -> Option<&'static str> {
+    let component = |x: u8| (x & 0x03) >= 2;
+
+    match (
+        component(color >> 4),
+        component(color >> 2),
+        component(color),
+    ) {
+        (false, false, false) => Some("black"),
+        (false, false, true) => Some("blue"),
+        (false, true, false) => Some("lime"),
+        (false, true, true) => Some("cyan"),
+        (true, false, false) => Some("red"),
+        (true, false, true) => Some("magenta"),
+        (true, true, false) => Some("yellow"),
+        (true, true, true) => None,
+    }
+}
+
+/// Characters of g2 code set.
+fn g2_char(code: u8) -> Option<char> {
+    Some(match code {
+        0x20 => ' ',
+        0x21 => '\u{a0}',
+        0x25 => '…',
+        0x2a => 'Š',
+        0x2c => 'Œ',
+        0x30 => '█',
+        0x31 => '‘',
+        0x32 => '’',
+        0x33 => '“',
+        0x34 => '”',
+        0x35 => '•',
+        0x39 => '™',
+        0x3a => 'š',
+        0x3c => 'œ',
+        0x3d => '℠',
+        0x3f => 'Ÿ',
+        0x76 => '⅛',
+        0x77 => '⅜',
+        0x78 => '⅝',
+        0x79 => '⅞',
+        0x7a => '│',
+        0x7b => '┐',
+        0x7c => '└',
+        0x7d => '─',
+        0x7e => '┘',
+        0x7f => '┌',
+        _ => return None,
+    })
+}
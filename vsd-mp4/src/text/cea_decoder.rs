@@ -0,0 +1,396 @@
+//! Decode CEA-608 and CEA-708 closed captions.
+
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/tree/main/lib/cea
+    2. https://en.wikipedia.org/wiki/EIA-608
+    3. https://en.wikipedia.org/wiki/CEA-708
+
+*/
+
+use super::{
+    Cue, CueStyle, Subtitles, cea608_channel::Cea608Field, cea708_service::Cea708Service,
+    styles::escape,
+};
+use std::collections::BTreeMap;
+
+/// Itu-t t.35 country code of united states.
+const USA_COUNTRY_CODE: u8 = 0xb5;
+/// Atsc provider code.
+const ATSC_PROVIDER_CODE: u16 = 0x0031;
+/// Atsc user identifier i.e. `GA94`.
+const ATSC_USER_IDENTIFIER: &[u8] = b"GA94";
+/// Atsc user data type code of `cc_data` structure.
+const CC_DATA_TYPE_CODE: u8 = 0x03;
+/// Sei payload type of `user_data_registered_itu_t_t35` message.
+const USER_DATA_REGISTERED_ITU_T_T35: usize = 4;
+
+/// Video codecs whose sei nal units can carry closed captions.
+#[derive(Clone, Copy)]
+pub(super) enum VideoCodec {
+    H264,
+    H265,
+}
+
+/// A single `cc_data` byte pair.
+struct Packet {
+    data1: u8,
+    data2: u8,
+    /// `cc_type` i.e. 0 and 1 for CEA-608 fields, 2 and 3 for CEA-708 packet data and start.
+    kind: u8,
+    /// Position of packet in stream, used for keeping order of packets having same pts.
+    order: usize,
+    pts: f64,
+}
+
+/// Decode closed captions carried inside sei nal units of video streams.
+///
+/// Cue times are relative to presentation time of earliest video frame.
+#[derive(Default)]
+pub struct CeaDecoder {
+    packets: Vec<Packet>,
+    start: Option<f64>,
+}
+
+impl CeaDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track presentation time (in seconds) of a video frame.
+    pub(super) fn add_frame(&mut self, pts: f64) {
+        self.start = Some(self.start.map_or(pts, |x| x.min(pts)));
+    }
+
+    /// Extract closed caption packets from sei messages of a nal unit.
+    pub(super) fn process_nal(&mut self, nal: &[u8], codec: VideoCodec, pts: f64) {
+        let (is_sei, header_size) = match codec {
+            VideoCodec::H264 => (nal.first().is_some_and(|x| x & 0x1f == 6), 1),
+            // Prefix and suffix sei nal units.
+            VideoCodec::H265 => (
+                nal.first()
+                    .is_some_and(|x| matches!((x >> 1) & 0x3f, 39 | 40)),
+                2,
+            ),
+        };
+
+        if !is_sei || nal.len() <= header_size {
+            return;
+        }
+
+        let rbsp = remove_emulation_prevention(&nal[header_size..]);
+        let mut i = 0;
+
+        // Last byte contains rbsp trailing bits.
+        while i + 1 < rbsp.len() {
+            let mut payload_type = 0;
+            let mut payload_size = 0;
+
+            while let Some(0xff) = rbsp.get(i) {
+                payload_type += 255;
+                i += 1;
+            }
+
+            payload_type += *rbsp.get(i).unwrap_or(&0) as usize;
+            i += 1;
+
+            while let Some(0xff) = rbsp.get(i) {
+                payload_size += 255;
+                i += 1;
+            }
+
+            payload_size += *rbsp.get(i).unwrap_or(&0) as usize;
+            i += 1;
+
+            let Some(payload) = rbsp.get(i..(i + payload_size)) else {
+                break;
+            };
+
+            if payload_type == USER_DATA_REGISTERED_ITU_T_T35 {
+                self.extract(payload, pts);
+            }
+
+            i += payload_size;
+        }
+    }
+
+    /// Extract `cc_data` packets from `user_data_registered_itu_t_t35` sei message payload.
+    fn extract(&mut self, payload: &[u8], pts: f64) {
+        // country code (1) + provider code (2) + user identifier (4) + user data type code (1)
+        // + flags (1) + em_data (1)
+        if payload.len() < 10
+            || payload[0] != USA_COUNTRY_CODE
+            || u16::from_be_bytes([payload[1], payload[2]]) != ATSC_PROVIDER_CODE
+            || &payload[3..7] != ATSC_USER_IDENTIFIER
+            || payload[7] != CC_DATA_TYPE_CODE
+            // process_cc_data_flag
+            || payload[8] & 0x40 == 0
+        {
+            return;
+        }
+
+        let count = (payload[8] & 0x1f) as usize;
+
+        for triplet in payload[10..].chunks_exact(3).take(count) {
+            // cc_valid
+            if triplet[0] & 0x04 == 0 {
+                continue;
+            }
+
+            self.packets.push(Packet {
+                data1: triplet[1],
+                data2: triplet[2],
+                kind: triplet[0] & 0x03,
+                order: self.packets.len(),
+                pts,
+            });
+        }
+    }
+
+    /// Decode extracted packets into subtitles of every caption channel present.
+    ///
+    /// Channels are named same as hls `INSTREAM-ID` attribute values
+    /// i.e. `CC1` to `CC4` for CEA-608 and `SERVICE1` to `SERVICE63` for CEA-708.
+    pub fn decode(self) -> BTreeMap<String, Subtitles> {
+        let start = self.start.unwrap_or(0.0);
+        let mut packets = self.packets;
+        packets.sort_by(|a, b| a.pts.total_cmp(&b.pts).then(a.order.cmp(&b.order)));
+
+        let mut fields = [Cea608Field::default(), Cea608Field::default()];
+        let mut services = BTreeMap::<u8, Cea708Service>::new();
+        let mut dtvcc_packet = vec![];
+        let mut time = 0.0;
+
+        for packet in packets {
+            time = packet.pts - start;
+
+            match packet.kind {
+                0 | 1 => fields[packet.kind as usize].decode(
+                    packet.data1 & 0x7f,
+                    packet.data2 & 0x7f,
+                    time,
+                ),
+                _ => {
+                    if packet.kind == 3 {
+                        // Incomplete packets are decoded as it is.
+                        process_dtvcc_packet(&dtvcc_packet, &mut services, time);
+                        dtvcc_packet.clear();
+                    } else if dtvcc_packet.is_empty() {
+                        continue;
+                    }
+
+                    dtvcc_packet.extend_from_slice(&[packet.data1, packet.data2]);
+
+                    let packet_size = match dtvcc_packet[0] & 0x3f {
+                        0 => 128,
+                        x => x as usize * 2,
+                    };
+
+                    if dtvcc_packet.len() >= packet_size {
+                        process_dtvcc_packet(&dtvcc_packet[..packet_size], &mut services, time);
+                        dtvcc_packet.clear();
+                    }
+                }
+            }
+        }
+
+        process_dtvcc_packet(&dtvcc_packet, &mut services, time);
+
+        let mut channels = BTreeMap::new();
+
+        for (i, field) in fields.into_iter().enumerate() {
+            for (j, cues) in field.finish(time).into_iter().enumerate() {
+                if !cues.is_empty() {
+                    channels.insert(format!("CC{}", i * 2 + j + 1), Subtitles::new(cues));
+                }
+            }
+        }
+
+        for (number, service) in services {
+            let cues = service.finish(time);
+
+            if !cues.is_empty() {
+                channels.insert(format!("SERVICE{number}"), Subtitles::new(cues));
+            }
+        }
+
+        channels
+    }
+}
+
+/// Split dtvcc packet into service blocks and decode them.
+fn process_dtvcc_packet(packet: &[u8], services: &mut BTreeMap<u8, Cea708Service>, time: f64) {
+    // First byte contains sequence number and packet size.
+    let mut i = 1;
+
+    while let Some(header) = packet.get(i) {
+        let mut number = header >> 5;
+        let size = (header & 0x1f) as usize;
+        i += 1;
+
+        // Extended service number.
+        if number == 7 && size != 0 {
+            let Some(extended) = packet.get(i) else {
+                break;
+            };
+            number = extended & 0x3f;
+            i += 1;
+        }
+
+        // Null service block is used as padding.
+        if number == 0 {
+            break;
+        }
+
+        let Some(block) = packet.get(i..(i + size)) else {
+            break;
+        };
+
+        services.entry(number).or_default().decode(block, time);
+        i += size;
+    }
+}
+
+/// Remove `emulation_prevention_three_byte` i.e. `0x03` of every `0x000003` sequence.
+fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+
+    for &byte in data {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+
+    rbsp
+}
+
+/// Pen attributes of a caption character.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(super) struct Pen {
+    /// Webvtt color class, white is used when it is not set.
+    pub(super) color: Option<&'static str>,
+    pub(super) italic: bool,
+    pub(super) underline: bool,
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct StyledChar {
+    pub(super) character: char,
+    pub(super) pen: Pen,
+}
+
+/// Rows of caption characters, empty cells are `None`.
+pub(super) type Grid = Vec<Vec<Option<StyledChar>>>;
+
+pub(super) fn is_empty(grid: &Grid) -> bool {
+    grid.iter().flatten().all(|x| x.is_none())
+}
+
+/// Build cue from non empty rows of grid, inline tags are used for pen attributes.
+pub(super) fn build_cue(
+    grid: &Grid,
+    start_time: f64,
+    end_time: f64,
+    line: Option<f32>,
+) -> Option<Cue> {
+    if end_time <= start_time {
+        return None;
+    }
+
+    let mut rows = vec![];
+
+    for row in grid {
+        let (Some(first), Some(last)) = (
+            row.iter().position(|x| x.is_some()),
+            row.iter().rposition(|x| x.is_some()),
+        ) else {
+            continue;
+        };
+
+        let mut text = String::new();
+        let mut pen = Pen::default();
+
+        for cell in &row[first..=last] {
+            let cell = cell.unwrap_or(StyledChar {
+                character: ' ',
+                pen,
+            });
+
+            if cell.pen != pen {
+                text += &close_tags(&pen);
+                text += &open_tags(&cell.pen);
+                pen = cell.pen;
+            }
+
+            text += &escape(&cell.character.to_string());
+        }
+
+        text += &close_tags(&pen);
+        let text = text.trim_end();
+
+        if !text.is_empty() {
+            rows.push(text.to_owned());
+        }
+    }
+
+    if rows.is_empty() {
+        return None;
+    }
+
+    let style = CueStyle {
+        line,
+        ..Default::default()
+    };
+
+    Some(Cue {
+        end_time: end_time as f32,
+        _id: String::new(),
+        payload: rows.join("\n"),
+        settings: style.vtt_settings(),
+        start_time: start_time as f32,
+        style,
+    })
+}
+
+fn open_tags(pen: &Pen) -> String {
+    let mut tags = String::new();
+
+    if let Some(color) = pen.color {
+        tags += &format!("<c.{color}>");
+    }
+
+    if pen.italic {
+        tags += "<i>";
+    }
+
+    if pen.underline {
+        tags += "<u>";
+    }
+
+    tags
+}
+
+fn close_tags(pen: &Pen) -> String {
+    let mut tags = String::new();
+
+    if pen.underline {
+        tags += "</u>";
+    }
+
+    if pen.italic {
+        tags += "</i>";
+    }
+
+    if pen.color.is_some() {
+        tags += "</c>";
+    }
+
+    tags
+}
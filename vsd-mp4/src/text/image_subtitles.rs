@@ -0,0 +1,436 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://blog.thescorpius.com/index.php/2017/07/15/presentation-graphic-stream-sup-files-bluray-subtitle-format
+    2. https://github.com/mjuhasz/BDSup2Sub/wiki/Supported-Formats
+
+*/
+
+use super::{CueTiming, png};
+use std::{collections::HashMap, fmt::Write};
+
+#[cfg(feature = "text-ttml")]
+use crate::Result;
+
+/// Palettized image, every pixel is an index into palette of rgba colors.
+/// First palette color is always fully transparent.
+#[derive(Clone)]
+pub struct Bitmap {
+    height: u32,
+    palette: Vec<[u8; 4]>,
+    pixels: Vec<u8>,
+    width: u32,
+}
+
+impl Bitmap {
+    pub(super) fn new(width: u32, height: u32, palette: Vec<[u8; 4]>, pixels: Vec<u8>) -> Self {
+        Self {
+            height,
+            palette,
+            pixels,
+            width,
+        }
+    }
+
+    /// Reduce rgba pixels to atmost 256 colors by dropping lower bits of every channel.
+    pub(super) fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Self {
+        for shift in 0..8 {
+            let mask = 0xff_u8 << shift;
+            let mut palette = vec![[0; 4]];
+            let mut lookup = HashMap::from([([0; 4], 0_u8)]);
+            let mut pixels = Vec::with_capacity(rgba.len() / 4);
+
+            for pixel in rgba.chunks_exact(4) {
+                let color = if pixel[3] == 0 {
+                    [0; 4]
+                } else {
+                    [
+                        pixel[0] & mask,
+                        pixel[1] & mask,
+                        pixel[2] & mask,
+                        pixel[3] & mask,
+                    ]
+                };
+
+                let index = match lookup.get(&color) {
+                    Some(index) => *index,
+                    None if palette.len() < 256 => {
+                        let index = palette.len() as u8;
+                        palette.push(color);
+                        lookup.insert(color, index);
+                        index
+                    }
+                    None => break,
+                };
+
+                pixels.push(index);
+            }
+
+            if pixels.len() == rgba.len() / 4 {
+                return Self::new(width, height, palette, pixels);
+            }
+        }
+
+        Self::new(
+            width,
+            height,
+            vec![[0; 4]],
+            vec![0; (width * height) as usize],
+        )
+    }
+
+    #[cfg(feature = "text-ttml")]
+    pub(super) fn from_png(data: &[u8]) -> Result<Self> {
+        let (width, height, rgba) = png::decode(data)?;
+        Ok(Self::from_rgba(width, height, &rgba))
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Encode bitmap as png image.
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(self.width, self.height, &self.palette, &self.pixels)
+    }
+
+    /// Remove fully transparent borders, offset of cropped bitmap is also returned.
+    /// `None` is returned when every pixel is transparent.
+    pub(super) fn crop(&self) -> Option<(u32, u32, Self)> {
+        let width = self.width as usize;
+        let visible = |i: usize| self.palette[self.pixels[i] as usize][3] != 0;
+        let rows = (0..self.height as usize)
+            .filter(|y| (0..width).any(|x| visible(y * width + x)))
+            .collect::<Vec<_>>();
+        let columns = (0..width)
+            .filter(|x| rows.iter().any(|y| visible(y * width + x)))
+            .collect::<Vec<_>>();
+
+        let (Some(&top), Some(&bottom), Some(&left), Some(&right)) =
+            (rows.first(), rows.last(), columns.first(), columns.last())
+        else {
+            return None;
+        };
+
+        let mut pixels = vec![];
+
+        for y in top..=bottom {
+            pixels.extend_from_slice(&self.pixels[(y * width + left)..=(y * width + right)]);
+        }
+
+        Some((
+            left as u32,
+            top as u32,
+            Self::new(
+                (right - left + 1) as u32,
+                (bottom - top + 1) as u32,
+                self.palette.clone(),
+                pixels,
+            ),
+        ))
+    }
+}
+
+/// A bitmap shown at some position of video frame.
+#[derive(Clone)]
+pub struct ImageCue {
+    pub bitmap: Bitmap,
+    pub end_time: f32,
+    pub start_time: f32,
+    /// Horizontal position of bitmap in video frame.
+    pub x: u32,
+    /// Vertical position of bitmap in video frame.
+    pub y: u32,
+}
+
+/// Image based subtitles builder.
+pub struct ImageSubtitles {
+    cues: Vec<ImageCue>,
+    /// Height of video frame which cues are positioned in.
+    height: u32,
+    /// Width of video frame which cues are positioned in.
+    width: u32,
+}
+
+impl ImageSubtitles {
+    /// Transparent borders of cues are removed and cues are sorted by their start time.
+    pub(super) fn new(width: u32, height: u32, cues: Vec<ImageCue>) -> Self {
+        let mut subtitles = Self {
+            cues: vec![],
+            height,
+            width,
+        };
+        subtitles.extend_cues(cues);
+        subtitles
+    }
+
+    fn extend_cues(&mut self, cues: Vec<ImageCue>) {
+        for cue in cues {
+            if cue.end_time <= cue.start_time {
+                continue;
+            }
+
+            if let Some((x, y, bitmap)) = cue.bitmap.crop() {
+                // Frame is enlarged when bitmaps don't fit inside it.
+                self.width = self.width.max(cue.x + x + bitmap.width);
+                self.height = self.height.max(cue.y + y + bitmap.height);
+                self.cues.push(ImageCue {
+                    bitmap,
+                    x: cue.x + x,
+                    y: cue.y + y,
+                    ..cue
+                });
+            }
+        }
+
+        self.cues
+            .sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    }
+
+    pub fn cues(&self) -> &[ImageCue] {
+        &self.cues
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cues.is_empty()
+    }
+
+    /// Extend these subtitles with another subtitles.
+    pub fn extend(&mut self, other: Self) {
+        self.extend_cues(other.cues);
+    }
+
+    /// End times of cues, a cue is ended when next cue starts as only one image can be shown at once.
+    fn end_times(&self) -> Vec<f32> {
+        self.cues
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match self.cues.get(i + 1) {
+                Some(next) => x.end_time.min(next.start_time),
+                None => x.end_time,
+            })
+            .collect()
+    }
+
+    /// Build subtitles in bdn xml format, which is an index of png images of cues.
+    /// Images are referenced as `0001.png`, `0002.png` and so on in order of cues.
+    pub fn as_bdn_xml(&self, frame_rate: f32) -> String {
+        let timecode = |seconds: f32| {
+            let frames = (seconds * frame_rate).round() as u64;
+            let frame_rate = frame_rate.round().max(1.0) as u64;
+            let seconds = frames / frame_rate;
+            format!(
+                "{:02}:{:02}:{:02}:{:02}",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60,
+                frames % frame_rate
+            )
+        };
+        let end_times = self.end_times();
+        let video_format = match self.height {
+            480 => "480i",
+            576 => "576i",
+            720 => "720p",
+            _ => "1080p",
+        };
+
+        let mut subtitles = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <BDN Version=\"0.93\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:noNamespaceSchemaLocation=\"BD-03-006-0093b BDN File Format.xsd\">\n\
+            \x20 <Description>\n\
+            \x20   <Name Title=\"subtitles\" Content=\"\"/>\n\
+            \x20   <Language Code=\"und\"/>\n\
+            \x20   <Format VideoFormat=\"{video_format}\" FrameRate=\"{frame_rate}\" DropFrame=\"False\"/>\n\
+            \x20   <Events Type=\"Graphic\" FirstEventInTC=\"{}\" LastEventOutTC=\"{}\" NumberofEvents=\"{}\"/>\n\
+            \x20 </Description>\n\
+            \x20 <Events>\n",
+            timecode(self.cues.first().map(|x| x.start_time).unwrap_or(0.0)),
+            timecode(end_times.last().copied().unwrap_or(0.0)),
+            self.cues.len(),
+        );
+
+        for (i, (cue, end_time)) in self.cues.iter().zip(end_times).enumerate() {
+            let _ = write!(
+                subtitles,
+                "    <Event InTC=\"{}\" OutTC=\"{}\" Forced=\"False\">\n\
+                \x20     <Graphic Width=\"{}\" Height=\"{}\" X=\"{}\" Y=\"{}\">{:04}.png</Graphic>\n\
+                \x20   </Event>\n",
+                timecode(cue.start_time),
+                timecode(end_time),
+                cue.bitmap.width,
+                cue.bitmap.height,
+                cue.x,
+                cue.y,
+                i + 1,
+            );
+        }
+
+        subtitles += "  </Events>\n</BDN>\n";
+        subtitles
+    }
+
+    /// Build subtitles in presentation graphic stream (pgs/sup) format.
+    pub fn as_sup(&self) -> Vec<u8> {
+        const PDS: u8 = 0x14;
+        const ODS: u8 = 0x15;
+        const PCS: u8 = 0x16;
+        const WDS: u8 = 0x17;
+        const END: u8 = 0x80;
+
+        let mut sup = vec![];
+        let mut composition_number = 0_u16;
+
+        for (cue, end_time) in self.cues.iter().zip(self.end_times()) {
+            let bitmap = &cue.bitmap;
+            let pts = (cue.start_time * 90000.0) as u32;
+            let window = [
+                &[1, 0][..],
+                &(cue.x as u16).to_be_bytes(),
+                &(cue.y as u16).to_be_bytes(),
+                &(bitmap.width as u16).to_be_bytes(),
+                &(bitmap.height as u16).to_be_bytes(),
+            ]
+            .concat();
+
+            // Epoch start with a single object.
+            let composition = [
+                &self.composition(composition_number, 0x80)[..],
+                &[1, 0, 0, 0, 0],
+                &(cue.x as u16).to_be_bytes(),
+                &(cue.y as u16).to_be_bytes(),
+            ]
+            .concat();
+            write_segment(&mut sup, pts, PCS, &composition);
+            write_segment(&mut sup, pts, WDS, &window);
+
+            let mut palette = vec![0, 0];
+
+            for (i, color) in bitmap.palette.iter().enumerate() {
+                let (y, cr, cb) = ycrcb(color, self.height > 576);
+                palette.extend_from_slice(&[i as u8, y, cr, cb, color[3]]);
+            }
+
+            write_segment(&mut sup, pts, PDS, &palette);
+
+            // Object data is split into fragments when it doesn't fit inside a single segment.
+            let rle = run_length_encode(bitmap);
+            let data = [
+                &((rle.len() + 4) as u32).to_be_bytes()[1..],
+                &(bitmap.width as u16).to_be_bytes(),
+                &(bitmap.height as u16).to_be_bytes(),
+                &rle,
+            ]
+            .concat();
+            let fragments = data.chunks(u16::MAX as usize - 4).collect::<Vec<_>>();
+
+            for (i, fragment) in fragments.iter().enumerate() {
+                let mut flag = 0;
+
+                if i == 0 {
+                    flag |= 0x80;
+                }
+
+                if i == fragments.len() - 1 {
+                    flag |= 0x40;
+                }
+
+                write_segment(&mut sup, pts, ODS, &[&[0, 0, 0, flag], *fragment].concat());
+            }
+
+            write_segment(&mut sup, pts, END, &[]);
+            composition_number = composition_number.wrapping_add(1);
+
+            // Empty composition clears the screen.
+            let pts = (end_time * 90000.0) as u32;
+            let composition = [&self.composition(composition_number, 0x00)[..], &[0]].concat();
+            write_segment(&mut sup, pts, PCS, &composition);
+            write_segment(&mut sup, pts, WDS, &window);
+            write_segment(&mut sup, pts, END, &[]);
+            composition_number = composition_number.wrapping_add(1);
+        }
+
+        sup
+    }
+
+    /// Presentation composition segment data without composition objects.
+    fn composition(&self, number: u16, state: u8) -> Vec<u8> {
+        [
+            &(self.width as u16).to_be_bytes()[..],
+            &(self.height as u16).to_be_bytes(),
+            // frame rate (always 0x10), composition number, state, palette update flag and palette id
+            &[0x10],
+            &number.to_be_bytes(),
+            &[state, 0, 0],
+        ]
+        .concat()
+    }
+}
+
+impl CueTiming for ImageSubtitles {
+    fn retain_times(&mut self, mut f: impl FnMut(&mut f32, &mut f32) -> bool) {
+        self.cues
+            .retain_mut(|x| f(&mut x.start_time, &mut x.end_time));
+    }
+}
+
+fn write_segment(sup: &mut Vec<u8>, pts: u32, kind: u8, data: &[u8]) {
+    sup.extend_from_slice(b"PG");
+    sup.extend_from_slice(&pts.to_be_bytes());
+    // Decoding timestamp is unused.
+    sup.extend_from_slice(&[0; 4]);
+    sup.push(kind);
+    sup.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    sup.extend_from_slice(data);
+}
+
+/// Rgb to limited range y, cr, cb using bt.709 (hd) or bt.601 (sd) coefficients.
+fn ycrcb(color: &[u8; 4], hd: bool) -> (u8, u8, u8) {
+    let (kr, kb) = if hd { (0.2126, 0.0722) } else { (0.299, 0.114) };
+    let (r, g, b) = (color[0] as f32, color[1] as f32, color[2] as f32);
+    let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+    let scale = |x: f32| x.round().clamp(0.0, 255.0) as u8;
+
+    (
+        scale(16.0 + y * 219.0 / 255.0),
+        scale(128.0 + (r - y) / (2.0 * (1.0 - kr)) * 224.0 / 255.0),
+        scale(128.0 + (b - y) / (2.0 * (1.0 - kb)) * 224.0 / 255.0),
+    )
+}
+
+fn run_length_encode(bitmap: &Bitmap) -> Vec<u8> {
+    let mut rle = vec![];
+
+    for row in bitmap.pixels.chunks_exact(bitmap.width.max(1) as usize) {
+        let mut x = 0;
+
+        while x < row.len() {
+            let color = row[x];
+            let mut length = 1;
+
+            while x + length < row.len() && row[x + length] == color && length < 0x3fff {
+                length += 1;
+            }
+
+            match (color, length) {
+                (1.., 1 | 2) => rle.extend(std::iter::repeat_n(color, length)),
+                (0, ..64) => rle.extend_from_slice(&[0, length as u8]),
+                (0, _) => rle.extend_from_slice(&[0, 0x40 | (length >> 8) as u8, length as u8]),
+                (_, ..64) => rle.extend_from_slice(&[0, 0x80 | length as u8, color]),
+                _ => rle.extend_from_slice(&[0, 0xc0 | (length >> 8) as u8, length as u8, color]),
+            }
+
+            x += length;
+        }
+
+        // End of line.
+        rle.extend_from_slice(&[0, 0]);
+    }
+
+    rle
+}
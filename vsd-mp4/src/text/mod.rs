@@ -8,17 +8,83 @@
 
 */
 
+#[cfg(any(feature = "text-cea", feature = "text-ttml", feature = "text-vtt"))]
 mod boxes;
+
+mod styles;
 mod subtitles;
 
-use subtitles::Cue;
+// Used only by text based subtitles parsers.
+#[cfg(any(
+    feature = "text-cea",
+    feature = "text-sami",
+    feature = "text-srt",
+    feature = "text-ttml",
+    feature = "text-vtt"
+))]
+use {styles::CueStyle, subtitles::Cue};
+
+pub use subtitles::{CueTiming, Subtitles};
+
+#[cfg(feature = "text-cea")]
+mod cea608_channel;
+
+#[cfg(feature = "text-cea")]
+mod cea708_service;
+
+#[cfg(feature = "text-cea")]
+mod cea_decoder;
+
+#[cfg(feature = "text-cea")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-cea")))]
+pub use cea_decoder::CeaDecoder;
+
+#[cfg(feature = "text-cea")]
+mod mp4_cea_parser;
+
+#[cfg(feature = "text-cea")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-cea")))]
+pub use mp4_cea_parser::Mp4CeaParser;
+
+#[cfg(any(feature = "text-dvb", feature = "text-ttml"))]
+mod image_subtitles;
+
+#[cfg(any(feature = "text-dvb", feature = "text-ttml"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "text-dvb", feature = "text-ttml"))))]
+pub use image_subtitles::{Bitmap, ImageCue, ImageSubtitles};
+
+#[cfg(any(feature = "text-dvb", feature = "text-ttml"))]
+mod png;
+
+#[cfg(feature = "text-sami")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-sami")))]
+pub mod sami_text_parser;
 
-pub use subtitles::Subtitles;
+#[cfg(feature = "text-srt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-srt")))]
+pub mod srt_text_parser;
 
 #[cfg(feature = "text-ttml")]
 #[cfg_attr(docsrs, doc(cfg(feature = "text-ttml")))]
 pub mod ttml_text_parser;
 
+#[cfg(any(feature = "text-cea", feature = "text-dvb"))]
+mod ts;
+
+#[cfg(feature = "text-cea")]
+mod ts_cea_parser;
+
+#[cfg(feature = "text-cea")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-cea")))]
+pub use ts_cea_parser::TsCeaParser;
+
+#[cfg(feature = "text-dvb")]
+mod ts_dvb_parser;
+
+#[cfg(feature = "text-dvb")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-dvb")))]
+pub use ts_dvb_parser::{DvbSubtitles, TsDvbParser};
+
 #[cfg(feature = "text-ttml")]
 mod mp4_ttml_parser;
 
@@ -32,3 +98,11 @@ mod mp4_vtt_parser;
 #[cfg(feature = "text-vtt")]
 #[cfg_attr(docsrs, doc(cfg(feature = "text-vtt")))]
 pub use mp4_vtt_parser::Mp4VttParser;
+
+#[cfg(feature = "text-vtt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-vtt")))]
+pub mod vtt_text_parser;
+
+#[cfg(feature = "text-vtt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "text-vtt")))]
+pub use vtt_text_parser::VttTextParser;
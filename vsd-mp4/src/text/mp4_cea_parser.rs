@@ -0,0 +1,303 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/mp4_cea_parser.js
+
+*/
+
+use super::{
+    CeaDecoder,
+    boxes::{MDHDBox, TFDTBox, TFHDBox, TKHDBox, TRUNBox, TRUNSample},
+    cea_decoder::VideoCodec,
+};
+use crate::{Error, Result, parser, parser::Mp4Parser};
+use std::sync::{Arc, Mutex};
+
+/// Extract closed captions from video samples of mp4 files.
+pub struct Mp4CeaParser {
+    codec: VideoCodec,
+    /// Size of length field preceding every nal unit inside samples.
+    nal_length_size: usize,
+    timescale: u32,
+    track_id: u32,
+}
+
+/// Samples of a video track run.
+struct Run {
+    base_time: u64,
+    /// Offset of first sample from start of `moof` box.
+    data_offset: Option<u32>,
+    default_duration: Option<u32>,
+    default_size: Option<u32>,
+    samples: Vec<TRUNSample>,
+}
+
+#[derive(Default)]
+struct Traf {
+    base_time: u64,
+    default_duration: Option<u32>,
+    default_size: Option<u32>,
+    is_video: bool,
+}
+
+impl Mp4CeaParser {
+    /// Parse intialization segment, a valid `avcC` or `hvcC` box should be present.
+    pub fn parse_init(data: &[u8]) -> Result<Self> {
+        let track = Arc::new(Mutex::new((0, None)));
+        let video = Arc::new(Mutex::new(None));
+
+        let track_c = track.clone();
+        let track_cc = track.clone();
+        let video_c = video.clone();
+
+        let config = move |codec: VideoCodec| -> parser::CallbackType {
+            let track = track.clone();
+            let video = video_c.clone();
+
+            parser::alldata(Arc::new(move |data| {
+                let mut video = video.lock().unwrap();
+
+                // Only first video track is used.
+                if video.is_some() {
+                    return Ok(());
+                }
+
+                // lengthSizeMinusOne is stored at 5th byte of avcC and 22nd byte of hvcC.
+                let position = match codec {
+                    VideoCodec::H264 => 4,
+                    VideoCodec::H265 => 21,
+                };
+                let nal_length_size = *data.get(position).ok_or_else(|| {
+                    Error::new_read("decoder configuration record length size minus one (u8).")
+                })? as usize
+                    & 0x03;
+                let (track_id, timescale) = *track.lock().unwrap();
+
+                *video = Some((codec, nal_length_size + 1, track_id, timescale));
+                Ok(())
+            }))
+        };
+
+        Mp4Parser::default()
+            .basic_box("moov", Arc::new(parser::children))
+            .basic_box("trak", Arc::new(parser::children))
+            .full_box(
+                "tkhd",
+                Arc::new(move |mut _box| {
+                    let _box_version = _box.version.unwrap();
+                    if _box_version != 0 && _box_version != 1 {
+                        return Err(Error::new("TKHD box version can only be 0 or 1."));
+                    }
+                    let parsed_tkhd_box = TKHDBox::parse(&mut _box.reader, _box_version)?;
+                    *track_c.lock().unwrap() = (parsed_tkhd_box.track_id, None);
+                    Ok(())
+                }),
+            )
+            .basic_box("mdia", Arc::new(parser::children))
+            .full_box(
+                "mdhd",
+                Arc::new(move |mut _box| {
+                    let _box_version = _box.version.unwrap();
+                    if _box_version != 0 && _box_version != 1 {
+                        return Err(Error::new("MDHD box version can only be 0 or 1."));
+                    }
+                    let parsed_mdhd_box = MDHDBox::parse(&mut _box.reader, _box_version)?;
+                    track_cc.lock().unwrap().1 = Some(parsed_mdhd_box.timescale);
+                    Ok(())
+                }),
+            )
+            .basic_box("minf", Arc::new(parser::children))
+            .basic_box("stbl", Arc::new(parser::children))
+            .full_box("stsd", Arc::new(parser::sample_description))
+            .basic_box("avc1", Arc::new(parser::visual_sample_entry))
+            .basic_box("avc3", Arc::new(parser::visual_sample_entry))
+            .basic_box("dvav", Arc::new(parser::visual_sample_entry))
+            .basic_box("dva1", Arc::new(parser::visual_sample_entry))
+            .basic_box("dvh1", Arc::new(parser::visual_sample_entry))
+            .basic_box("dvhe", Arc::new(parser::visual_sample_entry))
+            .basic_box("encv", Arc::new(parser::visual_sample_entry))
+            .basic_box("hev1", Arc::new(parser::visual_sample_entry))
+            .basic_box("hvc1", Arc::new(parser::visual_sample_entry))
+            .basic_box("avcC", config(VideoCodec::H264))
+            .basic_box("hvcC", config(VideoCodec::H265))
+            .parse(data, false, false)?;
+
+        let video = *video.lock().unwrap();
+
+        match video {
+            Some((codec, nal_length_size, track_id, Some(timescale))) => Ok(Self {
+                codec,
+                nal_length_size,
+                timescale,
+                track_id,
+            }),
+            Some(_) => Err(Error::new(
+                "missing timescale (should exist inside MDHD box).",
+            )),
+            None => Err(Error::new("AVCC or HVCC box not found.")),
+        }
+    }
+
+    /// Parse media segments and extract closed captions from sei nal units of video samples.
+    pub fn parse_media(&self, data: &[u8], decoder: &mut CeaDecoder) -> Result<()> {
+        let moof_start = Arc::new(Mutex::new(0_u64));
+        let runs = Arc::new(Mutex::new(Vec::<Run>::new()));
+        let traf = Arc::new(Mutex::new(Traf::default()));
+        let samples = Arc::new(Mutex::new(vec![]));
+
+        let moof_start_c = moof_start.clone();
+        let runs_c = runs.clone();
+        let runs_cc = runs.clone();
+        let traf_c = traf.clone();
+        let traf_cc = traf.clone();
+        let traf_ccc = traf.clone();
+        let samples_c = samples.clone();
+
+        let track_id = self.track_id;
+
+        Mp4Parser::default()
+            .basic_box(
+                "moof",
+                Arc::new(move |_box| {
+                    *moof_start_c.lock().unwrap() = _box.start;
+                    runs_c.lock().unwrap().clear();
+                    parser::children(_box)
+                }),
+            )
+            .basic_box(
+                "traf",
+                Arc::new(move |_box| {
+                    *traf_c.lock().unwrap() = Traf::default();
+                    parser::children(_box)
+                }),
+            )
+            .full_box(
+                "tfhd",
+                Arc::new(move |mut _box| {
+                    if _box.flags.is_none() {
+                        return Err(Error::new("TFHD box should have a valid flags value."));
+                    }
+
+                    let parsed_tfhd_box = TFHDBox::parse(&mut _box.reader, _box.flags.unwrap())?;
+                    let mut traf = traf_cc.lock().unwrap();
+                    traf.default_duration = parsed_tfhd_box.default_sample_duration;
+                    traf.default_size = parsed_tfhd_box.default_sample_size;
+                    traf.is_video = parsed_tfhd_box.track_id == track_id;
+                    Ok(())
+                }),
+            )
+            .full_box(
+                "tfdt",
+                Arc::new(move |mut _box| {
+                    let _box_version = _box.version.unwrap();
+                    if _box_version != 0 && _box_version != 1 {
+                        return Err(Error::new("TFDT version can only be 0 or 1."));
+                    }
+
+                    let parsed_tfdt_box = TFDTBox::parse(&mut _box.reader, _box_version)?;
+                    traf_ccc.lock().unwrap().base_time = parsed_tfdt_box.base_media_decode_time;
+                    Ok(())
+                }),
+            )
+            .full_box(
+                "trun",
+                Arc::new(move |mut _box| {
+                    if _box.version.is_none() {
+                        return Err(Error::new("TRUN box should have a valid version value."));
+                    }
+                    if _box.flags.is_none() {
+                        return Err(Error::new("TRUN box should have a valid flags value."));
+                    }
+
+                    let traf = traf.lock().unwrap();
+
+                    if !traf.is_video {
+                        return Ok(());
+                    }
+
+                    let parsed_trun_box = TRUNBox::parse(
+                        &mut _box.reader,
+                        _box.version.unwrap(),
+                        _box.flags.unwrap(),
+                    )?;
+                    runs_cc.lock().unwrap().push(Run {
+                        base_time: traf.base_time,
+                        data_offset: parsed_trun_box.data_offset,
+                        default_duration: traf.default_duration,
+                        default_size: traf.default_size,
+                        samples: parsed_trun_box.sample_data,
+                    });
+                    Ok(())
+                }),
+            )
+            .basic_box(
+                "mdat",
+                Arc::new(move |mut _box| {
+                    let moof_start = *moof_start.lock().unwrap();
+                    let mdat_start = _box.start + _box.header_size();
+                    let all = _box.reader.get_length() - _box.reader.get_position();
+                    let data = _box
+                        .reader
+                        .read_bytes_u8(all as usize)
+                        .map_err(|_| Error::new_read(format!("all data {all} bytes.")))?;
+
+                    // Samples are placed one after another, unless a run specifies its offset.
+                    let mut offset = 0;
+
+                    for run in runs.lock().unwrap().drain(..) {
+                        if let Some(data_offset) = run.data_offset {
+                            let Some(run_offset) =
+                                (moof_start + data_offset as u64).checked_sub(mdat_start)
+                            else {
+                                continue;
+                            };
+                            offset = run_offset as usize;
+                        }
+
+                        let mut time = run.base_time;
+
+                        for sample in run.samples {
+                            let size = sample
+                                .sample_size
+                                .or(run.default_size)
+                                .ok_or_else(|| Error::new("video sample size unknown."))?
+                                as usize;
+                            let pts = time as i64
+                                + sample.sample_composition_time_offset.unwrap_or(0) as i64;
+
+                            if let Some(sample) = data.get(offset..(offset + size)) {
+                                samples_c.lock().unwrap().push((pts, sample.to_vec()));
+                            }
+
+                            offset += size;
+                            time +=
+                                sample.sample_duration.or(run.default_duration).unwrap_or(0) as u64;
+                        }
+                    }
+
+                    Ok(())
+                }),
+            )
+            .parse(data, false, false)?;
+
+        for (pts, sample) in samples.lock().unwrap().iter() {
+            let pts = *pts as f64 / self.timescale as f64;
+            let mut sample = sample.as_slice();
+            decoder.add_frame(pts);
+
+            while sample.len() > self.nal_length_size {
+                let (length, rest) = sample.split_at(self.nal_length_size);
+                let length = length.iter().fold(0, |x, y| (x << 8) | *y as usize);
+                let Some(nal) = rest.get(..length) else {
+                    break;
+                };
+
+                decoder.process_nal(nal, self.codec, pts);
+                sample = &rest[length..];
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -6,7 +6,11 @@
 
 */
 
-use super::{ttml_text_parser, Subtitles};
+use super::{
+    ImageSubtitles, Subtitles,
+    boxes::{SUBSBox, TFHDBox, TRUNBox},
+    ttml_text_parser::{self, TT},
+};
 use crate::{parser, parser::Mp4Parser, Error, Result};
 use std::sync::{Arc, Mutex};
 
@@ -47,31 +51,160 @@ impl Mp4TtmlParser {
 
     /// Parse media segments, only if valid `mdat` box(s) are present.
     pub fn parse_media(&self, data: &[u8]) -> Result<Subtitles> {
+        let mut cues = vec![];
+
+        for (xml, _) in Self::parse_samples(data)? {
+            cues.append(&mut xml.into_cues());
+        }
+
+        Ok(Subtitles::new(cues))
+    }
+
+    /// Parse image subtitles from media segments, images are either embedded inside ttml
+    /// document or stored as subsamples of mp4 samples.
+    pub fn parse_media_images(&self, data: &[u8]) -> Result<ImageSubtitles> {
+        let mut subtitles: Option<ImageSubtitles> = None;
+
+        for (xml, images) in Self::parse_samples(data)? {
+            let images = xml.image_subtitles(&images)?;
+
+            match &mut subtitles {
+                Some(subtitles) => subtitles.extend(images),
+                None => subtitles = Some(images),
+            }
+        }
+
+        subtitles.ok_or_else(|| Error::new("MDAT box not found."))
+    }
+
+    /// Split `mdat` boxes into samples, first subsample of every sample is a ttml document
+    /// and rest of the subsamples are images referenced by it.
+    fn parse_samples(data: &[u8]) -> Result<Vec<(TT, Vec<Vec<u8>>)>> {
         let saw_mdat = Arc::new(Mutex::new(false));
-        let cues = Arc::new(Mutex::new(vec![]));
+        let default_size = Arc::new(Mutex::new(None));
+        let sample_sizes = Arc::new(Mutex::new(vec![]));
+        let subsamples = Arc::new(Mutex::new(vec![]));
+        let samples = Arc::new(Mutex::new(vec![]));
 
         let saw_mdat_c = saw_mdat.clone();
-        let cues_c = cues.clone();
+        let default_size_c = default_size.clone();
+        let sample_sizes_c = sample_sizes.clone();
+        let subsamples_c = subsamples.clone();
+        let samples_c = samples.clone();
 
         Mp4Parser::default()
+            .basic_box("moof", Arc::new(parser::children))
+            .basic_box("traf", Arc::new(parser::children))
+            .full_box(
+                "tfhd",
+                Arc::new(move |mut _box| {
+                    if _box.flags.is_none() {
+                        return Err(Error::new("TFHD box should have a valid flags value."));
+                    }
+
+                    let parsed_tfhd_box = TFHDBox::parse(&mut _box.reader, _box.flags.unwrap())?;
+                    *default_size_c.lock().unwrap() = parsed_tfhd_box.default_sample_size;
+                    Ok(())
+                }),
+            )
+            .full_box(
+                "trun",
+                Arc::new(move |mut _box| {
+                    if _box.version.is_none() {
+                        return Err(Error::new("TRUN box should have a valid version value."));
+                    }
+                    if _box.flags.is_none() {
+                        return Err(Error::new("TRUN box should have a valid flags value."));
+                    }
+
+                    let parsed_trun_box = TRUNBox::parse(
+                        &mut _box.reader,
+                        _box.version.unwrap(),
+                        _box.flags.unwrap(),
+                    )?;
+                    *sample_sizes_c.lock().unwrap() = parsed_trun_box
+                        .sample_data
+                        .iter()
+                        .map(|x| x.sample_size)
+                        .collect();
+                    Ok(())
+                }),
+            )
+            .full_box(
+                "subs",
+                Arc::new(move |mut _box| {
+                    if _box.version.is_none() {
+                        return Err(Error::new("SUBS box should have a valid version value."));
+                    }
+
+                    let parsed_subs_box = SUBSBox::parse(&mut _box.reader, _box.version.unwrap())?;
+                    *subsamples_c.lock().unwrap() = parsed_subs_box.entries;
+                    Ok(())
+                }),
+            )
             .basic_box(
                 "mdat",
                 parser::alldata(Arc::new(move |data| {
                     *saw_mdat_c.lock().unwrap() = true;
-                    // Join this to any previous payload, in case the mp4 has multiple
-                    // mdats.
-                    let xml = String::from_utf8(data).map_err(|_| {
-                        Error::new_decode("MDAT box payload as valid utf-8 data.")
-                    })?;
-                    cues_c.lock().unwrap().append(
-                        &mut ttml_text_parser::parse(&xml)
-                            .map_err(|x| {
-                                Error::new_decode(format!(
-                                    "xml string as ttml content.\n\n{xml}\n\n{x:#?}"
-                                ))
-                            })?
-                            .into_cues(),
-                    );
+
+                    let default_size = *default_size.lock().unwrap();
+                    let sample_sizes = std::mem::take(&mut *sample_sizes.lock().unwrap());
+                    let entries = std::mem::take(&mut *subsamples.lock().unwrap());
+                    let mut sizes = sample_sizes
+                        .iter()
+                        .map(|x| x.or(default_size).map(|x| x as usize))
+                        .collect::<Option<Vec<_>>>()
+                        .unwrap_or_default();
+
+                    // Whole payload is a single sample when sample sizes are unknown.
+                    if sizes.is_empty() {
+                        sizes.push(data.len());
+                    }
+
+                    let mut subsample_sizes = vec![vec![]; sizes.len()];
+                    let mut index = 0;
+
+                    for entry in entries {
+                        index += entry.sample_delta as usize;
+
+                        if let Some(x) = subsample_sizes.get_mut(index.wrapping_sub(1)) {
+                            *x = entry.subsample_sizes;
+                        }
+                    }
+
+                    let mut position = 0;
+
+                    for (size, subsample_sizes) in sizes.into_iter().zip(subsample_sizes) {
+                        let sample = data.get(position..(position + size)).ok_or_else(|| {
+                            Error::new_read("MDAT box sample data (sample size is too large).")
+                        })?;
+                        let mut parts = vec![];
+                        let mut offset = 0;
+
+                        for size in subsample_sizes {
+                            if let Some(x) = sample.get(offset..(offset + size as usize)) {
+                                parts.push(x.to_vec());
+                            }
+
+                            offset += size as usize;
+                        }
+
+                        if parts.is_empty() {
+                            parts.push(sample.to_vec());
+                        }
+
+                        let xml = String::from_utf8(parts.remove(0)).map_err(|_| {
+                            Error::new_decode("MDAT box payload as valid utf-8 data.")
+                        })?;
+                        let tt = ttml_text_parser::parse(&xml).map_err(|x| {
+                            Error::new_decode(format!(
+                                "xml string as ttml content.\n\n{xml}\n\n{x:#?}"
+                            ))
+                        })?;
+                        samples_c.lock().unwrap().push((tt, parts));
+                        position += size;
+                    }
+
                     Ok(())
                 })),
             )
@@ -83,7 +216,6 @@ impl Mp4TtmlParser {
             return Err(Error::new("MDAT box not found."));
         }
 
-        let cues = cues.lock().unwrap().clone();
-        Ok(Subtitles::new(cues))
+        Ok(std::mem::take(&mut *samples.lock().unwrap()))
     }
 }
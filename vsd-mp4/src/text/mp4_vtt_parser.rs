@@ -8,7 +8,7 @@
 
 use super::{
     boxes::{MDHDBox, TFDTBox, TFHDBox, TRUNBox, TRUNSample},
-    Cue, Subtitles,
+    Cue, CueStyle, Subtitles,
 };
 use crate::{parser, parser::Mp4Parser, Error, Reader, Result};
 use std::sync::{Arc, Mutex};
@@ -337,6 +337,7 @@ fn parse_vttc(data: &[u8], start_time: f32, end_time: f32) -> Result<Option<Cue>
         return Ok(Some(Cue {
             payload,
             _id: id,
+            style: CueStyle::from_vtt_settings(&settings),
             settings,
             start_time,
             end_time,
@@ -0,0 +1,340 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://www.w3.org/TR/png-3
+
+*/
+
+// Subtitle images only need 8 bit paletted encoding and decoding of non-interlaced images,
+// which is a small amount of code on top of flate2 (already used for zlib streams),
+// so png crate along with its own dependencies isn't added for it.
+
+use flate2::{Compression, Crc, write::ZlibEncoder};
+use std::io::Write;
+
+// Png images are only decoded from ttml subtitles.
+#[cfg(feature = "text-ttml")]
+use crate::{Error, Result};
+#[cfg(feature = "text-ttml")]
+use flate2::read::ZlibDecoder;
+#[cfg(feature = "text-ttml")]
+use std::io::Read;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Decode png image into rgba pixels, interlaced images are not supported.
+#[cfg(feature = "text-ttml")]
+pub(super) fn decode(data: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(Error::new_decode("image data as png (invalid signature)."));
+    }
+
+    let mut i = SIGNATURE.len();
+    let mut header = None;
+    let mut palette = vec![];
+    let mut transparency = vec![];
+    let mut compressed = vec![];
+
+    while i + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[i..(i + 4)].try_into().unwrap()) as usize;
+        let kind = &data[(i + 4)..(i + 8)];
+        let chunk = data
+            .get((i + 8)..(i + 8 + length))
+            .ok_or_else(|| Error::new_read("png chunk data."))?;
+
+        match kind {
+            b"IHDR" if length >= 13 => header = Some(chunk.to_vec()),
+            b"PLTE" => palette = chunk.to_vec(),
+            b"tRNS" => transparency = chunk.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => (),
+        }
+
+        // length (4) + type (4) + data + crc (4)
+        i += 12 + length;
+    }
+
+    let header = header.ok_or_else(|| Error::new("png IHDR chunk not found."))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let bit_depth = header[8] as usize;
+    let color_type = header[9];
+
+    if header[12] != 0 {
+        return Err(Error::new("interlaced png images are not supported."));
+    }
+
+    let channels = match (color_type, bit_depth) {
+        (0, 1 | 2 | 4 | 8 | 16) | (3, 1 | 2 | 4 | 8) => 1,
+        (2, 8 | 16) => 3,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => {
+            return Err(Error::new(format!(
+                "png color type {color_type} with bit depth {bit_depth} is not supported."
+            )));
+        }
+    };
+
+    let mut filtered = vec![];
+    ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut filtered)
+        .map_err(|_| Error::new_decode("png image data (zlib stream)."))?;
+
+    let bits_per_pixel = channels * bit_depth;
+    let stride = (width as usize)
+        .checked_mul(bits_per_pixel)
+        .map(|x| x.div_ceil(8));
+    // Every scanline starts with its filter type byte.
+    let size = stride
+        .and_then(|x| x.checked_add(1))
+        .and_then(|x| x.checked_mul(height as usize));
+
+    // Header values are checked against image data before allocating anything from them.
+    let (Some(stride), Some(size)) = (stride, size) else {
+        return Err(Error::new("png image dimensions are too large."));
+    };
+
+    if filtered.len() < size {
+        return Err(Error::new_read("png scanline data."));
+    }
+
+    // Filters operate on bytes of previous pixel, which is atleast one byte away.
+    let bpp = bits_per_pixel.div_ceil(8);
+    let mut previous = vec![0_u8; stride];
+    let mut rgba = vec![];
+
+    for line in filtered[..size].chunks_exact(stride + 1) {
+        let mut current = line[1..].to_vec();
+
+        for x in 0..stride {
+            let a = if x >= bpp { current[x - bpp] } else { 0 };
+            let b = previous[x];
+            let c = if x >= bpp { previous[x - bpp] } else { 0 };
+
+            current[x] = current[x].wrapping_add(match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                x => return Err(Error::new(format!("png filter type {x} is invalid."))),
+            });
+        }
+
+        for x in 0..width as usize {
+            // Samples are reduced to 8 bits.
+            let sample = |channel: usize| -> u8 {
+                let index = x * channels + channel;
+
+                match bit_depth {
+                    16 => current[index * 2],
+                    8 => current[index],
+                    _ => {
+                        let bit = index * bit_depth;
+                        let value = (current[bit / 8] >> (8 - bit_depth - bit % 8))
+                            & ((1 << bit_depth) - 1);
+
+                        if color_type == 3 {
+                            value
+                        } else {
+                            (value as usize * 255 / ((1 << bit_depth) - 1)) as u8
+                        }
+                    }
+                }
+            };
+
+            match color_type {
+                0 => {
+                    let gray = sample(0);
+                    rgba.extend_from_slice(&[gray, gray, gray, 255]);
+                }
+                2 => rgba.extend_from_slice(&[sample(0), sample(1), sample(2), 255]),
+                3 => {
+                    let index = sample(0) as usize;
+                    let color = palette.get((index * 3)..(index * 3 + 3)).unwrap_or(&[0; 3]);
+                    rgba.extend_from_slice(color);
+                    rgba.push(*transparency.get(index).unwrap_or(&255));
+                }
+                4 => {
+                    let gray = sample(0);
+                    rgba.extend_from_slice(&[gray, gray, gray, sample(1)]);
+                }
+                _ => rgba.extend_from_slice(&[sample(0), sample(1), sample(2), sample(3)]),
+            }
+        }
+
+        previous = current;
+    }
+
+    Ok((width, height, rgba))
+}
+
+/// Encode indexed pixels into png image with palette and transparency chunks.
+pub(super) fn encode(width: u32, height: u32, palette: &[[u8; 4]], pixels: &[u8]) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // bit depth, color type, compression, filter and interlace method.
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let mut filtered = Vec::with_capacity(pixels.len() + height as usize);
+
+    for row in pixels.chunks_exact(width.max(1) as usize) {
+        filtered.push(0);
+        filtered.extend_from_slice(row);
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    let _ = encoder.write_all(&filtered);
+    let compressed = encoder.finish().unwrap_or_default();
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(
+        &mut png,
+        b"PLTE",
+        &palette
+            .iter()
+            .flat_map(|x| [x[0], x[1], x[2]])
+            .collect::<Vec<_>>(),
+    );
+    write_chunk(
+        &mut png,
+        b"tRNS",
+        &palette.iter().map(|x| x[3]).collect::<Vec<_>>(),
+    );
+    write_chunk(&mut png, b"IDAT", &compressed);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+#[cfg(feature = "text-ttml")]
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(all(test, feature = "text-ttml"))]
+mod tests {
+    use super::*;
+
+    /// Png image from scanlines which are already filtered.
+    fn png(header: &[u8], filtered: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(filtered).unwrap();
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", header);
+        write_chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn encode_decode() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 128]];
+        let pixels = [0, 1, 2, 2, 1, 0];
+        let (width, height, rgba) = decode(&encode(3, 2, &palette, &pixels)).unwrap();
+
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(
+            rgba,
+            pixels
+                .iter()
+                .flat_map(|x| palette[*x as usize])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn decode_filters() {
+        // 2x5 rgb image, every row uses a different filter type.
+        let rows: [[u8; 6]; 5] = [
+            [10, 20, 30, 40, 50, 60],
+            [15, 25, 35, 45, 55, 65],
+            [200, 100, 50, 210, 90, 40],
+            [5, 250, 128, 0, 255, 1],
+            [60, 70, 80, 30, 20, 10],
+        ];
+        let mut filtered = vec![];
+
+        for (filter, row) in rows.iter().enumerate() {
+            let previous = if filter > 0 { rows[filter - 1] } else { [0; 6] };
+            filtered.push(filter as u8);
+
+            for x in 0..6 {
+                let a = if x >= 3 { row[x - 3] } else { 0 };
+                let b = previous[x];
+                let c = if x >= 3 { previous[x - 3] } else { 0 };
+
+                filtered.push(row[x].wrapping_sub(match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                }));
+            }
+        }
+
+        let header = [0, 0, 0, 2, 0, 0, 0, 5, 8, 2, 0, 0, 0];
+        let (width, height, rgba) = decode(&png(&header, &filtered)).unwrap();
+
+        assert_eq!((width, height), (2, 5));
+        assert_eq!(
+            rgba,
+            rows.iter()
+                .flat_map(|x| x.chunks_exact(3).flat_map(|x| [x[0], x[1], x[2], 255]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn decode_low_bit_depth() {
+        // 3x1 grayscale image with 2 bits per sample.
+        let header = [0, 0, 0, 3, 0, 0, 0, 1, 2, 0, 0, 0, 0];
+        let (_, _, rgba) = decode(&png(&header, &[0, 0b00_01_11_00])).unwrap();
+
+        assert_eq!(rgba, [0, 0, 0, 255, 85, 85, 85, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert!(decode(b"not a png").is_err());
+
+        let header = [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 1];
+        assert!(decode(&png(&header, &[0, 0])).is_err());
+
+        // Dimensions which don't match image data.
+        let header = [
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 16, 6, 0, 0, 0,
+        ];
+        assert!(decode(&png(&header, &[0, 0])).is_err());
+        let header = [0, 0, 0, 2, 0, 0, 0, 2, 8, 0, 0, 0, 0];
+        assert!(decode(&png(&header, &[0, 0, 0])).is_err());
+    }
+}
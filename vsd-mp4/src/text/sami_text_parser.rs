@@ -0,0 +1,205 @@
+//! Parse sami content.
+
+/*
+    REFERENCES
+    ----------
+
+    1. https://learn.microsoft.com/en-us/previous-versions/windows/desktop/dnacc/understanding-sami-1.0
+    2. https://en.wikipedia.org/wiki/SAMI
+
+*/
+
+use super::{Cue, CueStyle, Subtitles};
+use crate::{Error, Result};
+
+/// Duration (in seconds) of last cue, which is not ended by any other `SYNC` element.
+const LAST_CUE_DURATION: f32 = 5.0;
+
+/// Parse sami content.
+/// Only paragraphs of first language class (`<P Class=...>`) are used when multiple languages are present.
+pub fn parse(text: &str) -> Result<Subtitles> {
+    // Ascii lowercase keeps byte positions same, so it is used for case-insensitive search.
+    let lowercase = text.to_ascii_lowercase();
+
+    if !lowercase.contains("<sami") {
+        return Err(Error::new_decode("sami content (SAMI element not found)."));
+    }
+
+    let body_end = lowercase.rfind("</body>").unwrap_or(text.len());
+    let mut syncs = vec![];
+    let mut position = 0;
+
+    while let Some(start) = lowercase[position..body_end]
+        .find("<sync")
+        .map(|x| x + position)
+    {
+        let Some(tag_end) = lowercase[start..body_end].find('>').map(|x| x + start) else {
+            break;
+        };
+        let content_end = lowercase[(tag_end + 1)..body_end]
+            .find("<sync")
+            .map(|x| x + tag_end + 1)
+            .unwrap_or(body_end);
+
+        if let Some(start_time) =
+            attribute(&text[start..tag_end], "start").and_then(|x| x.parse::<f32>().ok())
+        {
+            syncs.push((start_time / 1000.0, &text[(tag_end + 1)..content_end]));
+        }
+
+        position = content_end;
+    }
+
+    let mut class = None;
+    let mut cues = vec![];
+
+    for (i, (start_time, content)) in syncs.iter().enumerate() {
+        let payload = paragraphs(content, &mut class);
+
+        // Empty sync elements (usually &nbsp;) only end previous cue.
+        if payload.trim().is_empty() {
+            continue;
+        }
+
+        cues.push(Cue {
+            end_time: syncs
+                .get(i + 1)
+                .map(|x| x.0)
+                .unwrap_or(start_time + LAST_CUE_DURATION),
+            _id: String::new(),
+            payload,
+            settings: String::new(),
+            start_time: *start_time,
+            style: CueStyle::default(),
+        });
+    }
+
+    Ok(Subtitles::new(cues))
+}
+
+/// Text of paragraphs having same language class, with only basic formatting tags kept.
+fn paragraphs(content: &str, class: &mut Option<String>) -> String {
+    let lowercase = content.to_ascii_lowercase();
+    let mut texts = vec![];
+    let mut position = 0;
+
+    // Content without any paragraph is used as it is.
+    if !lowercase.contains("<p") {
+        return clean(content);
+    }
+
+    while let Some(start) = lowercase[position..].find("<p").map(|x| x + position) {
+        // Skip tags like <param>.
+        if lowercase[(start + 2)..]
+            .chars()
+            .next()
+            .is_some_and(|x| x.is_ascii_alphanumeric())
+        {
+            position = start + 2;
+            continue;
+        }
+
+        let Some(tag_end) = lowercase[start..].find('>').map(|x| x + start) else {
+            break;
+        };
+        let content_end = lowercase[(tag_end + 1)..]
+            .find("<p")
+            .map(|x| x + tag_end + 1)
+            .unwrap_or(content.len());
+        let p_class = attribute(&content[start..tag_end], "class").map(|x| x.to_lowercase());
+
+        if class.is_none() {
+            class.clone_from(&p_class);
+        }
+
+        if p_class == *class {
+            texts.push(clean(&content[(tag_end + 1)..content_end]));
+        }
+
+        position = content_end;
+    }
+
+    texts.join("\n")
+}
+
+/// Convert line breaks, remove unsupported tags and collapse whitespace.
+fn clean(content: &str) -> String {
+    let mut text = String::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|x| x + start) else {
+            break;
+        };
+
+        text += &rest[..start];
+
+        let tag = &rest[(start + 1)..end];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|x: char| x.is_whitespace() || x == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match name.as_str() {
+            "br" => text += "\n",
+            "b" | "i" | "u" => text += &format!("<{}>", tag.to_ascii_lowercase()),
+            "font" if tag.starts_with('/') => text += "</font>",
+            "font" => {
+                if let Some(color) = attribute(tag, "color") {
+                    text += &format!("<font color=\"{color}\">");
+                } else {
+                    text += "<font>";
+                }
+            }
+            _ => (),
+        }
+
+        rest = &rest[(end + 1)..];
+    }
+
+    text += rest;
+    text.split('\n')
+        .map(|x| {
+            x.replace("&nbsp;", " ")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Case-insensitive attribute value, quotes are optional.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut position = 0;
+
+    while let Some(start) = lowercase[position..].find(name).map(|x| x + position) {
+        let value = lowercase[(start + name.len())..].trim_start();
+
+        // Name must be a whole word followed by `=`.
+        if !lowercase[..start].ends_with(|x: char| x.is_whitespace()) || !value.starts_with('=') {
+            position = start + name.len();
+            continue;
+        }
+
+        let offset = tag.len() - value.len() + 1;
+        let value = tag[offset..].trim_start();
+
+        return if let Some(value) = value.strip_prefix('"') {
+            value.split('"').next().map(|x| x.to_owned())
+        } else if let Some(value) = value.strip_prefix('\'') {
+            value.split('\'').next().map(|x| x.to_owned())
+        } else {
+            value
+                .split(|x: char| x.is_whitespace() || x == '>')
+                .next()
+                .map(|x| x.to_owned())
+        };
+    }
+
+    None
+}
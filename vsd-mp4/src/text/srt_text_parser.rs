@@ -0,0 +1,120 @@
+//! Parse subrip content.
+
+/*
+    REFERENCES
+    ----------
+
+    1. https://en.wikipedia.org/wiki/SubRip
+    2. https://docs.fileformat.com/video/srt
+
+*/
+
+use super::{Cue, CueStyle, Subtitles};
+use crate::{Error, Result};
+
+/// Parse subrip content.
+/// Position set using `{\anN}` tags is kept, any other ass override tags are removed.
+pub fn parse(text: &str) -> Result<Subtitles> {
+    let text = text
+        .trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .replace('\r', "\n");
+    let mut cues = vec![];
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+
+        // Cue index line is optional, only timing line is required.
+        let timing = if line.contains("-->") {
+            line
+        } else if lines.peek().is_some_and(|x| x.contains("-->")) {
+            lines.next().unwrap().trim()
+        } else {
+            continue;
+        };
+
+        let Some((start, end)) = timing.split_once("-->") else {
+            continue;
+        };
+        // Coordinates (X1:.. X2:.. Y1:.. Y2:..) after end time are ignored.
+        let end = end.split_whitespace().next().unwrap_or_default();
+
+        // Invalid cue blocks are ignored.
+        let (Some(start_time), Some(end_time)) = (timestamp(start.trim()), timestamp(end)) else {
+            continue;
+        };
+
+        let mut payload = vec![];
+
+        while let Some(line) = lines.next_if(|x| !x.trim().is_empty()) {
+            payload.push(line);
+        }
+
+        let (payload, style) = override_tags(&payload.join("\n"));
+
+        cues.push(Cue {
+            end_time,
+            _id: String::new(),
+            payload,
+            settings: style.vtt_settings(),
+            start_time,
+            style,
+        });
+    }
+
+    if cues.is_empty() && !text.trim().is_empty() {
+        return Err(Error::new_decode("subrip content (no valid cues found)."));
+    }
+
+    Ok(Subtitles::new(cues))
+}
+
+/// Remove ass override tags i.e. `{\...}` from payload, only alignment is kept.
+fn override_tags(payload: &str) -> (String, CueStyle) {
+    let mut style = CueStyle::default();
+    let mut text = String::new();
+    let mut rest = payload;
+
+    while let Some(start) = rest.find("{\\") {
+        let Some(end) = rest[start..].find('}').map(|x| x + start) else {
+            break;
+        };
+
+        text += &rest[..start];
+
+        for tag in rest[(start + 2)..end].split('\\') {
+            if let Some(alignment) = tag.strip_prefix("an").and_then(|x| x.parse::<u8>().ok()) {
+                style.align = match alignment {
+                    1 | 4 | 7 => Some("left".to_owned()),
+                    3 | 6 | 9 => Some("right".to_owned()),
+                    _ => None,
+                };
+                style.line = match alignment {
+                    7..=9 => Some(0.0),
+                    4..=6 => Some(50.0),
+                    _ => None,
+                };
+            }
+        }
+
+        rest = &rest[(end + 1)..];
+    }
+
+    text += rest;
+    (text, style)
+}
+
+/// Parse `hh:mm:ss,ttt` timestamp in seconds, `.` is also accepted as millisecond separator.
+fn timestamp(value: &str) -> Option<f32> {
+    let (time, milliseconds) = value.split_once([',', '.'])?;
+    let mut seconds = 0.0;
+
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<u32>().ok()? as f32;
+    }
+
+    Some(
+        seconds + milliseconds.parse::<u32>().ok()? as f32 / 10_f32.powi(milliseconds.len() as i32),
+    )
+}
@@ -0,0 +1,289 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://w3c.github.io/webvtt/#webvtt-cue-settings
+    2. https://w3c.github.io/webvtt/#default-classes
+    3. https://w3c.github.io/ttml2/#styling-attribute-vocabulary
+
+*/
+
+/// Styling and positioning of a single cue.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) struct CueStyle {
+    /// Text alignment i.e. `start`, `center`, `end`, `left` or `right`.
+    pub(super) align: Option<String>,
+    pub(super) bold: bool,
+    /// Css color i.e. name, `#rrggbb` or `rgb(r,g,b)`.
+    pub(super) color: Option<String>,
+    pub(super) italic: bool,
+    /// Vertical position in percentage of video height.
+    pub(super) line: Option<f32>,
+    /// Horizontal position in percentage of video width.
+    pub(super) position: Option<f32>,
+    pub(super) underline: bool,
+}
+
+impl CueStyle {
+    /// Parse alignment and positioning from webvtt cue settings.
+    #[cfg(feature = "text-vtt")]
+    pub(super) fn from_vtt_settings(settings: &str) -> Self {
+        let mut style = Self::default();
+
+        for setting in settings.split_whitespace() {
+            let Some((name, value)) = setting.split_once(':') else {
+                continue;
+            };
+            let value = value.split(',').next().unwrap_or_default();
+
+            match name {
+                "align" => style.align = Some(value.to_owned()),
+                "line" => {
+                    style.line = if let Some(value) = value.strip_suffix('%') {
+                        value.parse().ok()
+                    } else {
+                        // Line numbers are mapped assuming 15 lines fit in video height.
+                        value.parse::<i32>().ok().map(|x| {
+                            if x < 0 {
+                                (100.0 + (x + 1) as f32 * 100.0 / 15.0).max(0.0)
+                            } else {
+                                (x as f32 * 100.0 / 15.0).min(100.0)
+                            }
+                        })
+                    }
+                }
+                "position" => style.position = value.strip_suffix('%').and_then(|x| x.parse().ok()),
+                _ => (),
+            }
+        }
+
+        style
+    }
+
+    /// Build webvtt cue settings from alignment and positioning.
+    #[cfg(any(feature = "text-cea", feature = "text-srt", feature = "text-ttml"))]
+    pub(super) fn vtt_settings(&self) -> String {
+        let mut settings = vec![];
+
+        if let Some(align) = &self.align {
+            settings.push(format!("align:{align}"));
+        }
+
+        if let Some(line) = self.line {
+            if self.is_top() {
+                settings.push(format!("line:{line}%"));
+            } else {
+                settings.push(format!("line:{line}%,end"));
+            }
+        }
+
+        if let Some(position) = self.position {
+            settings.push(format!("position:{position}%"));
+        }
+
+        settings.join(" ")
+    }
+
+    /// Cue is placed in upper half of the video.
+    pub(super) fn is_top(&self) -> bool {
+        self.line.is_some_and(|x| x < 50.0)
+    }
+
+    /// Numpad style alignment used by ass and `{\anN}` srt tags.
+    pub(super) fn numpad_alignment(&self) -> u8 {
+        let horizontal = match self.align.as_deref() {
+            Some("left" | "start") => 1,
+            Some("right" | "end") => 3,
+            _ => 2,
+        };
+
+        if self.is_top() {
+            horizontal + 6
+        } else {
+            horizontal
+        }
+    }
+}
+
+/// Inline formatting tag present inside cue payload.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Tag {
+    Bold,
+    Color(String),
+    Italic,
+    /// Any other tag like voice (`<v>`), ruby or timestamp kept as it is.
+    Other(String),
+    Underline,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Token {
+    Close(Tag),
+    Open(Tag),
+    Text(String),
+}
+
+/// Split cue payload into text and formatting tags.
+/// Supports `<b>`, `<i>`, `<u>`, `<font color="">` and webvtt color classes like `<c.yellow>`.
+pub(super) fn tokenize(payload: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut open_tags: Vec<(String, Tag)> = vec![];
+    let mut rest = payload;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|x| x + start) else {
+            break;
+        };
+
+        if start > 0 {
+            tokens.push(Token::Text(unescape(&rest[..start])));
+        }
+
+        let raw = &rest[start..=end];
+        let tag = &rest[(start + 1)..end];
+        rest = &rest[(end + 1)..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_lowercase();
+
+            if let Some(position) = open_tags.iter().rposition(|x| x.0 == name) {
+                let (_, tag) = open_tags.remove(position);
+
+                tokens.push(Token::Close(match tag {
+                    Tag::Other(_) => Tag::Other(raw.to_owned()),
+                    x => x,
+                }));
+            } else {
+                tokens.push(Token::Close(Tag::Other(raw.to_owned())));
+            }
+
+            continue;
+        }
+
+        let name = tag
+            .split(|x: char| x.is_whitespace() || x == '.')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let parsed_tag = match name.as_str() {
+            "b" => Tag::Bold,
+            "i" => Tag::Italic,
+            "u" => Tag::Underline,
+            "font" => match attribute(tag, "color") {
+                Some(color) => Tag::Color(color),
+                None => Tag::Other(raw.to_owned()),
+            },
+            "c" => match tag.split('.').skip(1).find(|x| rgb(x).is_some()) {
+                Some(color) => Tag::Color(color.to_owned()),
+                None => Tag::Other(raw.to_owned()),
+            },
+            _ => Tag::Other(raw.to_owned()),
+        };
+
+        // Timestamp tags are never closed.
+        if !name.starts_with(|x: char| x.is_ascii_digit()) {
+            open_tags.push((name, parsed_tag.clone()));
+        }
+
+        tokens.push(Token::Open(parsed_tag));
+    }
+
+    if !rest.is_empty() {
+        tokens.push(Token::Text(unescape(rest)));
+    }
+
+    tokens
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!("{name}="))? + name.len() + 1;
+    let value = &tag[start..];
+
+    if let Some(value) = value.strip_prefix('"') {
+        value.split('"').next().map(|x| x.to_owned())
+    } else if let Some(value) = value.strip_prefix('\'') {
+        value.split('\'').next().map(|x| x.to_owned())
+    } else {
+        value.split_whitespace().next().map(|x| x.to_owned())
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Convert css color to red, green and blue components.
+pub(super) fn rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let color = color.trim().to_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        // Also makes slicing below safe, as every char is a single byte.
+        if !hex.chars().all(|x| x.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let hex = match hex.len() {
+            3 | 4 => hex.chars().take(3).flat_map(|x| [x, x]).collect(),
+            6 | 8 => hex[..6].to_owned(),
+            _ => return None,
+        };
+        let value = u32::from_str_radix(&hex, 16).ok()?;
+        return Some(((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+
+    if let Some(values) = color
+        .strip_prefix("rgba(")
+        .or(color.strip_prefix("rgb("))
+        .and_then(|x| x.strip_suffix(')'))
+    {
+        let mut values = values.split(',').map(|x| x.trim().parse::<u8>().ok());
+        return Some((values.next()??, values.next()??, values.next()??));
+    }
+
+    Some(match color.as_str() {
+        "aqua" | "cyan" => (0, 255, 255),
+        "black" => (0, 0, 0),
+        "blue" => (0, 0, 255),
+        "fuchsia" | "magenta" => (255, 0, 255),
+        "gray" | "grey" => (128, 128, 128),
+        "green" => (0, 128, 0),
+        "lime" => (0, 255, 0),
+        "maroon" => (128, 0, 0),
+        "navy" => (0, 0, 128),
+        "olive" => (128, 128, 0),
+        "purple" => (128, 0, 128),
+        "red" => (255, 0, 0),
+        "silver" => (192, 192, 192),
+        "teal" => (0, 128, 128),
+        "white" => (255, 255, 255),
+        "yellow" => (255, 255, 0),
+        _ => return None,
+    })
+}
+
+/// Webvtt only supports a few named colors as default classes.
+pub(super) fn vtt_color_class(color: &str) -> Option<&'static str> {
+    Some(match rgb(color)? {
+        (0, 0, 0) => "black",
+        (0, 0, 255) => "blue",
+        (0, 255, 0) => "lime",
+        (0, 255, 255) => "cyan",
+        (255, 0, 0) => "red",
+        (255, 0, 255) => "magenta",
+        (255, 255, 0) => "yellow",
+        (255, 255, 255) => "white",
+        _ => return None,
+    })
+}
@@ -5,9 +5,12 @@
     1. https://github.com/shaka-project/shaka-player/blob/9ce2f675d88d5de6f779f2a62a4f4af2bcc14611/lib/text/cue.js
     2. https://w3c.github.io/webvtt
     3. https://developer.mozilla.org/en-US/docs/Web/API/WebVTT_API
+    4. https://github.com/libass/libass/wiki/ASS-File-Format-Guide
+    5. https://w3c.github.io/ttml2
 
 */
 
+use super::styles::{self, CueStyle, Tag, Token};
 use std::fmt::Write;
 
 #[derive(Clone)]
@@ -17,6 +20,46 @@ pub(super) struct Cue {
     pub(super) payload: String,
     pub(super) settings: String,
     pub(super) start_time: f32,
+    pub(super) style: CueStyle,
+}
+
+/// Timing changes of cues, shared by text and image based subtitles.
+pub trait CueTiming {
+    /// Call `f` with start and end time (in seconds) of every cue,
+    /// cues for which it returns `false` are removed.
+    fn retain_times(&mut self, f: impl FnMut(&mut f32, &mut f32) -> bool);
+
+    /// Shift timings of all cues by given seconds, which can be negative.
+    /// Cues ending before zero are removed and cues starting before zero are trimmed.
+    fn shift(&mut self, seconds: f32) {
+        self.retain_times(|start, end| {
+            *start = (*start + seconds).max(0.0);
+            *end += seconds;
+            end > start
+        });
+    }
+
+    /// Multiply timings of all cues by given factor.
+    /// Use `from_fps / to_fps` for converting frame rate of subtitles e.g. `25.0 / 23.976`.
+    fn scale(&mut self, factor: f32) {
+        self.retain_times(|start, end| {
+            *start *= factor;
+            *end *= factor;
+            true
+        });
+    }
+
+    /// Keep only cues (or parts of them) which are shown between given start and end seconds.
+    fn clip(&mut self, start: Option<f32>, end: Option<f32>) {
+        let clip_start = start.unwrap_or(0.0);
+        let clip_end = end.unwrap_or(f32::INFINITY);
+
+        self.retain_times(|start, end| {
+            *start = start.max(clip_start);
+            *end = end.min(clip_end);
+            end > start
+        });
+    }
 }
 
 /// Subtitles builder.
@@ -25,6 +68,13 @@ pub struct Subtitles {
 }
 
 impl Subtitles {
+    #[cfg(any(
+        feature = "text-cea",
+        feature = "text-sami",
+        feature = "text-srt",
+        feature = "text-ttml",
+        feature = "text-vtt"
+    ))]
     pub(super) fn new(cues: Vec<Cue>) -> Self {
         let mut trimmed_cues: Vec<Cue> = vec![];
 
@@ -34,6 +84,7 @@ impl Subtitles {
                 if let Some(last_cue) = trimmed_cues.last()
                     && last_cue.end_time == current_cue.start_time
                         && last_cue.settings == current_cue.settings
+                        && last_cue.style == current_cue.style
                         && last_cue.payload == current_cue.payload
                     {
                         let last_cue_index = trimmed_cues.len() - 1;
@@ -49,23 +100,116 @@ impl Subtitles {
         Self { cues: trimmed_cues }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.cues.is_empty()
+    }
+
     /// Extend these subtitles with another subtitles.
     pub fn extend(&mut self, other: Self) {
         self.cues.extend(other.cues);
     }
 
+    /// Merge subtitles of next segment into these subtitles.
+    /// Cues repeated across segments (same payload and settings with overlapping
+    /// or adjacent timings) are merged into a single cue.
+    pub fn merge(&mut self, other: Self) {
+        // Timestamps have millisecond precision.
+        const TOLERANCE: f32 = 0.002;
+
+        for cue in other.cues {
+            let mut repeated = None;
+
+            for (i, existing) in self.cues.iter().enumerate().rev() {
+                // Cues are in order, so only recent cues can be repeated.
+                if existing.start_time + 60.0 < cue.start_time {
+                    break;
+                }
+
+                if existing.payload == cue.payload
+                    && existing.settings == cue.settings
+                    && existing.style == cue.style
+                    && existing.start_time <= cue.end_time + TOLERANCE
+                    && cue.start_time <= existing.end_time + TOLERANCE
+                {
+                    repeated = Some(i);
+                    break;
+                }
+            }
+
+            match repeated {
+                Some(i) => {
+                    let existing = &mut self.cues[i];
+                    existing.start_time = existing.start_time.min(cue.start_time);
+                    existing.end_time = existing.end_time.max(cue.end_time);
+                }
+                None => self.cues.push(cue),
+            }
+        }
+    }
+
+    /// Sort cues by their start time, cues having same start time are sorted by their end time.
+    pub fn sort(&mut self) {
+        self.cues.sort_by(|a, b| {
+            a.start_time
+                .total_cmp(&b.start_time)
+                .then(a.end_time.total_cmp(&b.end_time))
+        });
+    }
+
+    /// Sort cues and merge overlapping or adjacent cues having same payload, settings and style.
+    pub fn merge_overlapping(&mut self) {
+        self.sort();
+
+        let mut merged: Vec<Cue> = vec![];
+
+        for cue in std::mem::take(&mut self.cues) {
+            let repeated = merged.iter_mut().rev().find(|x| {
+                x.payload == cue.payload
+                    && x.settings == cue.settings
+                    && x.style == cue.style
+                    && x.end_time >= cue.start_time
+            });
+
+            match repeated {
+                Some(existing) => existing.end_time = existing.end_time.max(cue.end_time),
+                None => merged.push(cue),
+            }
+        }
+
+        self.cues = merged;
+    }
+
     /// Build subtitles in webvtt format.
     pub fn as_vtt(&self) -> String {
         let mut subtitles = "WEBVTT\n\n".to_owned();
 
         for cue in &self.cues {
+            let mut payload = String::new();
+
+            for token in styles::tokenize(&cue.payload) {
+                match token {
+                    Token::Open(Tag::Color(color)) => match styles::vtt_color_class(&color) {
+                        Some(class) => payload += &format!("<c.{class}>"),
+                        None => payload += "<c>",
+                    },
+                    Token::Close(Tag::Color(_)) => payload += "</c>",
+                    Token::Open(tag) => payload += &html_tag(&tag, false),
+                    Token::Close(tag) => payload += &html_tag(&tag, true),
+                    Token::Text(text) => payload += &styles::escape(&text),
+                }
+            }
+
+            if let Some(class) = cue.style.color.as_deref().and_then(styles::vtt_color_class) {
+                payload = format!("<c.{class}>{payload}</c>");
+            }
+
             let _ = write!(
                 subtitles,
                 "{} --> {} {}\n{}\n\n",
                 seconds_to_timestamp(cue.start_time, "."),
                 seconds_to_timestamp(cue.end_time, "."),
                 cue.settings,
-                cue.payload
+                wrap_html_style(&cue.style, payload)
             );
         }
 
@@ -73,22 +217,244 @@ impl Subtitles {
     }
 
     /// Build subtitles in subrip format.
+    /// Position of cues is kept using `{\anN}` tags.
     pub fn as_srt(&self) -> String {
         let mut subtitles = String::new();
 
         for (i, cue) in self.cues.iter().enumerate() {
+            let mut payload = String::new();
+
+            for token in styles::tokenize(&cue.payload) {
+                match token {
+                    Token::Open(Tag::Color(color)) => {
+                        payload += &format!("<font color=\"{color}\">")
+                    }
+                    Token::Close(Tag::Color(_)) => payload += "</font>",
+                    Token::Open(Tag::Other(_)) | Token::Close(Tag::Other(_)) => (),
+                    Token::Open(tag) => payload += &html_tag(&tag, false),
+                    Token::Close(tag) => payload += &html_tag(&tag, true),
+                    Token::Text(text) => payload += &text,
+                }
+            }
+
+            if let Some(color) = &cue.style.color {
+                payload = format!("<font color=\"{color}\">{payload}</font>");
+            }
+
+            payload = wrap_html_style(&cue.style, payload);
+
+            let alignment = cue.style.numpad_alignment();
+
+            if alignment != 2 {
+                payload = format!("{{\\an{alignment}}}{payload}");
+            }
+
             let _ = write!(
                 subtitles,
                 "{}\n{} --> {}\n{}\n\n",
                 i + 1,
                 seconds_to_timestamp(cue.start_time, ","),
                 seconds_to_timestamp(cue.end_time, ","),
-                cue.payload
+                payload
             );
         }
 
         subtitles
     }
+
+    /// Build subtitles in advanced substation alpha (ass) format.
+    pub fn as_ass(&self) -> String {
+        let mut subtitles = "[Script Info]\n\
+            ScriptType: v4.00+\n\
+            PlayResX: 384\n\
+            PlayResY: 288\n\
+            WrapStyle: 0\n\
+            ScaledBorderAndShadow: yes\n\n\
+            [V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,Arial,16,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,1\n\n\
+            [Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n"
+            .to_owned();
+
+        for cue in &self.cues {
+            let mut overrides = format!("\\an{}", cue.style.numpad_alignment());
+
+            if let (Some(position), Some(line)) = (cue.style.position, cue.style.line) {
+                let _ = write!(
+                    overrides,
+                    "\\pos({},{})",
+                    (position * 3.84).round(),
+                    (line * 2.88).round()
+                );
+            }
+
+            if cue.style.bold {
+                overrides += "\\b1";
+            }
+
+            if cue.style.italic {
+                overrides += "\\i1";
+            }
+
+            if cue.style.underline {
+                overrides += "\\u1";
+            }
+
+            if let Some(color) = cue.style.color.as_deref().and_then(ass_color) {
+                overrides += &format!("\\c{color}");
+            }
+
+            let mut payload = format!("{{{overrides}}}");
+
+            for token in styles::tokenize(&cue.payload) {
+                match token {
+                    Token::Open(Tag::Bold) => payload += "{\\b1}",
+                    Token::Close(Tag::Bold) => payload += "{\\b0}",
+                    Token::Open(Tag::Color(color)) => {
+                        if let Some(color) = ass_color(&color) {
+                            payload += &format!("{{\\c{color}}}");
+                        }
+                    }
+                    Token::Close(Tag::Color(_)) => payload += "{\\c}",
+                    Token::Open(Tag::Italic) => payload += "{\\i1}",
+                    Token::Close(Tag::Italic) => payload += "{\\i0}",
+                    Token::Open(Tag::Underline) => payload += "{\\u1}",
+                    Token::Close(Tag::Underline) => payload += "{\\u0}",
+                    Token::Open(Tag::Other(_)) | Token::Close(Tag::Other(_)) => (),
+                    Token::Text(text) => {
+                        payload += &text
+                            .replace('{', "\\{")
+                            .replace('}', "\\}")
+                            .replace('\n', "\\N")
+                    }
+                }
+            }
+
+            let _ = writeln!(
+                subtitles,
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+                seconds_to_ass_timestamp(cue.start_time),
+                seconds_to_ass_timestamp(cue.end_time),
+                payload
+            );
+        }
+
+        subtitles
+    }
+
+    /// Build subtitles in timed text markup language (ttml/dfxp) format.
+    pub fn as_ttml(&self) -> String {
+        let mut subtitles = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:tts=\"http://www.w3.org/ns/ttml#styling\">\n\
+            \x20 <head>\n\
+            \x20   <layout>\n\
+            \x20     <region xml:id=\"top\" tts:origin=\"10% 10%\" tts:extent=\"80% 80%\" tts:displayAlign=\"before\"/>\n\
+            \x20     <region xml:id=\"bottom\" tts:origin=\"10% 10%\" tts:extent=\"80% 80%\" tts:displayAlign=\"after\"/>\n\
+            \x20   </layout>\n\
+            \x20 </head>\n\
+            \x20 <body>\n\
+            \x20   <div>\n"
+            .to_owned();
+
+        for cue in &self.cues {
+            let mut attributes = format!(
+                "begin=\"{}\" end=\"{}\" region=\"{}\"",
+                seconds_to_timestamp(cue.start_time, "."),
+                seconds_to_timestamp(cue.end_time, "."),
+                if cue.style.is_top() { "top" } else { "bottom" }
+            );
+
+            if let Some(align) = &cue.style.align {
+                let _ = write!(attributes, " tts:textAlign=\"{align}\"");
+            }
+
+            if let Some(color) = &cue.style.color {
+                let _ = write!(attributes, " tts:color=\"{color}\"");
+            }
+
+            if cue.style.bold {
+                attributes += " tts:fontWeight=\"bold\"";
+            }
+
+            if cue.style.italic {
+                attributes += " tts:fontStyle=\"italic\"";
+            }
+
+            if cue.style.underline {
+                attributes += " tts:textDecoration=\"underline\"";
+            }
+
+            let mut payload = String::new();
+
+            for token in styles::tokenize(&cue.payload) {
+                match token {
+                    Token::Open(Tag::Bold) => payload += "<span tts:fontWeight=\"bold\">",
+                    Token::Open(Tag::Color(color)) => {
+                        payload += &format!("<span tts:color=\"{color}\">")
+                    }
+                    Token::Open(Tag::Italic) => payload += "<span tts:fontStyle=\"italic\">",
+                    Token::Open(Tag::Underline) => {
+                        payload += "<span tts:textDecoration=\"underline\">"
+                    }
+                    Token::Open(Tag::Other(_)) | Token::Close(Tag::Other(_)) => (),
+                    Token::Close(_) => payload += "</span>",
+                    Token::Text(text) => payload += &styles::escape(&text).replace('\n', "<br/>"),
+                }
+            }
+
+            let _ = writeln!(subtitles, "      <p {attributes}>{payload}</p>");
+        }
+
+        subtitles += "    </div>\n  </body>\n</tt>\n";
+        subtitles
+    }
+}
+
+impl CueTiming for Subtitles {
+    fn retain_times(&mut self, mut f: impl FnMut(&mut f32, &mut f32) -> bool) {
+        self.cues
+            .retain_mut(|x| f(&mut x.start_time, &mut x.end_time));
+    }
+}
+
+fn html_tag(tag: &Tag, close: bool) -> String {
+    let name = match tag {
+        Tag::Bold => "b",
+        Tag::Italic => "i",
+        Tag::Underline => "u",
+        Tag::Other(raw) => return raw.to_owned(),
+        Tag::Color(_) => return String::new(),
+    };
+
+    if close {
+        format!("</{name}>")
+    } else {
+        format!("<{name}>")
+    }
+}
+
+/// Wrap payload with cue level bold, italic and underline tags.
+fn wrap_html_style(style: &CueStyle, mut payload: String) -> String {
+    if style.bold {
+        payload = format!("<b>{payload}</b>");
+    }
+
+    if style.italic {
+        payload = format!("<i>{payload}</i>");
+    }
+
+    if style.underline {
+        payload = format!("<u>{payload}</u>");
+    }
+
+    payload
+}
+
+/// Css color to `&HBBGGRR&` format.
+fn ass_color(color: &str) -> Option<String> {
+    let (r, g, b) = styles::rgb(color)?;
+    Some(format!("&H{b:02X}{g:02X}{r:02X}&"))
 }
 
 fn divmod(x: usize, y: usize) -> (usize, usize) {
@@ -103,3 +469,10 @@ fn seconds_to_timestamp(seconds: f32, millisecond_sep: &str) -> String {
         "{hours:02}:{minutes:02}:{seconds:02}{millisecond_sep}{milliseconds:03}"
     )
 }
+
+fn seconds_to_ass_timestamp(seconds: f32) -> String {
+    let (seconds, centiseconds) = divmod((seconds * 100.0).round() as usize, 100);
+    let (minutes, seconds) = divmod(seconds, 60);
+    let (hours, minutes) = divmod(minutes, 60);
+    format!("{hours}:{minutes:02}:{seconds:02}.{centiseconds:02}")
+}
@@ -0,0 +1,384 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://en.wikipedia.org/wiki/MPEG_transport_stream
+    2. https://en.wikipedia.org/wiki/Packetized_elementary_stream
+
+*/
+
+use std::collections::BTreeMap;
+
+const PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+/// Pts values are 33 bit values which rollover after ~26.5 hours.
+const PTS_ROLLOVER: u64 = 1 << 33;
+
+/// Elementary stream of a program listed inside program map table.
+pub(super) struct ElementaryStream {
+    /// Elementary stream descriptors.
+    #[cfg_attr(not(feature = "text-dvb"), allow(dead_code))]
+    pub descriptors: Vec<u8>,
+    pub pid: u16,
+    pub stream_type: u8,
+}
+
+/// Demuxer of pes packets of first program of mpeg transport streams.
+pub(super) struct TsDemuxer {
+    /// Pes packets being received for every selected pid.
+    pes: BTreeMap<u16, Vec<u8>>,
+    pmt_pid: Option<u16>,
+    /// Incomplete transport stream packet from previous data.
+    remainder: Vec<u8>,
+    /// Selects pids of elementary streams whose pes packets are received.
+    select: fn(&[ElementaryStream]) -> Vec<u16>,
+    /// Earliest pts of any elementary stream of program.
+    start: Option<u64>,
+    streams: Vec<ElementaryStream>,
+}
+
+impl TsDemuxer {
+    pub(super) fn new(select: fn(&[ElementaryStream]) -> Vec<u16>) -> Self {
+        Self {
+            pes: BTreeMap::new(),
+            pmt_pid: None,
+            remainder: vec![],
+            select,
+            start: None,
+            streams: vec![],
+        }
+    }
+
+    /// Elementary streams of first program, available once its program map table is parsed.
+    pub(super) fn streams(&self) -> &[ElementaryStream] {
+        &self.streams
+    }
+
+    /// Earliest pts of first program.
+    #[cfg_attr(not(feature = "text-dvb"), allow(dead_code))]
+    pub(super) fn start(&self) -> Option<u64> {
+        self.start
+    }
+
+    /// Parse transport stream data, which can be split at any position.
+    /// Complete pes packets of selected pids are returned in order.
+    pub(super) fn parse(&mut self, data: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut data = [std::mem::take(&mut self.remainder).as_slice(), data].concat();
+        let mut position = 0;
+        let mut packets = vec![];
+
+        while position + PACKET_SIZE <= data.len() {
+            // Resynchronize on corrupted data.
+            if data[position] != SYNC_BYTE {
+                position += 1;
+                continue;
+            }
+
+            if let Some(packet) = self.parse_packet(&data[position..(position + PACKET_SIZE)]) {
+                packets.push(packet);
+            }
+
+            position += PACKET_SIZE;
+        }
+
+        self.remainder = data.split_off(position);
+        packets
+    }
+
+    /// Last pes packets of selected pids, should be called after all data is parsed.
+    pub(super) fn flush(&mut self) -> Vec<(u16, Vec<u8>)> {
+        std::mem::take(&mut self.pes)
+            .into_iter()
+            .filter(|(_, x)| !x.is_empty())
+            .collect()
+    }
+
+    fn parse_packet(&mut self, packet: &[u8]) -> Option<(u16, Vec<u8>)> {
+        let pid = u16::from_be_bytes([packet[1] & 0x1f, packet[2]]);
+        let payload_unit_start = packet[1] & 0x40 != 0;
+        let adaptation_field_control = (packet[3] >> 4) & 0x03;
+
+        // Packet without payload.
+        if adaptation_field_control & 0x01 == 0 {
+            return None;
+        }
+
+        let mut start = 4;
+
+        if adaptation_field_control & 0x02 != 0 {
+            start += 1 + packet[4] as usize;
+        }
+
+        let payload = packet.get(start..)?;
+
+        if pid == 0 {
+            self.parse_pat(payload, payload_unit_start);
+            return None;
+        }
+
+        if Some(pid) == self.pmt_pid {
+            self.parse_pmt(payload, payload_unit_start);
+            return None;
+        }
+
+        if !self.streams.iter().any(|x| x.pid == pid) {
+            return None;
+        }
+
+        if payload_unit_start && let Some(pts) = pts(payload) {
+            self.start = Some(self.start.map_or(pts, |x| x.min(pts)));
+        }
+
+        let pes = self.pes.get_mut(&pid)?;
+        let complete = if payload_unit_start && !pes.is_empty() {
+            Some((pid, std::mem::take(pes)))
+        } else {
+            None
+        };
+
+        pes.extend_from_slice(payload);
+        complete
+    }
+
+    /// Program specific information section, pointer field is skipped.
+    fn section(payload: &[u8], payload_unit_start: bool) -> Option<&[u8]> {
+        if !payload_unit_start {
+            return None;
+        }
+
+        let pointer = *payload.first()? as usize;
+        let section = payload.get((1 + pointer)..)?;
+        let length = u16::from_be_bytes([section.get(1)? & 0x0f, *section.get(2)?]) as usize;
+
+        // Crc32 is excluded.
+        section.get(..(3 + length).checked_sub(4)?)
+    }
+
+    fn parse_pat(&mut self, payload: &[u8], payload_unit_start: bool) {
+        if self.pmt_pid.is_some() {
+            return;
+        }
+
+        let Some(section) = Self::section(payload, payload_unit_start) else {
+            return;
+        };
+
+        for program in section.get(8..).unwrap_or_default().chunks_exact(4) {
+            // Program number zero is used for network pid.
+            if program[0] != 0 || program[1] != 0 {
+                self.pmt_pid = Some(u16::from_be_bytes([program[2] & 0x1f, program[3]]));
+                break;
+            }
+        }
+    }
+
+    fn parse_pmt(&mut self, payload: &[u8], payload_unit_start: bool) {
+        if !self.streams.is_empty() {
+            return;
+        }
+
+        let Some(section) = Self::section(payload, payload_unit_start) else {
+            return;
+        };
+        let Some(&[info_length1, info_length2]) = section.get(10..12) else {
+            return;
+        };
+
+        let mut i = 12 + u16::from_be_bytes([info_length1 & 0x0f, info_length2]) as usize;
+
+        while let Some(stream) = section.get(i..(i + 5)) {
+            let info_length = u16::from_be_bytes([stream[3] & 0x0f, stream[4]]) as usize;
+
+            self.streams.push(ElementaryStream {
+                descriptors: section
+                    .get((i + 5)..(i + 5 + info_length))
+                    .unwrap_or_default()
+                    .to_vec(),
+                pid: u16::from_be_bytes([stream[1] & 0x1f, stream[2]]),
+                stream_type: stream[0],
+            });
+
+            i += 5 + info_length;
+        }
+
+        for pid in (self.select)(&self.streams) {
+            self.pes.insert(pid, vec![]);
+        }
+    }
+}
+
+/// Presentation time stamp of pes packet header.
+fn pts(pes: &[u8]) -> Option<u64> {
+    if pes.get(..3)? != [0x00, 0x00, 0x01] || pes.get(7)? & 0x80 == 0 {
+        return None;
+    }
+
+    let pts = pes.get(9..14)?;
+
+    Some(
+        ((pts[0] as u64 & 0x0e) << 29)
+            | ((pts[1] as u64) << 22)
+            | ((pts[2] as u64 & 0xfe) << 14)
+            | ((pts[3] as u64) << 7)
+            | ((pts[4] as u64) >> 1),
+    )
+}
+
+/// Pes packet data following its header.
+pub(super) fn pes_data(pes: &[u8]) -> Option<&[u8]> {
+    // Packet start code prefix (3) + stream id (1) + packet length (2) + flags (2)
+    // + header data length (1)
+    if pes.len() < 9 || pes[..3] != [0x00, 0x00, 0x01] {
+        return None;
+    }
+
+    pes.get((9 + pes[8] as usize)..)
+}
+
+/// Pts of consecutive pes packets of an elementary stream, corrected for rollovers.
+#[derive(Default)]
+pub(super) struct PtsClock {
+    last_pts: Option<u64>,
+    /// Number of times pts has rolled over.
+    rollovers: u64,
+}
+
+impl PtsClock {
+    /// Pts of pes packet in 90khz units, pes packets without pts use pts of previous packet.
+    pub(super) fn update(&mut self, pes: &[u8]) -> Option<u64> {
+        if let Some(pts) = pts(pes) {
+            if self.last_pts.is_some_and(|x| pts + PTS_ROLLOVER / 2 < x) {
+                self.rollovers += 1;
+            }
+
+            self.last_pts = Some(pts);
+        }
+
+        Some(self.last_pts? + self.rollovers * PTS_ROLLOVER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transport stream packets of a payload, last packet is padded with adaptation field.
+    fn packets(pid: u16, mut payload: &[u8]) -> Vec<u8> {
+        let mut packets = vec![];
+        let mut payload_unit_start = 0x40;
+
+        while !payload.is_empty() {
+            let length = payload.len().min(PACKET_SIZE - 4);
+            packets.extend_from_slice(&[
+                SYNC_BYTE,
+                payload_unit_start | (pid >> 8) as u8,
+                pid as u8,
+            ]);
+
+            if length < PACKET_SIZE - 4 {
+                let stuffing = PACKET_SIZE - 4 - length;
+                packets.push(0x30);
+                packets.push(stuffing as u8 - 1);
+                packets.extend(vec![0xff; stuffing - 1]);
+            } else {
+                packets.push(0x10);
+            }
+
+            packets.extend_from_slice(&payload[..length]);
+            payload = &payload[length..];
+            payload_unit_start = 0;
+        }
+
+        packets
+    }
+
+    /// Program specific information section with pointer field, crc32 isn't verified.
+    fn section(table_id: u8, data: &[u8]) -> Vec<u8> {
+        let mut section = vec![0x00, table_id];
+        section.extend_from_slice(&(0xb000 | (data.len() as u16 + 4)).to_be_bytes());
+        section.extend_from_slice(data);
+        section.extend_from_slice(&[0; 4]);
+        section
+    }
+
+    fn pes(pts: u64, data: &[u8]) -> Vec<u8> {
+        let mut pes = vec![0x00, 0x00, 0x01, 0xbd];
+        pes.extend_from_slice(&(data.len() as u16 + 8).to_be_bytes());
+        pes.extend_from_slice(&[
+            0x80,
+            0x80,
+            0x05,
+            0x21 | ((pts >> 29) & 0x0e) as u8,
+            (pts >> 22) as u8,
+            0x01 | ((pts >> 14) & 0xfe) as u8,
+            (pts >> 7) as u8,
+            0x01 | ((pts << 1) & 0xfe) as u8,
+        ]);
+        pes.extend_from_slice(data);
+        pes
+    }
+
+    #[test]
+    fn demux_selected_streams() {
+        let program = [0x00, 0x01, 0xc1, 0x00, 0x00];
+        let pat = section(0x00, &[&program[..], &[0x00, 0x01, 0xf0, 0x00]].concat());
+        let pmt = section(
+            0x02,
+            &[
+                &program[..],
+                &[0xe1, 0x01, 0xf0, 0x00],
+                &[0x1b, 0xe1, 0x01, 0xf0, 0x00],
+                &[0x06, 0xe1, 0x02, 0xf0, 0x02, 0x59, 0x00],
+            ]
+            .concat(),
+        );
+        let long = vec![0xaa; 300];
+        let data = [
+            packets(0x0000, &pat),
+            packets(0x1000, &pmt),
+            packets(0x0101, &pes(90000, &[0x00; 10])),
+            packets(0x0102, &pes(180000, &long)),
+            packets(0x0102, &pes(270000, &[0xbb; 10])),
+        ]
+        .concat();
+
+        let mut demuxer = TsDemuxer::new(|streams| {
+            streams
+                .iter()
+                .filter(|x| x.stream_type == 0x06)
+                .map(|x| x.pid)
+                .collect()
+        });
+        // Data can be split at any position.
+        let mut packets = data
+            .chunks(100)
+            .flat_map(|x| demuxer.parse(x))
+            .collect::<Vec<_>>();
+        packets.extend(demuxer.flush());
+
+        assert_eq!(demuxer.streams().len(), 2);
+        assert_eq!(demuxer.streams()[1].descriptors, [0x59, 0x00]);
+        assert_eq!(demuxer.start(), Some(90000));
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].0, 0x0102);
+        assert_eq!(pes_data(&packets[0].1), Some(long.as_slice()));
+        assert_eq!(pes_data(&packets[1].1), Some([0xbb; 10].as_slice()));
+    }
+
+    #[test]
+    fn pts_rollover() {
+        let mut clock = PtsClock::default();
+
+        assert_eq!(clock.update(&pes(0, &[])), Some(0));
+        assert_eq!(
+            clock.update(&pes(PTS_ROLLOVER - 90000, &[])),
+            Some(PTS_ROLLOVER - 90000)
+        );
+        assert_eq!(clock.update(&pes(90000, &[])), Some(PTS_ROLLOVER + 90000));
+        // Pes packet without pts uses pts of previous packet.
+        assert_eq!(
+            clock.update(&[0x00, 0x00, 0x01, 0xbd, 0x00, 0x03, 0x80, 0x00, 0x00]),
+            Some(PTS_ROLLOVER + 90000)
+        );
+    }
+}
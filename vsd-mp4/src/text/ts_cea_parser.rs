@@ -0,0 +1,123 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/blob/main/lib/cea/ts_cea_parser.js
+
+*/
+
+use super::{
+    CeaDecoder,
+    cea_decoder::VideoCodec,
+    ts::{self, PtsClock, TsDemuxer},
+};
+
+/// Extract closed captions from video elementary stream of mpeg transport streams.
+///
+/// Only first h264 or h265 video stream of first program is used.
+pub struct TsCeaParser {
+    clock: PtsClock,
+    demuxer: TsDemuxer,
+}
+
+impl Default for TsCeaParser {
+    fn default() -> Self {
+        Self {
+            clock: PtsClock::default(),
+            demuxer: TsDemuxer::new(|streams| {
+                streams
+                    .iter()
+                    .find(|x| codec(x.stream_type).is_some())
+                    .map(|x| vec![x.pid])
+                    .unwrap_or_default()
+            }),
+        }
+    }
+}
+
+impl TsCeaParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse transport stream data, which can be split at any position.
+    pub fn parse(&mut self, data: &[u8], decoder: &mut CeaDecoder) {
+        for (pid, pes) in self.demuxer.parse(data) {
+            self.parse_pes(pid, &pes, decoder);
+        }
+    }
+
+    /// Process last pes packet, should be called after all data is parsed.
+    pub fn flush(&mut self, decoder: &mut CeaDecoder) {
+        for (pid, pes) in self.demuxer.flush() {
+            self.parse_pes(pid, &pes, decoder);
+        }
+    }
+
+    fn parse_pes(&mut self, pid: u16, pes: &[u8], decoder: &mut CeaDecoder) {
+        let Some(codec) = self
+            .demuxer
+            .streams()
+            .iter()
+            .find(|x| x.pid == pid)
+            .and_then(|x| codec(x.stream_type))
+        else {
+            return;
+        };
+
+        let Some(data) = ts::pes_data(pes) else {
+            return;
+        };
+
+        let Some(pts) = self.clock.update(pes) else {
+            return;
+        };
+
+        let pts = pts as f64 / 90000.0;
+        decoder.add_frame(pts);
+
+        for nal in nal_units(data) {
+            decoder.process_nal(nal, codec, pts);
+        }
+    }
+}
+
+/// Video codec of elementary stream type.
+fn codec(stream_type: u8) -> Option<VideoCodec> {
+    match stream_type {
+        0x1b => Some(VideoCodec::H264),
+        0x24 => Some(VideoCodec::H265),
+        _ => None,
+    }
+}
+
+/// Split annex b byte stream into nal units.
+fn nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+
+    while i + 3 <= data.len() {
+        if data[i..(i + 3)] == [0x00, 0x00, 0x01] {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(j, &start)| {
+            let end = starts.get(j + 1).map(|x| x - 3).unwrap_or(data.len());
+            // Zero byte of four byte start code belongs to next start code.
+            let mut nal = &data[start..end];
+
+            while let Some((&0, rest)) = nal.split_last() {
+                nal = rest;
+            }
+
+            nal
+        })
+        .collect()
+}
@@ -0,0 +1,751 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://www.etsi.org/deliver/etsi_en/300700_300799/300743/01.06.01_60/en_300743v010601p.pdf
+    2. https://github.com/FFmpeg/FFmpeg/blob/master/libavcodec/dvbsubdec.c
+
+*/
+
+use super::{
+    Bitmap, ImageCue, ImageSubtitles,
+    ts::{self, ElementaryStream, PtsClock, TsDemuxer},
+};
+use std::collections::BTreeMap;
+
+/// Subtitling descriptor tag inside program map table.
+const SUBTITLING_DESCRIPTOR: u8 = 0x59;
+
+/// Dvb subtitles of a single subtitling service.
+pub struct DvbSubtitles {
+    pub language: Option<String>,
+    pub pid: u16,
+    pub subtitles: ImageSubtitles,
+}
+
+/// Extract dvb bitmap subtitles from mpeg transport streams.
+///
+/// Every subtitling service (language) of first program is decoded separately.
+/// Cue times are relative to presentation time of earliest packet of first program.
+pub struct TsDvbParser {
+    demuxer: TsDemuxer,
+    services: Vec<Service>,
+}
+
+impl Default for TsDvbParser {
+    fn default() -> Self {
+        Self {
+            demuxer: TsDemuxer::new(|streams| {
+                streams
+                    .iter()
+                    .filter(|x| !services(x).is_empty())
+                    .map(|x| x.pid)
+                    .collect()
+            }),
+            services: vec![],
+        }
+    }
+}
+
+impl TsDvbParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse transport stream data, which can be split at any position.
+    pub fn parse(&mut self, data: &[u8]) {
+        for (pid, pes) in self.demuxer.parse(data) {
+            self.parse_pes(pid, &pes);
+        }
+    }
+
+    /// Decode subtitles of every subtitling service, should be called after all data is parsed.
+    pub fn decode(mut self) -> Vec<DvbSubtitles> {
+        for (pid, pes) in self.demuxer.flush() {
+            self.parse_pes(pid, &pes);
+        }
+
+        let start = self.demuxer.start().unwrap_or(0);
+
+        self.services
+            .into_iter()
+            .filter_map(|mut x| {
+                x.decoder.flush();
+                let cues = x
+                    .decoder
+                    .cues
+                    .into_iter()
+                    .map(|mut cue| {
+                        cue.start_time -= start as f32 / 90000.0;
+                        cue.end_time -= start as f32 / 90000.0;
+                        cue
+                    })
+                    .collect::<Vec<_>>();
+                let subtitles = ImageSubtitles::new(x.decoder.display.0, x.decoder.display.1, cues);
+
+                (!subtitles.is_empty()).then_some(DvbSubtitles {
+                    language: x.language,
+                    pid: x.pid,
+                    subtitles,
+                })
+            })
+            .collect()
+    }
+
+    fn parse_pes(&mut self, pid: u16, pes: &[u8]) {
+        if self.services.is_empty() {
+            self.services = self.demuxer.streams().iter().flat_map(services).collect();
+        }
+
+        let Some(data) = ts::pes_data(pes) else {
+            return;
+        };
+
+        for service in self.services.iter_mut().filter(|x| x.pid == pid) {
+            let Some(pts) = service.clock.update(pes) else {
+                continue;
+            };
+
+            let pts = pts as f32 / 90000.0;
+
+            // Data identifier (0x20) and subtitle stream id (0x00).
+            if let Some(segments) = data.strip_prefix(&[0x20, 0x00]) {
+                service.decoder.parse_segments(segments, pts);
+            }
+        }
+    }
+}
+
+/// Subtitling services listed in subtitling descriptors of an elementary stream.
+fn services(stream: &ElementaryStream) -> Vec<Service> {
+    let mut services = vec![];
+    let mut descriptors = stream.descriptors.as_slice();
+
+    // Subtitling descriptors are only used with private pes data.
+    while stream.stream_type == 0x06 && descriptors.len() >= 2 {
+        let tag = descriptors[0];
+        let length = descriptors[1] as usize;
+        let data = descriptors.get(2..(2 + length)).unwrap_or_default();
+
+        if tag == SUBTITLING_DESCRIPTOR {
+            // language (3) + subtitling type (1) + composition page id (2)
+            // + ancillary page id (2)
+            for service in data.chunks_exact(8) {
+                services.push(Service {
+                    clock: PtsClock::default(),
+                    decoder: Decoder {
+                        ancillary_page: u16::from_be_bytes([service[6], service[7]]),
+                        composition_page: u16::from_be_bytes([service[4], service[5]]),
+                        ..Default::default()
+                    },
+                    language: String::from_utf8(service[..3].to_vec())
+                        .ok()
+                        .filter(|x| x.chars().all(|x| x.is_ascii_alphabetic())),
+                    pid: stream.pid,
+                });
+            }
+        }
+
+        descriptors = descriptors.get((2 + length)..).unwrap_or_default();
+    }
+
+    services
+}
+
+struct Service {
+    clock: PtsClock,
+    decoder: Decoder,
+    language: Option<String>,
+    pid: u16,
+}
+
+#[derive(Clone)]
+struct Region {
+    clut: u8,
+    /// Bits per pixel i.e. 2, 4 or 8.
+    depth: u8,
+    height: usize,
+    /// Objects placed inside region with their positions.
+    objects: Vec<(u16, usize, usize)>,
+    pixels: Vec<u8>,
+    width: usize,
+}
+
+/// Decoder of a single subtitling service i.e. composition page.
+struct Decoder {
+    ancillary_page: u16,
+    /// Colors of 2, 4 and 8 bit entries of color lookup tables.
+    cluts: BTreeMap<u8, [Vec<[u8; 4]>; 3]>,
+    composition_page: u16,
+    cues: Vec<ImageCue>,
+    display: (u32, u32),
+    /// Regions shown on page along with their positions.
+    page: Vec<(u8, usize, usize)>,
+    /// Displayed cue which hasn't ended yet along with its timeout.
+    pending: Option<(ImageCue, f32)>,
+    /// Page composition is received but display set isn't rendered yet.
+    received: Option<(f32, f32)>,
+    regions: BTreeMap<u8, Region>,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            ancillary_page: 0,
+            cluts: BTreeMap::new(),
+            composition_page: 0,
+            cues: vec![],
+            display: (720, 576),
+            page: vec![],
+            pending: None,
+            received: None,
+            regions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Decoder {
+    fn parse_segments(&mut self, mut data: &[u8], pts: f32) {
+        // sync byte (1) + segment type (1) + page id (2) + segment length (2)
+        while data.len() >= 6 && data[0] == 0x0f {
+            let kind = data[1];
+            let page = u16::from_be_bytes([data[2], data[3]]);
+            let length = u16::from_be_bytes([data[4], data[5]]) as usize;
+            let Some(segment) = data.get(6..(6 + length)) else {
+                break;
+            };
+
+            if page == self.composition_page || page == self.ancillary_page {
+                match kind {
+                    0x10 => self.page_composition(segment, pts),
+                    0x11 => self.region_composition(segment),
+                    0x12 => self.clut_definition(segment),
+                    0x13 => self.object_data(segment),
+                    0x14 => self.display_definition(segment),
+                    0x80 => self.end_of_display_set(),
+                    _ => (),
+                }
+            }
+
+            data = &data[(6 + length)..];
+        }
+    }
+
+    fn page_composition(&mut self, data: &[u8], pts: f32) {
+        if data.len() < 2 {
+            return;
+        }
+
+        // Display set without end segment is rendered when next one starts.
+        self.end_of_display_set();
+
+        // Mode change starts a new epoch, where every region and color lookup table is reset.
+        if (data[1] >> 2) & 0x03 == 2 {
+            self.regions.clear();
+            self.cluts.clear();
+        }
+
+        self.page = data[2..]
+            .chunks_exact(6)
+            .map(|x| {
+                (
+                    x[0],
+                    u16::from_be_bytes([x[2], x[3]]) as usize,
+                    u16::from_be_bytes([x[4], x[5]]) as usize,
+                )
+            })
+            .collect();
+        self.received = Some((pts, data[0] as f32));
+    }
+
+    fn region_composition(&mut self, data: &[u8]) {
+        if data.len() < 10 {
+            return;
+        }
+
+        let id = data[0];
+        let fill = data[1] & 0x08 != 0;
+        let width = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let height = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let depth = match (data[6] >> 2) & 0x07 {
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        let background = match depth {
+            2 => (data[9] >> 2) & 0x03,
+            4 => data[9] >> 4,
+            _ => data[8],
+        };
+
+        let region = self.regions.entry(id).or_insert_with(|| Region {
+            clut: 0,
+            depth,
+            height,
+            objects: vec![],
+            pixels: vec![0; width * height],
+            width,
+        });
+
+        if region.width != width || region.height != height || region.depth != depth {
+            region.pixels = vec![0; width * height];
+        }
+
+        region.clut = data[7];
+        region.depth = depth;
+        region.height = height;
+        region.width = width;
+
+        if fill {
+            region.pixels.fill(background);
+        }
+
+        let mut objects = &data[10..];
+        region.objects.clear();
+
+        while objects.len() >= 6 {
+            let object_type = objects[2] >> 6;
+            region.objects.push((
+                u16::from_be_bytes([objects[0], objects[1]]),
+                u16::from_be_bytes([objects[2] & 0x0f, objects[3]]) as usize,
+                u16::from_be_bytes([objects[4] & 0x0f, objects[5]]) as usize,
+            ));
+
+            // Character objects have foreground and background pixel codes.
+            let size = if matches!(object_type, 1 | 2) { 8 } else { 6 };
+            objects = objects.get(size..).unwrap_or_default();
+        }
+    }
+
+    fn clut_definition(&mut self, data: &[u8]) {
+        let Some(&id) = data.first() else {
+            return;
+        };
+
+        let clut = self.cluts.entry(id).or_insert_with(default_cluts);
+        let mut entries = data.get(2..).unwrap_or_default();
+
+        while entries.len() >= 2 {
+            let entry = entries[0] as usize;
+            let flags = entries[1];
+            let full_range = flags & 0x01 != 0;
+            let size = if full_range { 6 } else { 4 };
+            let Some(values) = entries.get(2..size) else {
+                break;
+            };
+
+            let (y, cr, cb, t) = if full_range {
+                (values[0], values[1], values[2], values[3])
+            } else {
+                (
+                    values[0] & 0xfc,
+                    ((values[0] & 0x03) << 6) | ((values[1] & 0xc0) >> 2),
+                    (values[1] & 0x3c) << 2,
+                    (values[1] & 0x03) << 6,
+                )
+            };
+            let color = rgba(y, cr, cb, t);
+
+            for (i, flag) in [0x80, 0x40, 0x20].iter().enumerate() {
+                if flags & flag != 0
+                    && let Some(x) = clut[i].get_mut(entry)
+                {
+                    *x = color;
+                }
+            }
+
+            entries = &entries[size..];
+        }
+    }
+
+    fn object_data(&mut self, data: &[u8]) {
+        if data.len() < 7 {
+            return;
+        }
+
+        let id = u16::from_be_bytes([data[0], data[1]]);
+        let coding_method = (data[2] >> 2) & 0x03;
+
+        // Character coded objects are not supported.
+        if coding_method != 0 {
+            return;
+        }
+
+        let top_length = u16::from_be_bytes([data[3], data[4]]) as usize;
+        let bottom_length = u16::from_be_bytes([data[5], data[6]]) as usize;
+        let top = data.get(7..(7 + top_length)).unwrap_or_default();
+        // Top field is repeated when bottom field is empty.
+        let bottom = match bottom_length {
+            0 => top,
+            _ => data
+                .get((7 + top_length)..(7 + top_length + bottom_length))
+                .unwrap_or_default(),
+        };
+
+        for region in self.regions.values_mut() {
+            for &(_, x, y) in region.objects.clone().iter().filter(|x| x.0 == id) {
+                for (field, block) in [top, bottom].iter().enumerate() {
+                    draw_field(region, block, x, y + field);
+                }
+            }
+        }
+    }
+
+    fn display_definition(&mut self, data: &[u8]) {
+        if data.len() >= 5 {
+            self.display = (
+                u16::from_be_bytes([data[1], data[2]]) as u32 + 1,
+                u16::from_be_bytes([data[3], data[4]]) as u32 + 1,
+            );
+        }
+    }
+
+    /// Render regions of page into a bitmap, previous cue is ended at start of this display set.
+    fn end_of_display_set(&mut self) {
+        let Some((pts, timeout)) = self.received.take() else {
+            return;
+        };
+
+        if let Some((mut cue, timeout)) = self.pending.take() {
+            cue.end_time = pts.min(cue.start_time + timeout);
+            self.cues.push(cue);
+        }
+
+        let (width, height) = (self.display.0 as usize, self.display.1 as usize);
+        let mut rgba = vec![0; width * height * 4];
+        let mut visible = false;
+
+        for (id, x, y) in &self.page {
+            let Some(region) = self.regions.get(id) else {
+                continue;
+            };
+
+            let clut = self
+                .cluts
+                .get(&region.clut)
+                .cloned()
+                .unwrap_or_else(default_cluts);
+            let colors = &clut[match region.depth {
+                2 => 0,
+                4 => 1,
+                _ => 2,
+            }];
+
+            for row in 0..region.height.min(height.saturating_sub(*y)) {
+                for column in 0..region.width.min(width.saturating_sub(*x)) {
+                    let color =
+                        colors[region.pixels[row * region.width + column] as usize % colors.len()];
+                    let i = ((y + row) * width + x + column) * 4;
+                    rgba[i..(i + 4)].copy_from_slice(&color);
+                    visible |= color[3] != 0;
+                }
+            }
+        }
+
+        if visible {
+            self.pending = Some((
+                ImageCue {
+                    bitmap: Bitmap::from_rgba(width as u32, height as u32, &rgba),
+                    end_time: pts + timeout,
+                    start_time: pts,
+                    x: 0,
+                    y: 0,
+                },
+                timeout,
+            ));
+        }
+    }
+
+    /// End last displayed cue by its timeout.
+    fn flush(&mut self) {
+        self.end_of_display_set();
+
+        if let Some((cue, _)) = self.pending.take() {
+            self.cues.push(cue);
+        }
+    }
+}
+
+/// Decode pixel data sub-blocks of a field into every other line of region.
+fn draw_field(region: &mut Region, mut data: &[u8], x: usize, mut y: usize) {
+    // Map tables used when pixel codes have less bits than region depth.
+    let mut map2to4 = [0x0, 0x7, 0x8, 0xf];
+    let mut map2to8 = [0x00, 0x77, 0x88, 0xff];
+    let mut map4to8: [u8; 16] = std::array::from_fn(|i| i as u8 * 0x11);
+    let mut column = x;
+
+    while let Some((&data_type, rest)) = data.split_first() {
+        data = rest;
+
+        let (bits, map): (u8, Vec<u8>) = match (data_type, region.depth) {
+            (0x10, 4) => (2, map2to4.to_vec()),
+            (0x10, 8) => (2, map2to8.to_vec()),
+            (0x10, _) => (2, vec![0, 1, 2, 3]),
+            (0x11, 2) => (4, (0..16).map(|x| x >> 2).collect()),
+            (0x11, 8) => (4, map4to8.to_vec()),
+            (0x11, _) => (4, (0..16).collect()),
+            (0x12, depth) => (8, (0..=255).map(|x: u8| x >> (8 - depth)).collect()),
+            (0x20, _) if data.len() >= 2 => {
+                map2to4 = [data[0] >> 4, data[0] & 0x0f, data[1] >> 4, data[1] & 0x0f];
+                data = &data[2..];
+                continue;
+            }
+            (0x21, _) if data.len() >= 4 => {
+                map2to8.copy_from_slice(&data[..4]);
+                data = &data[4..];
+                continue;
+            }
+            (0x22, _) if data.len() >= 8 => {
+                for (i, byte) in data[..8].iter().enumerate() {
+                    map4to8[i * 2] = byte >> 4;
+                    map4to8[i * 2 + 1] = byte & 0x0f;
+                }
+                data = &data[8..];
+                continue;
+            }
+            // End of object line.
+            (0xf0, _) => {
+                column = x;
+                y += 2;
+                continue;
+            }
+            _ => break,
+        };
+
+        let mut reader = BitReader { data, position: 0 };
+
+        for (code, run) in pixel_runs(&mut reader, bits) {
+            if y < region.height {
+                let end = (column + run).min(region.width);
+                let start = (y * region.width + column.min(end))..(y * region.width + end);
+                region.pixels[start].fill(map[code as usize]);
+            }
+
+            column += run;
+        }
+
+        data = data.get(reader.position.div_ceil(8)..).unwrap_or_default();
+    }
+}
+
+/// Decode pixel code string of given bits per pixel into (pixel code, run length) pairs.
+fn pixel_runs(reader: &mut BitReader, bits: u8) -> Vec<(u8, usize)> {
+    let mut runs = vec![];
+
+    while let Some(code) = reader.read(bits) {
+        if code != 0 {
+            runs.push((code as u8, 1));
+            continue;
+        }
+
+        let run = match bits {
+            2 => match (reader.read(1), reader.read(1)) {
+                (Some(1), _) => {
+                    // Switch 2 bit is part of run length, so it is read again.
+                    reader.position -= 1;
+                    (reader.read(3).map(|x| x + 3), reader.read(2))
+                }
+                (Some(0), Some(1)) => (Some(1), Some(0)),
+                (Some(0), Some(0)) => match reader.read(2) {
+                    Some(1) => (Some(2), Some(0)),
+                    Some(2) => (reader.read(4).map(|x| x + 12), reader.read(2)),
+                    Some(3) => (reader.read(8).map(|x| x + 29), reader.read(2)),
+                    _ => break,
+                },
+                _ => break,
+            },
+            4 => match reader.read(1) {
+                Some(0) => match reader.read(3) {
+                    Some(0) | None => break,
+                    Some(run) => (Some(run + 2), Some(0)),
+                },
+                Some(_) => match reader.read(1) {
+                    Some(0) => (reader.read(2).map(|x| x + 4), reader.read(4)),
+                    Some(_) => match reader.read(2) {
+                        Some(0) => (Some(1), Some(0)),
+                        Some(1) => (Some(2), Some(0)),
+                        Some(2) => (reader.read(4).map(|x| x + 9), reader.read(4)),
+                        Some(_) => (reader.read(8).map(|x| x + 25), reader.read(4)),
+                        None => break,
+                    },
+                    None => break,
+                },
+                None => break,
+            },
+            _ => match (reader.read(1), reader.read(7)) {
+                (Some(0), Some(0)) => break,
+                (Some(0), Some(run)) => (Some(run), Some(0)),
+                (Some(_), Some(run)) => (Some(run), reader.read(8)),
+                _ => break,
+            },
+        };
+
+        match run {
+            (Some(run), Some(code)) => runs.push((code as u8, run as usize)),
+            _ => break,
+        }
+    }
+
+    runs
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u8) -> Option<u32> {
+        let mut value = 0;
+
+        for _ in 0..bits {
+            let byte = self.data.get(self.position / 8)?;
+            value = (value << 1) | ((byte >> (7 - self.position % 8)) & 0x01) as u32;
+            self.position += 1;
+        }
+
+        Some(value)
+    }
+}
+
+/// Limited range y, cr, cb and transparency to rgba, luma value of zero is fully transparent.
+fn rgba(y: u8, cr: u8, cb: u8, t: u8) -> [u8; 4] {
+    if y == 0 {
+        return [0; 4];
+    }
+
+    let y = 1.164 * (y as f32 - 16.0);
+    let cr = cr as f32 - 128.0;
+    let cb = cb as f32 - 128.0;
+    let scale = |x: f32| x.round().clamp(0.0, 255.0) as u8;
+
+    [
+        scale(y + 1.596 * cr),
+        scale(y - 0.813 * cr - 0.391 * cb),
+        scale(y + 2.018 * cb),
+        255 - t,
+    ]
+}
+
+/// Default 2, 4 and 8 bit color lookup tables.
+fn default_cluts() -> [Vec<[u8; 4]>; 3] {
+    let clut2 = vec![
+        [0; 4],
+        [255, 255, 255, 255],
+        [0, 0, 0, 255],
+        [127, 127, 127, 255],
+    ];
+    let clut4 = (0..16)
+        .map(|i| {
+            let level = if i & 0x08 == 0 { 255 } else { 127 };
+            let channel = |bit: u8| if i & bit != 0 { level } else { 0 };
+
+            match i {
+                0 => [0; 4],
+                _ => [channel(0x01), channel(0x02), channel(0x04), 255],
+            }
+        })
+        .collect();
+    let clut8 = (0..=255_u8)
+        .map(|i| {
+            let channel = |low: u8, high: u8, base: u8, low_value: u8, high_value: u8| {
+                base + if i & low != 0 { low_value } else { 0 }
+                    + if i & high != 0 { high_value } else { 0 }
+            };
+
+            match (i, i & 0x88) {
+                (0, _) => [0; 4],
+                (1..8, _) => [
+                    channel(0x01, 0, 0, 255, 0),
+                    channel(0x02, 0, 0, 255, 0),
+                    channel(0x04, 0, 0, 255, 0),
+                    63,
+                ],
+                (_, 0x00) => [
+                    channel(0x01, 0x10, 0, 85, 170),
+                    channel(0x02, 0x20, 0, 85, 170),
+                    channel(0x04, 0x40, 0, 85, 170),
+                    255,
+                ],
+                (_, 0x08) => [
+                    channel(0x01, 0x10, 0, 85, 170),
+                    channel(0x02, 0x20, 0, 85, 170),
+                    channel(0x04, 0x40, 0, 85, 170),
+                    127,
+                ],
+                (_, 0x80) => [
+                    channel(0x01, 0x10, 127, 43, 85),
+                    channel(0x02, 0x20, 127, 43, 85),
+                    channel(0x04, 0x40, 127, 43, 85),
+                    255,
+                ],
+                _ => [
+                    channel(0x01, 0x10, 0, 43, 85),
+                    channel(0x02, 0x20, 0, 43, 85),
+                    channel(0x04, 0x40, 0, 43, 85),
+                    255,
+                ],
+            }
+        })
+        .collect();
+
+    [clut2, clut4, clut8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0x0f, kind, 0x00, 0x01];
+        segment.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    #[test]
+    fn decode_display_sets() {
+        // 4 bit pixel code string: 20 pixels of entry 1, end of string and end of line.
+        let line = [0x11, 0x0e, 0xb1, 0x00, 0xf0];
+        let mut object = vec![0x00, 0x00, 0x00, 0x00, line.len() as u8 * 5, 0x00, 0x00];
+        object.extend(line.repeat(5));
+
+        let mut decoder = Decoder {
+            composition_page: 1,
+            ..Default::default()
+        };
+        let display_set = [
+            // Page with 5s timeout and a new epoch, region 0 is shown at (100, 400).
+            segment(0x10, &[0x05, 0x08, 0x00, 0x00, 0x00, 0x64, 0x01, 0x90]),
+            // 40x10 region with 4 bit depth, object 0 is placed at (0, 0).
+            segment(
+                0x11,
+                &[
+                    0x00, 0x08, 0x00, 0x28, 0x00, 0x0a, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00,
+                ],
+            ),
+            // Entry 1 of 4 bit clut 0 is opaque white.
+            segment(0x12, &[0x00, 0x00, 0x01, 0x41, 0xeb, 0x80, 0x80, 0x00]),
+            segment(0x13, &object),
+            segment(0x80, &[]),
+        ]
+        .concat();
+        let clear = [segment(0x10, &[0x05, 0x00]), segment(0x80, &[])].concat();
+
+        decoder.parse_segments(&display_set, 2.0);
+        decoder.parse_segments(&clear, 4.0);
+        decoder.flush();
+
+        assert_eq!(decoder.cues.len(), 1);
+
+        let cue = &decoder.cues[0];
+        assert_eq!((cue.start_time, cue.end_time), (2.0, 4.0));
+        assert_eq!((cue.bitmap.width(), cue.bitmap.height()), (720, 576));
+
+        let (x, y, bitmap) = cue.bitmap.crop().unwrap();
+        assert_eq!((x, y), (100, 400));
+        assert_eq!((bitmap.width(), bitmap.height()), (20, 10));
+    }
+}
@@ -10,7 +10,8 @@
 
 */
 
-use super::{Cue, Subtitles};
+use super::{Bitmap, Cue, CueStyle, ImageCue, ImageSubtitles, Subtitles};
+use base64::Engine;
 use serde::Deserialize;
 
 pub use quick_xml::de::DeError;
@@ -81,7 +82,6 @@ impl Span {
 
         if let Some(color) = &self.color {
             value = format!("{{font color=\"{color}\">{value}{{/font}}");
-            value = format!("<font color=\"{color}\">{value}</font>");
         }
 
         value
@@ -90,10 +90,78 @@ impl Span {
 
 #[derive(Deserialize)]
 pub struct TT {
+    /// Size of root container, only pixel values are used for positioning images.
+    #[serde(rename = "@extent")]
+    pub extent: Option<String>,
+    #[serde(rename = "head")]
+    pub head: Option<Head>,
     #[serde(rename = "body")]
     pub body: Body,
 }
 
+#[derive(Deserialize)]
+pub struct Head {
+    #[serde(rename = "layout")]
+    pub layout: Option<Layout>,
+    #[serde(rename = "metadata")]
+    pub metadata: Option<Metadata>,
+    #[serde(rename = "styling")]
+    pub styling: Option<Styling>,
+}
+
+#[derive(Deserialize)]
+pub struct Layout {
+    #[serde(rename = "region", default)]
+    pub regions: Vec<Style>,
+}
+
+#[derive(Deserialize)]
+pub struct Metadata {
+    #[serde(rename = "image", default)]
+    pub images: Vec<Image>,
+}
+
+/// Base64 encoded png image (`smpte:image`).
+#[derive(Deserialize)]
+pub struct Image {
+    #[serde(rename = "@xml:id")]
+    pub id: Option<String>,
+    #[serde(rename = "$value", default)]
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+pub struct Styling {
+    #[serde(rename = "style", default)]
+    pub styles: Vec<Style>,
+}
+
+/// Styling attributes of `style` and `region` elements.
+#[derive(Clone, Default, Deserialize)]
+pub struct Style {
+    #[serde(rename = "@color")]
+    pub color: Option<String>,
+    #[serde(rename = "@displayAlign")]
+    pub display_align: Option<String>,
+    #[serde(rename = "@extent")]
+    pub extent: Option<String>,
+    #[serde(rename = "@fontStyle")]
+    pub font_style: Option<String>,
+    #[serde(rename = "@fontWeight")]
+    pub font_weight: Option<String>,
+    #[serde(rename = "@xml:id")]
+    pub id: Option<String>,
+    #[serde(rename = "@origin")]
+    pub origin: Option<String>,
+    /// Referenced style ids separated by whitespace.
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "@textAlign")]
+    pub text_align: Option<String>,
+    #[serde(rename = "@textDecoration")]
+    pub text_decoration: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct Body {
     #[serde(rename = "div", default)]
@@ -102,26 +170,181 @@ pub struct Body {
 
 #[derive(Deserialize)]
 pub struct Div {
+    /// Image shown by this div (`smpte:backgroundImage`), either `#id` of image
+    /// inside metadata or `urn:mpeg:14496-30:subs:N` for nth mp4 subsample.
+    #[serde(rename = "@backgroundImage")]
+    pub background_image: Option<String>,
+    #[serde(rename = "@begin")]
+    pub begin: Option<String>,
+    #[serde(rename = "@end")]
+    pub end: Option<String>,
     #[serde(rename = "p", default)]
     pub paragraphs: Vec<Paragraph>,
+    #[serde(rename = "@region")]
+    pub region: Option<String>,
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct Paragraph {
     #[serde(rename = "@begin")]
     pub begin: String,
+    #[serde(rename = "@color")]
+    pub color: Option<String>,
     #[serde(rename = "@end")]
     pub end: String,
+    #[serde(rename = "@fontStyle")]
+    pub font_style: Option<String>,
+    #[serde(rename = "@fontWeight")]
+    pub font_weight: Option<String>,
+    #[serde(rename = "@region")]
+    pub region: Option<String>,
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "@textAlign")]
+    pub text_align: Option<String>,
+    #[serde(rename = "@textDecoration")]
+    pub text_decoration: Option<String>,
     #[serde(rename = "$value")]
     pub value: String,
 }
 
+impl Style {
+    /// Override attributes which are present in other style.
+    fn merge(&mut self, other: &Style) {
+        let merge = |x: &mut Option<String>, y: &Option<String>| {
+            if y.is_some() {
+                x.clone_from(y);
+            }
+        };
+
+        merge(&mut self.color, &other.color);
+        merge(&mut self.display_align, &other.display_align);
+        merge(&mut self.extent, &other.extent);
+        merge(&mut self.font_style, &other.font_style);
+        merge(&mut self.font_weight, &other.font_weight);
+        merge(&mut self.origin, &other.origin);
+        merge(&mut self.text_align, &other.text_align);
+        merge(&mut self.text_decoration, &other.text_decoration);
+    }
+
+    fn cue_style(&self) -> CueStyle {
+        // Only percentage values can be mapped without knowing root container extent.
+        let percentages = |x: &Option<String>| {
+            let mut values = x
+                .as_deref()?
+                .split_whitespace()
+                .map(|x| x.strip_suffix('%').and_then(|x| x.parse::<f32>().ok()));
+            Some((values.next()??, values.next()??))
+        };
+        let origin = percentages(&self.origin);
+        let extent = percentages(&self.extent);
+
+        let line = match (self.display_align.as_deref(), origin, extent) {
+            (Some("before"), Some((_, y)), _) => Some(y),
+            (Some("center"), Some((_, y)), Some((_, height))) => Some(y + height / 2.0),
+            (_, Some((_, y)), Some((_, height))) => Some(y + height),
+            (_, Some((_, y)), None) => Some(y),
+            (Some("before"), None, _) => Some(0.0),
+            _ => None,
+        };
+        let position = match (origin, extent) {
+            (Some((x, _)), Some((width, _))) => Some(x + width / 2.0),
+            (Some((x, _)), None) => Some(x),
+            _ => None,
+        };
+
+        CueStyle {
+            align: self
+                .text_align
+                .clone()
+                .filter(|x| matches!(x.as_str(), "start" | "center" | "end" | "left" | "right")),
+            bold: self.font_weight.as_deref() == Some("bold"),
+            color: self.color.clone(),
+            italic: matches!(self.font_style.as_deref(), Some("italic" | "oblique")),
+            line,
+            position,
+            underline: self
+                .text_decoration
+                .as_deref()
+                .is_some_and(|x| x.contains("underline")),
+        }
+    }
+}
+
 impl TT {
+    /// Find styles by their ids along with styles referenced by them.
+    fn styles(&self, ids: Option<&String>, depth: u8) -> Vec<Style> {
+        let (Some(ids), Some(styling)) = (ids, self.head.as_ref().and_then(|x| x.styling.as_ref()))
+        else {
+            return vec![];
+        };
+
+        let mut found = vec![];
+
+        for id in ids.split_whitespace() {
+            if let Some(style) = styling.styles.iter().find(|x| x.id.as_deref() == Some(id)) {
+                // Referenced styles come first so that they can be overridden.
+                // Depth is limited to break cyclic references.
+                if depth < 8 {
+                    found.extend(self.styles(style.style.as_ref(), depth + 1));
+                }
+
+                found.push(style.clone());
+            }
+        }
+
+        found
+    }
+
+    /// Find region by its id along with styles referenced by it.
+    fn region(&self, id: Option<&String>) -> Vec<Style> {
+        let (Some(id), Some(layout)) = (id, self.head.as_ref().and_then(|x| x.layout.as_ref()))
+        else {
+            return vec![];
+        };
+
+        let Some(region) = layout.regions.iter().find(|x| x.id.as_ref() == Some(id)) else {
+            return vec![];
+        };
+
+        let mut found = self.styles(region.style.as_ref(), 0);
+        found.push(region.clone());
+        found
+    }
+
+    /// Resolve styles of a paragraph in order region -> div -> paragraph.
+    fn resolve_style(&self, div: &Div, paragraph: &Paragraph) -> Style {
+        let mut resolved = Style::default();
+
+        for style in self
+            .region(paragraph.region.as_ref().or(div.region.as_ref()))
+            .iter()
+            .chain(self.styles(div.style.as_ref(), 0).iter())
+            .chain(self.styles(paragraph.style.as_ref(), 0).iter())
+        {
+            resolved.merge(style);
+        }
+
+        resolved.merge(&Style {
+            color: paragraph.color.clone(),
+            font_style: paragraph.font_style.clone(),
+            font_weight: paragraph.font_weight.clone(),
+            text_align: paragraph.text_align.clone(),
+            text_decoration: paragraph.text_decoration.clone(),
+            ..Default::default()
+        });
+        resolved
+    }
+
     pub(super) fn into_cues(self) -> Vec<Cue> {
         let mut cues = vec![];
 
-        for div in self.body.divs {
+        for div in &self.body.divs {
             for paragraph in &div.paragraphs {
+                let style = self.resolve_style(div, paragraph).cue_style();
+
                 cues.push(Cue {
                     end_time: duration(&paragraph.end).unwrap_or_else(|_| {
                         panic!(
@@ -140,13 +363,14 @@ impl TT {
                         .replace("{/u}", "</u>")
                         .replace("{font", "<font")
                         .replace("{/font}", "</font>"),
-                    settings: String::new(),
+                    settings: style.vtt_settings(),
                     start_time: duration(&paragraph.begin).unwrap_or_else(|_| {
                         panic!(
                             "mp4parser.ttmltextparser: could'nt convert {} to seconds.",
                             paragraph.end
                         )
                     }),
+                    style,
                 });
             }
         }
@@ -157,6 +381,98 @@ impl TT {
     pub fn into_subtitles(self) -> Subtitles {
         Subtitles::new(self.into_cues())
     }
+
+    /// Decode png images shown by divs, `images` are the subsamples of mp4 sample
+    /// which are referenced by their index.
+    pub fn image_subtitles(&self, images: &[Vec<u8>]) -> crate::Result<ImageSubtitles> {
+        let pixels = |x: &Option<String>| {
+            let mut values = x
+                .as_deref()?
+                .split_whitespace()
+                .map(|x| x.strip_suffix("px").and_then(|x| x.parse::<f32>().ok()));
+            Some((values.next()??, values.next()??))
+        };
+        let (width, height) = pixels(&self.extent).unwrap_or((1920.0, 1080.0));
+        let mut cues = vec![];
+
+        for div in &self.body.divs {
+            let (Some(image), Some(begin), Some(end)) =
+                (&div.background_image, &div.begin, &div.end)
+            else {
+                continue;
+            };
+
+            let data = if let Some(id) = image.strip_prefix('#') {
+                let value = self
+                    .head
+                    .as_ref()
+                    .and_then(|x| x.metadata.as_ref())
+                    .and_then(|x| x.images.iter().find(|x| x.id.as_deref() == Some(id)))
+                    .ok_or_else(|| crate::Error::new(format!("ttml image {image} not found.")))?
+                    .value
+                    .split_whitespace()
+                    .collect::<String>();
+                base64::engine::general_purpose::STANDARD
+                    .decode(value)
+                    .map_err(|_| {
+                        crate::Error::new_decode(format!("ttml image {image} as base64."))
+                    })?
+            } else if let Some(index) = image
+                .strip_prefix("urn:mpeg:14496-30:subs:")
+                .and_then(|x| x.parse::<usize>().ok())
+            {
+                images
+                    .get(index.wrapping_sub(1))
+                    .ok_or_else(|| crate::Error::new(format!("ttml image {image} not found.")))?
+                    .clone()
+            } else {
+                return Err(crate::Error::new(format!(
+                    "ttml image {image} is not supported."
+                )));
+            };
+
+            let bitmap = Bitmap::from_png(&data)?;
+            let mut region = Style::default();
+
+            for style in self.region(div.region.as_ref()) {
+                region.merge(&style);
+            }
+
+            // Origin is either in percentages or pixels of root container.
+            let origin = region.origin.as_deref().and_then(|x| {
+                let mut values = x.split_whitespace().map(|x| {
+                    x.strip_suffix('%')
+                        .and_then(|x| x.parse::<f32>().ok())
+                        .map(|x| x / 100.0)
+                        .zip(Some(true))
+                        .or_else(|| x.strip_suffix("px")?.parse::<f32>().ok().zip(Some(false)))
+                });
+                let (x, x_percent) = values.next()??;
+                let (y, y_percent) = values.next()??;
+                Some((
+                    if x_percent { x * width } else { x },
+                    if y_percent { y * height } else { y },
+                ))
+            });
+            // Images without region are centered near bottom of frame.
+            let (x, y) = origin.unwrap_or((
+                (width - bitmap.width() as f32) / 2.0,
+                height * 0.9 - bitmap.height() as f32,
+            ));
+
+            cues.push(ImageCue {
+                bitmap,
+                end_time: duration(end)
+                    .map_err(|_| crate::Error::new_decode(format!("{end} as seconds.")))?,
+                start_time: duration(begin)
+                    .map_err(|_| crate::Error::new_decode(format!("{begin} as seconds.")))?,
+                x: x.max(0.0) as u32,
+                y: y.max(0.0) as u32,
+            });
+        }
+
+        Ok(ImageSubtitles::new(width as u32, height as u32, cues))
+    }
 }
 
 fn duration(duration: &str) -> Result<f32, std::num::ParseFloatError> {
@@ -184,3 +500,35 @@ fn duration(duration: &str) -> Result<f32, std::num::ParseFloatError> {
 
     Ok(total_seconds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_subtitles() {
+        let png = Bitmap::new(200, 40, vec![[0; 4], [255; 4]], vec![1; 200 * 40]).to_png();
+        let xml = format!(
+            r##"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt" tts:extent="1280px 720px">
+<head><metadata><smpte:image xml:id="img0" imageType="PNG" encoding="Base64">{}</smpte:image></metadata>
+<layout><region xml:id="r0" tts:origin="10% 80%" tts:extent="50% 10%"/></layout></head>
+<body><div begin="00:00:01.000" end="00:00:03.500" region="r0" smpte:backgroundImage="#img0"/>
+<div begin="00:00:04.000" end="00:00:05.000" smpte:backgroundImage="urn:mpeg:14496-30:subs:1"/></body></tt>"##,
+            base64::engine::general_purpose::STANDARD.encode(&png)
+        );
+        let ttml = parse(&xml).unwrap();
+        let subtitles = ttml.image_subtitles(&[png]).unwrap();
+        let cues = subtitles.cues();
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start_time, cues[0].end_time), (1.0, 3.5));
+        assert_eq!((cues[0].bitmap.width(), cues[0].bitmap.height()), (200, 40));
+        // Region origin in percentages of root container extent.
+        assert_eq!((cues[0].x, cues[0].y), (128, 576));
+        assert_eq!((cues[1].start_time, cues[1].end_time), (4.0, 5.0));
+        // Images without region are centered near bottom of frame.
+        assert_eq!((cues[1].x, cues[1].y), (540, 608));
+
+        assert!(ttml.image_subtitles(&[]).is_err());
+    }
+}
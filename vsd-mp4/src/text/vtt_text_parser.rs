@@ -0,0 +1,267 @@
+//! Parse webvtt content.
+
+/*
+    REFERENCES
+    ----------
+
+    1. https://github.com/shaka-project/shaka-player/blob/9ce2f675d88d5de6f779f2a62a4f4af2bcc14611/lib/text/vtt_text_parser.js
+    2. https://w3c.github.io/webvtt
+    3. https://datatracker.ietf.org/doc/html/rfc8216#section-3.5
+
+*/
+
+use super::{Cue, CueStyle, Subtitles};
+use crate::{Error, Result};
+
+/// Mpegts timestamps are 33 bit values which rollover after ~26.5 hours.
+const MPEGTS_ROLLOVER: u64 = 1 << 33;
+
+/// Parse webvtt subtitles which are split into multiple segments.
+///
+/// Cue times of every segment are mapped using its `X-TIMESTAMP-MAP` header (if present),
+/// relative to mapped time of first segment. Segments without this header use offset of
+/// previous segment.
+#[derive(Default)]
+pub struct VttTextParser {
+    /// Mapped time (in seconds) of first segment having `X-TIMESTAMP-MAP` header.
+    base: Option<f64>,
+    /// Mpegts time of previous segment, used for detecting rollovers.
+    last_mpegts: Option<u64>,
+    /// Offset (in seconds) of previous segment, also used by segments without
+    /// `X-TIMESTAMP-MAP` header.
+    offset: f64,
+    rollovers: u64,
+    subtitles: Option<Subtitles>,
+}
+
+impl VttTextParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a single webvtt segment and merge it with previously parsed segments.
+    /// `offset` (in seconds) is added to mapped cue times of only this segment.
+    pub fn parse_segment(&mut self, text: &str, offset: Option<f32>) -> Result<()> {
+        let subtitles = self.parse(text, offset.unwrap_or(0.0) as f64)?;
+
+        match &mut self.subtitles {
+            Some(x) => x.merge(subtitles),
+            None => self.subtitles = Some(subtitles),
+        }
+
+        Ok(())
+    }
+
+    /// Merged subtitles of all parsed segments.
+    pub fn subtitles(self) -> Subtitles {
+        self.subtitles.unwrap_or(Subtitles::new(vec![]))
+    }
+
+    fn parse(&mut self, text: &str, offset: f64) -> Result<Subtitles> {
+        let text = text
+            .trim_start_matches('\u{feff}')
+            .replace("\r\n", "\n")
+            .replace('\r', "\n");
+
+        // Empty segments are used for gaps in between.
+        if text.trim().is_empty() {
+            return Ok(Subtitles::new(vec![]));
+        }
+
+        let mut blocks = text.split("\n\n").filter(|x| !x.trim().is_empty());
+        let header = blocks.next().unwrap_or_default().trim_start();
+
+        if !header.starts_with("WEBVTT") {
+            return Err(Error::new_decode(
+                "webvtt content (WEBVTT header not found).",
+            ));
+        }
+
+        if let Some(map) = header
+            .lines()
+            .find_map(|x| x.trim().strip_prefix("X-TIMESTAMP-MAP="))
+        {
+            let mut mpegts = None;
+            let mut local = None;
+
+            for part in map.split(',') {
+                if let Some(value) = part.trim().strip_prefix("MPEGTS:") {
+                    mpegts = value.trim().parse::<u64>().ok();
+                } else if let Some(value) = part.trim().strip_prefix("LOCAL:") {
+                    local = timestamp(value.trim());
+                }
+            }
+
+            let (Some(mpegts), Some(local)) = (mpegts, local) else {
+                return Err(Error::new_decode(format!(
+                    "webvtt X-TIMESTAMP-MAP header ({map})."
+                )));
+            };
+
+            if self
+                .last_mpegts
+                .is_some_and(|x| mpegts + MPEGTS_ROLLOVER / 2 < x)
+            {
+                self.rollovers += 1;
+            }
+
+            self.last_mpegts = Some(mpegts);
+
+            let mapped = (mpegts + self.rollovers * MPEGTS_ROLLOVER) as f64 / 90000.0 - local;
+            self.offset = mapped - *self.base.get_or_insert(mapped);
+        }
+
+        let offset = self.offset + offset;
+
+        let mut cues = vec![];
+
+        for block in blocks {
+            if block.starts_with("NOTE")
+                || block.starts_with("STYLE")
+                || block.starts_with("REGION")
+            {
+                continue;
+            }
+
+            let mut lines = block.lines();
+            let mut id = String::new();
+            let mut timing = lines.next().unwrap_or_default();
+
+            if !timing.contains("-->") {
+                id = timing.to_owned();
+                timing = lines.next().unwrap_or_default();
+            }
+
+            // Invalid cue blocks are ignored as per specification.
+            let Some((start, rest)) = timing.split_once("-->") else {
+                continue;
+            };
+            let rest = rest.trim();
+            let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+            let (Some(start_time), Some(end_time)) = (timestamp(start.trim()), timestamp(end))
+            else {
+                continue;
+            };
+
+            let settings = settings.split_whitespace().collect::<Vec<_>>().join(" ");
+
+            cues.push(Cue {
+                end_time: (end_time + offset) as f32,
+                _id: id,
+                payload: lines.collect::<Vec<_>>().join("\n"),
+                style: CueStyle::from_vtt_settings(&settings),
+                settings,
+                start_time: (start_time + offset) as f32,
+            });
+        }
+
+        Ok(Subtitles::new(cues))
+    }
+}
+
+/// Parse complete webvtt content.
+pub fn parse(text: &str) -> Result<Subtitles> {
+    let mut parser = VttTextParser::new();
+    parser.parse_segment(text, None)?;
+    Ok(parser.subtitles())
+}
+
+/// Parse `hh:mm:ss.ttt` or `mm:ss.ttt` timestamp in seconds.
+fn timestamp(value: &str) -> Option<f64> {
+    let (time, milliseconds) = value.split_once('.')?;
+    let mut seconds = 0.0;
+
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.parse::<u64>().ok()? as f64;
+    }
+
+    Some(
+        seconds + milliseconds.parse::<u64>().ok()? as f64 / 10_f64.powi(milliseconds.len() as i32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_segments(segments: &[(&str, Option<f32>)]) -> String {
+        let mut parser = VttTextParser::new();
+
+        for (text, offset) in segments {
+            parser.parse_segment(text, *offset).unwrap();
+        }
+
+        // Cue timings are followed by a space even without settings.
+        parser
+            .subtitles()
+            .as_vtt()
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn timestamp_map() {
+        let vtt = parse_segments(&[
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:900000,LOCAL:00:00:00.000\n\n00:00:01.000 --> 00:00:02.000\nA",
+                None,
+            ),
+            // Mapped 10 seconds after first segment.
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=LOCAL:00:00:01.000,MPEGTS:1890000\n\n00:00:01.000 --> 00:00:02.000\nB",
+                None,
+            ),
+            // Offset of previous segment is carried forward, extra offset is only used for this segment.
+            ("WEBVTT\n\n00:00:03.000 --> 00:00:04.000\nC", Some(-1.0)),
+            ("WEBVTT\n\n00:00:05.000 --> 00:00:06.000\nD", None),
+        ]);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nA\n\n00:00:11.000 --> 00:00:12.000\nB\n\n00:00:12.000 --> 00:00:13.000\nC\n\n00:00:15.000 --> 00:00:16.000\nD\n"
+        );
+    }
+
+    #[test]
+    fn timestamp_map_rollover() {
+        let vtt = parse_segments(&[
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:8589844592,LOCAL:00:00:00.000\n\n00:00:00.000 --> 00:00:00.500\nA",
+                None,
+            ),
+            // Mpegts time wrapped around, 2 seconds after first segment.
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:90000,LOCAL:00:00:00.000\n\n00:00:00.000 --> 00:00:01.000\nB",
+                None,
+            ),
+        ]);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:00.000 --> 00:00:00.500\nA\n\n00:00:02.000 --> 00:00:03.000\nB\n"
+        );
+    }
+
+    #[test]
+    fn repeated_cue() {
+        // Cue spanning segment boundary is present in both segments.
+        let vtt = parse_segments(&[
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:900000,LOCAL:00:00:00.000\n\n00:00:01.000 --> 00:00:03.000\nA",
+                None,
+            ),
+            (
+                "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:900000,LOCAL:00:00:00.000\n\n00:00:01.000 --> 00:00:03.000\nA\n\n00:00:03.000 --> 00:00:04.000 line:0\nA\n\n00:00:03.500 --> 00:00:04.000\nB",
+                None,
+            ),
+        ]);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:01.000 --> 00:00:03.000\nA\n\n00:00:03.000 --> 00:00:04.000 line:0\nA\n\n00:00:03.500 --> 00:00:04.000\nB\n"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::playlist::MediaPlaylist;
+use clap::ValueEnum;
 use std::collections::HashSet;
 
 pub struct Prompter {
@@ -17,8 +19,11 @@ pub struct SelectOptions {
 pub struct Video {
     pub all: bool,
     pub preference: VideoPreference,
+    pub ranges: HashSet<String>,
     pub resolutions: HashSet<(u16, u16)>,
+    pub roles: HashSet<String>,
     pub skip: bool,
+    pub viewpoints: HashSet<String>,
 }
 
 #[derive(Debug)]
@@ -28,36 +33,66 @@ pub enum VideoPreference {
     Worst,
 }
 
-#[derive(Debug)]
+/// Video range preferred while sorting video streams.
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum RangePreference {
+    /// Sort only by resolution and bandwidth.
+    #[default]
+    Any,
+    /// Sort PQ and HLG streams before SDR streams.
+    Hdr,
+    /// Sort SDR streams before PQ and HLG streams.
+    Sdr,
+}
+
+#[derive(Debug, Default)]
 pub struct AudioSubs {
+    pub accessibility: HashSet<String>,
     pub all: bool,
+    pub characteristics: HashSet<String>,
+    pub default: bool,
+    pub forced: bool,
+    pub groups: HashSet<String>,
     pub languages: HashSet<String>,
+    pub no_forced: bool,
+    pub roles: HashSet<String>,
     pub skip: bool,
+    pub viewpoints: HashSet<String>,
 }
 
 impl SelectOptions {
     pub fn parse(data: &str) -> SelectOptions {
         let mut auto = SelectOptions {
-            audio: AudioSubs {
-                all: false,
-                languages: HashSet::new(),
-                skip: false,
-            },
+            audio: AudioSubs::default(),
             stream_numbers: HashSet::new(),
-            subs: AudioSubs {
-                all: false,
-                languages: HashSet::new(),
-                skip: false,
-            },
+            subs: AudioSubs::default(),
             video: Video {
                 all: false,
                 preference: VideoPreference::None,
+                ranges: HashSet::new(),
                 resolutions: HashSet::new(),
+                roles: HashSet::new(),
                 skip: false,
+                viewpoints: HashSet::new(),
             },
         };
 
+        // Queries like `char:public.accessibility.describes-video` also contains `:`
+        // so they are joined back with the previous stream type.
+        let mut streams: Vec<String> = vec![];
+
         for stream in data.split_terminator(':') {
+            if let Some(last) = streams.last_mut()
+                && !stream.contains('=')
+            {
+                last.push(':');
+                last.push_str(stream);
+            } else {
+                streams.push(stream.to_owned());
+            }
+        }
+
+        for stream in &streams {
             if let Some((_type, queries)) = stream.split_once('=') {
                 match _type {
                     "v" => {
@@ -76,6 +111,10 @@ impl SelectOptions {
                                 "low" | "min" | "worst" => {
                                     auto.video.preference = VideoPreference::Worst;
                                 }
+                                // video ranges
+                                "dv" | "hdr" | "hdr10" | "hlg" | "pq" | "sdr" => {
+                                    auto.video.ranges.insert(query.to_owned());
+                                }
                                 "144p" => {
                                     let _ = auto.video.resolutions.insert((256, 144));
                                 }
@@ -107,7 +146,13 @@ impl SelectOptions {
                                     let _ = auto.video.resolutions.insert((7680, 4320));
                                 }
                                 x => {
-                                    if let Some((w, h)) = x.split_once('x')
+                                    if let Some(range) = x.strip_prefix("range:") {
+                                        auto.video.ranges.insert(range.to_lowercase());
+                                    } else if let Some(role) = x.strip_prefix("role:") {
+                                        auto.video.roles.insert(role.to_owned());
+                                    } else if let Some(viewpoint) = x.strip_prefix("view:") {
+                                        auto.video.viewpoints.insert(viewpoint.to_owned());
+                                    } else if let Some((w, h)) = x.split_once('x')
                                         && let (Ok(w), Ok(h)) = (w.parse::<u16>(), h.parse::<u16>())
                                         {
                                             auto.video.resolutions.insert((w, h));
@@ -125,9 +170,7 @@ impl SelectOptions {
                             match query {
                                 "all" => auto.audio.all = true,
                                 "skip" => auto.audio.skip = true,
-                                x => {
-                                    auto.audio.languages.insert(x.to_owned());
-                                }
+                                x => auto.audio.parse_query(x),
                             }
                         }
                     }
@@ -140,9 +183,7 @@ impl SelectOptions {
                             match query {
                                 "all" => auto.subs.all = true,
                                 "skip" => auto.subs.skip = true,
-                                x => {
-                                    auto.subs.languages.insert(x.to_owned());
-                                }
+                                x => auto.subs.parse_query(x),
                             }
                         }
                     }
@@ -155,7 +196,110 @@ impl SelectOptions {
     }
 }
 
+impl Video {
+    /// Returns true if stream matches any video range filter or no video range filter is used.
+    /// Streams without any video range are considered as SDR.
+    pub fn matches_range(&self, stream: &MediaPlaylist) -> bool {
+        if self.ranges.is_empty() {
+            return true;
+        }
+
+        let video_range = stream
+            .video_range
+            .as_deref()
+            .unwrap_or("SDR")
+            .to_lowercase();
+        let dynamic_range = stream
+            .dynamic_range
+            .as_ref()
+            .map(|x| x.to_lowercase().replace(' ', "-"));
+
+        self.ranges.contains(&video_range)
+            || (stream.is_hdr() && self.ranges.contains("hdr"))
+            || dynamic_range.is_some_and(|x| {
+                self.ranges.contains(&x) || (x == "dolby-vision" && self.ranges.contains("dv"))
+            })
+    }
+
+    /// Returns true if stream matches any role or viewpoint filter.
+    pub fn matches(&self, stream: &MediaPlaylist) -> bool {
+        if stream.roles.iter().any(|x| self.roles.contains(x)) {
+            return true;
+        }
+
+        stream
+            .viewpoint
+            .as_ref()
+            .is_some_and(|x| self.viewpoints.contains(x))
+    }
+}
+
 impl AudioSubs {
+    fn parse_query(&mut self, query: &str) {
+        match query.split_once(':') {
+            Some(("access", x)) => {
+                self.accessibility.insert(x.to_owned());
+            }
+            Some(("char", x)) => {
+                self.characteristics.insert(x.to_owned());
+            }
+            Some(("group", x)) => {
+                self.groups.insert(x.to_owned());
+            }
+            Some(("role", x)) => {
+                self.roles.insert(x.to_owned());
+            }
+            Some(("view", x)) => {
+                self.viewpoints.insert(x.to_owned());
+            }
+            _ => match query {
+                "default" => self.default = true,
+                "forced" => self.forced = true,
+                "noforced" => self.no_forced = true,
+                x => {
+                    self.languages.insert(x.to_owned());
+                }
+            },
+        }
+    }
+
+    /// Returns true if stream matches any rendition filter (default, forced, char, group, role, access, view).
+    pub fn matches(&self, stream: &MediaPlaylist) -> bool {
+        if stream.roles.iter().any(|x| self.roles.contains(x))
+            || stream.accessibility.iter().any(|x| self.accessibility.contains(x))
+        {
+            return true;
+        }
+
+        if let Some(viewpoint) = &stream.viewpoint
+            && self.viewpoints.contains(viewpoint)
+        {
+            return true;
+        }
+
+        if self.default && stream.default {
+            return true;
+        }
+
+        if self.forced && stream.forced {
+            return true;
+        }
+
+        if let Some(group_id) = &stream.group_id
+            && self.groups.contains(group_id)
+        {
+            return true;
+        }
+
+        if let Some(characteristics) = &stream.characteristics {
+            return characteristics
+                .split(',')
+                .any(|x| self.characteristics.contains(x.trim()));
+        }
+
+        false
+    }
+
     pub fn contains_exact_lang(&mut self, s_lang: &str) -> bool {
         let languages = self.languages.clone();
 
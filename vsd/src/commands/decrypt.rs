@@ -0,0 +1,251 @@
+use super::save::keys_parser;
+use crate::{
+    downloader::{Decrypter, SidecarSegments},
+    playlist::{Key, KeyMethod},
+    utils::{is_mp4, split_init},
+};
+use anyhow::{Result, bail};
+use clap::{Args, ValueEnum};
+use kdam::term::Colorizer;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Decrypt already downloaded encrypted files.
+#[derive(Args, Clone, Debug)]
+pub struct Decrypt {
+    /// Path of encrypted file or directory of segments (created by --no-merge flag).
+    #[arg(required = true)]
+    input: PathBuf,
+
+    /// Path for decrypted output file.
+    /// If input is a directory, then this is a directory where decrypted segments are saved with same names.
+    #[arg(required = true)]
+    output: PathBuf,
+
+    /// Path of separate init segment for fmp4 (cenc) files.
+    /// By default init segment is taken from first segment when input is a directory.
+    #[arg(long)]
+    init: Option<PathBuf>,
+
+    /// IV for hls aes-128 and sample-aes encrypted files in hex.
+    /// By default media sequence of segment is used i.e. --media-sequence plus segment index.
+    /// Segments of a merged file are found using segment sizes saved in its sidecar file (<input>.drm.json),
+    /// without a sidecar file whole file is decrypted as a single segment.
+    #[arg(long)]
+    iv: Option<String>,
+
+    /// Key for hls aes-128 and sample-aes encrypted files.
+    /// Either path of key file or key in hex format.
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Keys for decrypting fmp4 (cenc) files.
+    /// KID:KEY should be specified in hex format.
+    #[arg(long, value_name = "KID:KEY;...", default_value = "", hide_default_value = true, value_parser = keys_parser)]
+    keys: Decrypter,
+
+    /// Media sequence of first segment, used as iv when --iv isn't specified.
+    /// By default it is read from sidecar file (<input>.drm.json) written by --no-decrypt flag,
+    /// otherwise 0 is used.
+    #[arg(long)]
+    media_sequence: Option<u64>,
+
+    /// Encryption method of input files.
+    /// By default it is detected from file contents.
+    #[arg(long, value_enum)]
+    method: Option<Method>,
+}
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+enum Method {
+    Aes128,
+    Cenc,
+    SampleAes,
+}
+
+impl Decrypt {
+    pub fn execute(self) -> Result<()> {
+        let sidecar = SidecarSegments::read(&self.input)?;
+        let media_sequence = self
+            .media_sequence
+            .or(sidecar.as_ref().map(|x| x.media_sequence))
+            .unwrap_or(0);
+
+        if self.input.is_dir() {
+            let mut segments = fs::read_dir(&self.input)?
+                .filter_map(|x| x.ok().map(|x| x.path()))
+                .filter(|x| x.is_file())
+                .collect::<Vec<_>>();
+
+            // Segments are named by their index i.e. 0.ts, 1.ts, ...
+            let index = |x: &PathBuf| {
+                x.file_stem()
+                    .and_then(|x| x.to_str())
+                    .and_then(|x| x.parse::<u64>().ok())
+            };
+            segments.sort_by_key(|x| (index(x).unwrap_or(u64::MAX), x.to_owned()));
+
+            if segments.is_empty() {
+                bail!("no segments found in {} directory.", self.input.display());
+            }
+
+            fs::create_dir_all(&self.output)?;
+            let mut init = self.init.as_ref().map(fs::read).transpose()?;
+
+            for (i, segment) in segments.iter().enumerate() {
+                let data = fs::read(segment)?;
+
+                if init.is_none() && i == 0 && is_mp4(&data) {
+                    let (init_data, _) = split_init(&data);
+
+                    if !init_data.is_empty() {
+                        init = Some(init_data.to_vec());
+                    }
+                }
+
+                let output = self.output.join(segment.file_name().unwrap());
+                self.decrypt_file(
+                    &data,
+                    init.as_deref(),
+                    media_sequence + index(segment).unwrap_or(i as u64),
+                    &[],
+                    segment,
+                    &output,
+                )?;
+            }
+        } else {
+            let data = fs::read(&self.input)?;
+            let init = self.init.as_ref().map(fs::read).transpose()?;
+
+            if let Some(parent) = self.output.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                fs::create_dir_all(parent)?;
+            }
+
+            self.decrypt_file(
+                &data,
+                init.as_deref(),
+                media_sequence,
+                sidecar
+                    .as_ref()
+                    .map(|x| x.segments.as_slice())
+                    .unwrap_or(&[]),
+                &self.input,
+                &self.output,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn decrypt_file(
+        &self,
+        data: &[u8],
+        init: Option<&[u8]>,
+        media_sequence: u64,
+        segments: &[usize],
+        input: &Path,
+        output: &Path,
+    ) -> Result<()> {
+        let method = match &self.method {
+            Some(x) => x.to_owned(),
+            None if is_mp4(data) || init.is_some() => Method::Cenc,
+            // Sample-aes only encrypts payloads, so mpeg-ts packets are still intact.
+            None if data.first() == Some(&0x47) && data.get(188) == Some(&0x47) => {
+                Method::SampleAes
+            }
+            None => Method::Aes128,
+        };
+
+        println!(
+            " {} [{:>10}] {} -> {}",
+            "Decrypting".colorize("bold green"),
+            match method {
+                Method::Aes128 => "aes-128",
+                Method::Cenc => "cenc",
+                Method::SampleAes => "sample-aes",
+            },
+            input.display(),
+            output.display()
+        );
+
+        let decrypted = match method {
+            Method::Cenc => {
+                if !matches!(self.keys, Decrypter::Mp4Decrypt(_)) {
+                    bail!("use --keys flag to specify content decryption keys for fmp4 files.");
+                }
+
+                // Init segment is prepended only if segment doesn't have it's own.
+                match init {
+                    Some(init) if split_init(data).0.is_empty() => {
+                        let decrypted = self.keys.decrypt([init, data].concat())?;
+                        let (_, media) = split_init(&decrypted);
+                        media.to_vec()
+                    }
+                    _ => self.keys.decrypt(data.to_vec())?,
+                }
+            }
+            Method::Aes128 | Method::SampleAes => {
+                let Some(key) = &self.key else {
+                    bail!("use --key flag to specify key for aes-128 and sample-aes files.");
+                };
+
+                let key_method = if method == Method::Aes128 {
+                    KeyMethod::Aes128
+                } else {
+                    KeyMethod::SampleAes
+                };
+                let key_info = Key {
+                    default_kid: None,
+                    iv: self.iv.clone(),
+                    key_format: None,
+                    method: key_method.clone(),
+                    pssh: vec![],
+                    uri: None,
+                };
+                let key = if Path::new(key).is_file() {
+                    fs::read(key)?
+                } else {
+                    hex::decode(key.strip_prefix("0x").unwrap_or(key))?
+                };
+
+                // Every segment of a merged file is encrypted separately with its own iv.
+                let segments = if segments.is_empty() {
+                    vec![data.len()]
+                } else {
+                    segments.to_vec()
+                };
+
+                if segments.iter().sum::<usize>() != data.len() {
+                    bail!(
+                        "segment sizes saved in sidecar file don't match size of {}, download stream using --no-merge flag to decrypt its segments separately.",
+                        input.display()
+                    );
+                }
+
+                let mut decrypted = Vec::with_capacity(data.len());
+                let mut start = 0;
+
+                for (i, size) in segments.into_iter().enumerate() {
+                    decrypted.extend(
+                        Decrypter::new_hls_aes(
+                            key_info.key(&key)?,
+                            key_info.iv(media_sequence + i as u64)?,
+                            &key_method,
+                        )
+                        .decrypt(data[start..start + size].to_vec())?,
+                    );
+                    start += size;
+                }
+
+                decrypted
+            }
+        };
+
+        fs::write(output, decrypted)?;
+        Ok(())
+    }
+}
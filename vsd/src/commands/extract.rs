@@ -1,49 +1,321 @@
-use anyhow::{bail, Result};
+use crate::{
+    downloader::{ImageSubtitleFormat, SubtitleFormat, SubtitleTiming, SubtitleType},
+    utils,
+};
+use anyhow::{Result, anyhow, bail};
 use clap::{Args, ValueEnum};
-use std::path::PathBuf;
-use vsd_mp4::text::{Mp4TtmlParser, Mp4VttParser};
+use kdam::term::Colorizer;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use vsd_mp4::text::{
+    ImageSubtitles, Mp4TtmlParser, Mp4VttParser, Subtitles, TsDvbParser, VttTextParser,
+    sami_text_parser, srt_text_parser, ttml_text_parser,
+};
 
-/// Extract subtitles from mp4 boxes.
+/// Extract subtitles from mp4 boxes or dvb subtitles from transport streams,
+/// and convert text subtitles from one format to another.
 #[derive(Args, Clone, Debug)]
 pub struct Extract {
-    /// Path of mp4 file which either contains WVTT or STPP box.
-    /// If there are multiple fragments of same mp4 file,
-    /// then merge them using merge sub-command.
+    /// List of input files, glob patterns e.g. *.m4s or directories of segments.
+    /// Inputs can be mp4 files which contain either WVTT or STPP box,
+    /// transport streams (.ts) which contain DVB subtitles
+    /// or text subtitles (webvtt, subrip, sami and ttml).
+    /// Every input is processed separately, except files inside a directory
+    /// which are treated as segments of a single stream.
     #[arg(required = true)]
-    input: PathBuf,
+    inputs: Vec<String>,
 
     /// Codec for output subtitles.
-    #[arg(short, long, value_enum, default_value_t = Codec::Webvtt)]
-    codec: Codec,
-}
+    /// Defaults to extension of --output file if it is known, otherwise webvtt.
+    #[arg(short, long, value_enum)]
+    codec: Option<SubtitleFormat>,
+
+    /// Format for image based subtitles (dvb subtitles and ttml images).
+    /// They are always saved to files instead of being printed.
+    #[arg(long, value_enum, default_value_t = ImageSubtitleFormat::Sup)]
+    image_format: ImageSubtitleFormat,
+
+    /// Path of initialization segment of mp4 inputs.
+    /// All inputs are then treated as media segments of a single stream,
+    /// so that they don't need to be merged first using merge sub-command.
+    #[arg(long)]
+    init: Option<PathBuf>,
+
+    /// Path for output file, or output directory when there are multiple inputs.
+    /// By default subtitles of a single input are printed to stdout
+    /// and subtitles of multiple inputs are saved next to them.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 
-#[derive(Clone, Debug, ValueEnum)]
-pub enum Codec {
-    Subrip,
-    Webvtt,
+    #[command(flatten)]
+    subs_timing: SubtitleTiming,
 }
 
 impl Extract {
     pub fn execute(self) -> Result<()> {
-        let data = std::fs::read(self.input)?;
-        let subtitles;
-
-        if let Ok(vtt) = Mp4VttParser::parse_init(&data) {
-            subtitles = vtt.parse_media(&data, None)?;
-        } else if let Ok(ttml) = Mp4TtmlParser::parse_init(&data) {
-            subtitles = ttml.parse_media(&data)?;
-        } else {
-            bail!("cannot determine subtitles codec because neither WVTT nor STPP box is found.");
+        // Every job is a path used for naming outputs and segments of a single stream.
+        let mut jobs = vec![];
+
+        for input in &self.inputs {
+            let path = Path::new(input);
+
+            if path.is_dir() {
+                let mut segments = fs::read_dir(path)?
+                    .map(|x| x.map(|x| x.path()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                segments.retain(|x| x.is_file());
+                segments
+                    .sort_by(|a, b| utils::natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+                if segments.is_empty() {
+                    bail!("no segments found inside {} directory.", input);
+                }
+
+                jobs.push((path.to_owned(), segments));
+            } else {
+                let mut files = glob::glob(input)?.collect::<Result<Vec<_>, _>>()?;
+                files
+                    .sort_by(|a, b| utils::natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+                if files.is_empty() {
+                    bail!("no files found matching {}.", input);
+                }
+
+                jobs.extend(files.into_iter().map(|x| (x.clone(), vec![x])));
+            }
+        }
+
+        if let Some(init) = &self.init {
+            // Init segment can also be matched by a glob pattern or be inside a directory.
+            let canonical_init = fs::canonicalize(init)?;
+            let mut segments = vec![init.to_owned()];
+            segments.extend(
+                jobs.into_iter()
+                    .flat_map(|x| x.1)
+                    .filter(|x| fs::canonicalize(x).ok().as_ref() != Some(&canonical_init)),
+            );
+            jobs = vec![(init.to_owned(), segments)];
+        }
+
+        let single = jobs.len() == 1;
+
+        if let Some(output) = &self.output
+            && !single
+        {
+            fs::create_dir_all(output)?;
+        }
+
+        let mut failed = 0;
+
+        for (input, segments) in &jobs {
+            if let Err(e) = self.extract(input, segments, single) {
+                if single {
+                    return Err(e);
+                }
+
+                eprintln!(
+                    "    {} couldn't extract {} ({})",
+                    "Warning".colorize("yellow"),
+                    input.to_string_lossy(),
+                    e,
+                );
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            bail!(
+                "couldn't extract subtitles from {} of {} inputs.",
+                failed,
+                jobs.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn extract(&self, input: &Path, segments: &[PathBuf], single: bool) -> Result<()> {
+        let data = segments
+            .iter()
+            .map(fs::read)
+            .collect::<Result<Vec<_>, _>>()?;
+        let timing = &self.subs_timing;
+
+        if data[0].first() == Some(&0x47) {
+            let mut parser = TsDvbParser::new();
+
+            for data in &data {
+                parser.parse(data);
+            }
+
+            let streams = parser.decode();
+            let tagged = streams.len() > 1;
+
+            if streams.is_empty() {
+                bail!("no dvb subtitles found in transport stream.");
+            }
+
+            for mut stream in streams {
+                timing.apply(&mut stream.subtitles);
+                let name = stream.language.unwrap_or_else(|| stream.pid.to_string());
+                self.write_images(&stream.subtitles, input, single, &name, tagged)?;
+            }
+
+            return Ok(());
         }
 
-        print!(
-            "{}",
-            match &self.codec {
-                Codec::Subrip => subtitles.as_srt(),
-                Codec::Webvtt => subtitles.as_vtt(),
+        let mut subtitles = match SubtitleType::detect(&data[0]) {
+            Some(SubtitleType::Mp4Vtt) => {
+                let data = data.concat();
+                Mp4VttParser::parse_init(&data)?.parse_media(&data, None)?
+            }
+            Some(SubtitleType::Mp4Ttml) => {
+                let data = data.concat();
+                let ttml = Mp4TtmlParser::parse_init(&data)?;
+                let subtitles = ttml.parse_media(&data)?;
+
+                if subtitles.is_empty() {
+                    let mut images = ttml.parse_media_images(&data)?;
+
+                    if !images.is_empty() {
+                        timing.apply(&mut images);
+                        return self.write_images(&images, input, single, "stpp", false);
+                    }
+                }
+
+                subtitles
+            }
+            Some(SubtitleType::VttText) => {
+                let mut parser = VttTextParser::new();
+
+                for data in &data {
+                    parser.parse_segment(&utils::decode_text(data), None)?;
+                }
+
+                parser.subtitles()
+            }
+            Some(codec) => {
+                let mut subtitles: Option<Subtitles> = None;
+                let mut images: Option<ImageSubtitles> = None;
+
+                for data in &data {
+                    let text = utils::decode_text(data);
+                    let segment_subtitles = match codec {
+                        SubtitleType::SamiText => sami_text_parser::parse(&text)?,
+                        SubtitleType::SrtText => srt_text_parser::parse(&text)?,
+                        _ => {
+                            let ttml = ttml_text_parser::parse(&text).map_err(|x| {
+                                anyhow!("couldn't parse xml string as ttml content.\n\n{:#?}", x)
+                            })?;
+                            let segment_images = ttml.image_subtitles(&[])?;
+
+                            match &mut images {
+                                Some(images) => images.extend(segment_images),
+                                None => images = Some(segment_images),
+                            }
+
+                            ttml.into_subtitles()
+                        }
+                    };
+
+                    match &mut subtitles {
+                        Some(subtitles) => subtitles.merge(segment_subtitles),
+                        None => subtitles = Some(segment_subtitles),
+                    }
+                }
+
+                let subtitles = subtitles.unwrap();
+
+                if let Some(mut images) = images
+                    && subtitles.is_empty()
+                    && !images.is_empty()
+                {
+                    timing.apply(&mut images);
+                    return self.write_images(&images, input, single, "ttml", false);
+                }
+
+                subtitles
+            }
+            None => bail!(
+                "cannot determine subtitles codec because neither WVTT nor STPP box is found \
+                 and contents aren't webvtt, subrip, sami or ttml text."
+            ),
+        };
+
+        // Cues of consecutive segments can overlap or repeat at segment boundaries.
+        subtitles.merge_overlapping();
+        timing.apply(&mut subtitles);
+
+        let codec = self
+            .codec
+            .clone()
+            .or_else(|| {
+                self.output
+                    .as_ref()
+                    .filter(|_| single)
+                    .and_then(|x| x.extension())
+                    .and_then(|x| SubtitleFormat::from_str(&x.to_string_lossy(), true).ok())
+            })
+            .unwrap_or(SubtitleFormat::Webvtt);
+        let subtitles = codec.build(&subtitles);
+
+        let path = match &self.output {
+            Some(output) if single && !output.is_dir() => output.to_owned(),
+            Some(output) => output
+                .join(input.file_name().unwrap_or_default())
+                .with_extension(codec.extension()),
+            None if single => {
+                print!("{subtitles}");
+                return Ok(());
             }
+            None => input.with_extension(codec.extension()),
+        };
+
+        if segments.contains(&path) {
+            bail!("output path is same as input, use --output to save them elsewhere.");
+        }
+
+        fs::write(&path, subtitles)?;
+        eprintln!(
+            "  {} {}",
+            "Extracted".colorize("bold green"),
+            path.to_string_lossy(),
         );
+        Ok(())
+    }
+
+    /// Write image subtitles as `<input stem>.<name>.<ext>` next to input file,
+    /// or to output path if it is used.
+    fn write_images(
+        &self,
+        subtitles: &ImageSubtitles,
+        input: &Path,
+        single: bool,
+        name: &str,
+        tagged: bool,
+    ) -> Result<()> {
+        let extension = format!("{}.{}", name, self.image_format.extension());
+        let path = match &self.output {
+            // Multiple streams of a single input are told apart by their name.
+            Some(output) if single && !output.is_dir() && tagged => {
+                output.with_extension(&extension)
+            }
+            Some(output) if single && !output.is_dir() => output.to_owned(),
+            Some(output) => output
+                .join(input.file_name().unwrap_or_default())
+                .with_extension(&extension),
+            None => input.with_extension(&extension),
+        };
 
+        self.image_format.write(subtitles, &path)?;
+        eprintln!(
+            "  {} {} ({} image subs)",
+            "Extracted".colorize("bold green"),
+            path.to_string_lossy(),
+            subtitles.cues().len(),
+        );
         Ok(())
     }
 }
@@ -0,0 +1,462 @@
+use anyhow::{Result, bail};
+use base64::Engine;
+use clap::Args;
+use kdam::term::Colorizer;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use vsd_mp4::{
+    CallbackType, HandlerResult, Mp4Parser, ParsedBox,
+    pssh::{Pssh, PsshData},
+    sidx,
+};
+
+/// Inspect mp4 boxes, tracks and encryption info.
+#[derive(Args, Clone, Debug)]
+pub struct Inspect {
+    /// Path or http(s):// url of mp4 file (init or media segment).
+    #[arg(required = true)]
+    input: String,
+
+    /// Print output in json format.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Default, Serialize)]
+struct Inspection {
+    boxes: Vec<Mp4Box>,
+    /// Boxes whose children are being parsed.
+    #[serde(skip)]
+    parents: Vec<Mp4Box>,
+    pssh: Vec<PsshBox>,
+    sidx: Vec<SidxReference>,
+    tracks: Vec<Track>,
+}
+
+#[derive(Serialize)]
+struct Mp4Box {
+    children: Vec<Mp4Box>,
+    name: String,
+    offset: u64,
+    size: u64,
+}
+
+#[derive(Default, Serialize)]
+struct PsshBox {
+    /// Playready encryption algorithm.
+    algid: Option<String>,
+    /// Widevine content id in hex.
+    content_id: Option<String>,
+    /// Whole `pssh` box in base64.
+    data: String,
+    key_ids: Vec<String>,
+    /// Playready license acquisition url.
+    la_url: Option<String>,
+    offset: u64,
+    /// Widevine protection scheme.
+    protection_scheme: Option<String>,
+    /// Widevine content provider.
+    provider: Option<String>,
+    system_id: String,
+    system_type: String,
+    version: u8,
+}
+
+#[derive(Serialize)]
+struct SidxReference {
+    end: u64,
+    size: u64,
+    start: u64,
+}
+
+#[derive(Default, Serialize)]
+struct Track {
+    codec: Option<String>,
+    default_kid: Option<String>,
+    /// In seconds.
+    duration: Option<f64>,
+    encrypted: bool,
+    handler: Option<String>,
+    id: Option<u32>,
+    language: Option<String>,
+    /// Protection scheme e.g. `cenc`, `cbcs`.
+    scheme: Option<String>,
+    timescale: Option<u32>,
+}
+
+impl Inspect {
+    pub fn execute(self) -> Result<()> {
+        let data = if self.input.starts_with("http://") || self.input.starts_with("https://") {
+            Client::new()
+                .get(&self.input)
+                .send()?
+                .error_for_status()?
+                .bytes()?
+                .to_vec()
+        } else if Path::new(&self.input).exists() {
+            std::fs::read(&self.input)?
+        } else {
+            bail!("{} is neither a file nor a http(s):// url.", self.input);
+        };
+
+        let inspection = Arc::new(Mutex::new(Inspection::default()));
+        let result = parse_boxes(&data, &inspection);
+        let inspection = std::mem::take(&mut *inspection.lock().unwrap());
+
+        if inspection.boxes.is_empty() {
+            match result {
+                Err(e) => bail!("no mp4 boxes found in {} ({}).", self.input, e),
+                Ok(_) => bail!("no mp4 boxes found in {}.", self.input),
+            }
+        }
+
+        if self.json {
+            serde_json::to_writer(std::io::stdout(), &inspection)?;
+            return Ok(());
+        }
+
+        println!("{}", "----------- Boxes ------------".colorize("cyan"));
+
+        for mp4_box in &inspection.boxes {
+            print_box(mp4_box, 0);
+        }
+
+        // Boxes are parsed until first invalid box.
+        if let Err(e) = result {
+            println!(
+                "    {} couldn't parse remaining boxes ({})",
+                "Warning".colorize("yellow"),
+                e
+            );
+        }
+
+        if !inspection.tracks.is_empty() {
+            println!("{}", "----------- Tracks -----------".colorize("cyan"));
+        }
+
+        for track in &inspection.tracks {
+            let mut extra = format!(
+                "handler: {}, codec: {}",
+                track.handler.as_deref().unwrap_or("?"),
+                track.codec.as_deref().unwrap_or("?")
+            );
+
+            if let Some(timescale) = track.timescale {
+                extra += &format!(", timescale: {timescale}");
+            }
+
+            if let Some(duration) = track.duration {
+                extra += &format!(", duration: {duration:.3}s");
+            }
+
+            if let Some(language) = &track.language {
+                extra += &format!(", language: {language}");
+            }
+
+            if track.encrypted {
+                extra += &format!(", encrypted: {}", track.scheme.as_deref().unwrap_or("?"));
+            }
+
+            if let Some(default_kid) = &track.default_kid {
+                extra += &format!(", default kid: {default_kid}");
+            }
+
+            println!(
+                "{:>2}) {}",
+                track.id.map(|x| x.to_string()).unwrap_or("?".to_owned()),
+                extra
+            );
+        }
+
+        if !inspection.pssh.is_empty() {
+            println!("{}", "------------ PSSH ------------".colorize("cyan"));
+        }
+
+        for pssh in &inspection.pssh {
+            println!(
+                "{} v{} ({}) at {}",
+                pssh.system_type.colorize("bold green"),
+                pssh.version,
+                pssh.system_id,
+                pssh.offset
+            );
+
+            for key_id in &pssh.key_ids {
+                println!("    kid: {key_id}");
+            }
+
+            for (name, value) in [
+                ("algid", &pssh.algid),
+                ("content id", &pssh.content_id),
+                ("la url", &pssh.la_url),
+                ("protection scheme", &pssh.protection_scheme),
+                ("provider", &pssh.provider),
+            ] {
+                if let Some(value) = value {
+                    println!("    {name}: {value}");
+                }
+            }
+
+            println!("    data: {}", pssh.data);
+        }
+
+        if !inspection.sidx.is_empty() {
+            println!("{}", "------------ SIDX ------------".colorize("cyan"));
+        }
+
+        for (i, reference) in inspection.sidx.iter().enumerate() {
+            println!(
+                "{:>2}) bytes={}-{} ({} bytes)",
+                i + 1,
+                reference.start,
+                reference.end,
+                reference.size
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn print_box(mp4_box: &Mp4Box, depth: usize) {
+    println!(
+        "{}{} (offset: {}, size: {})",
+        "  ".repeat(depth),
+        mp4_box.name.colorize("bold green"),
+        mp4_box.offset,
+        mp4_box.size
+    );
+
+    for child in &mp4_box.children {
+        print_box(child, depth + 1);
+    }
+}
+
+/// Parse box tree along with tracks, pssh and sidx boxes found in it.
+fn parse_boxes(data: &[u8], inspection: &Arc<Mutex<Inspection>>) -> HandlerResult {
+    let data = Arc::new(data.to_vec());
+    let mut parser = Mp4Parser::default();
+
+    for name in [
+        "dinf", "edts", "mdia", "mfra", "minf", "moof", "moov", "mvex", "schi", "sinf", "stbl",
+        "traf", "trak", "udta",
+    ] {
+        parser = parser.basic_box(name, record(&data, inspection, Some(vsd_mp4::children)));
+    }
+
+    for name in ["hdlr", "mdhd", "pssh", "schm", "sidx", "tenc", "tkhd"] {
+        parser = parser.full_box(name, record(&data, inspection, None));
+    }
+
+    parser
+        .full_box("meta", record(&data, inspection, Some(vsd_mp4::children)))
+        .full_box(
+            "stsd",
+            record(&data, inspection, Some(vsd_mp4::sample_description)),
+        )
+        .unknown_box(record(&data, inspection, None))
+        .parse(&data, true, false)
+}
+
+/// Callback which adds parsed box to box tree. Children of box are parsed using `body`,
+/// sample entries are parsed according to handler of their track and boxes without
+/// children are parsed as leaf boxes.
+fn record(
+    data: &Arc<Vec<u8>>,
+    inspection: &Arc<Mutex<Inspection>>,
+    body: Option<fn(ParsedBox) -> HandlerResult>,
+) -> CallbackType {
+    let data = data.clone();
+    let inspection = inspection.clone();
+
+    Arc::new(move |mut _box| {
+        let mut body = body;
+        let mut mp4_box = Mp4Box {
+            children: vec![],
+            name: _box.name.clone(),
+            offset: _box.start,
+            size: _box.size as u64,
+        };
+        let mut state = inspection.lock().unwrap();
+
+        if _box.name == "trak" {
+            state.tracks.push(Track::default());
+        }
+
+        if state.parents.last().is_some_and(|x| x.name == "stsd")
+            && let Some(track) = state.tracks.last_mut()
+        {
+            track.codec = Some(_box.name.clone());
+            track.encrypted = _box.name == "encv" || _box.name == "enca";
+            body = match track.handler.as_deref() {
+                Some("vide") => Some(vsd_mp4::visual_sample_entry),
+                Some("soun") => Some(vsd_mp4::audio_sample_entry),
+                _ => None,
+            };
+        }
+
+        let result = if let Some(body) = body {
+            // Lock is released while children are parsed, as their callbacks also use it.
+            state.parents.push(mp4_box);
+            drop(state);
+            let result = body(_box);
+            state = inspection.lock().unwrap();
+            mp4_box = state.parents.pop().unwrap();
+            result
+        } else {
+            // Truncated boxes (partial segments) are parsed as much as possible.
+            let start = _box.start as usize;
+            let end = start.saturating_add(_box.size).min(data.len());
+            parse_leaf_box(&mut _box, &data[start..end], &mut state);
+            Ok(())
+        };
+
+        match state.parents.last_mut() {
+            Some(parent) => parent.children.push(mp4_box),
+            None => state.boxes.push(mp4_box),
+        }
+
+        result
+    })
+}
+
+/// `data` is the whole box including its header.
+fn parse_leaf_box(_box: &mut ParsedBox, data: &[u8], inspection: &mut Inspection) {
+    let reader = &mut _box.reader;
+    let fourcc = |x: Vec<u8>| String::from_utf8_lossy(&x).to_string();
+
+    match _box.name.as_str() {
+        "frma" => {
+            if let (Some(track), Ok(x)) = (inspection.tracks.last_mut(), reader.read_bytes_u8(4)) {
+                track.codec = Some(fourcc(x));
+            }
+        }
+        "hdlr" => {
+            if let (Some(track), Ok(x)) = (
+                inspection.tracks.last_mut(),
+                reader.skip(4).and_then(|_| reader.read_bytes_u8(4)),
+            ) {
+                track.handler = Some(fourcc(x));
+            }
+        }
+        "mdhd" => {
+            let Some(track) = inspection.tracks.last_mut() else {
+                return;
+            };
+
+            // Creation and modification time are u64 in version 1.
+            let values = if _box.version == Some(1) {
+                reader
+                    .skip(16)
+                    .and_then(|_| Ok((reader.read_u32()?, reader.read_u64()?)))
+            } else {
+                reader
+                    .skip(8)
+                    .and_then(|_| Ok((reader.read_u32()?, reader.read_u32()? as u64)))
+            };
+
+            if let Ok((timescale, duration)) = values {
+                track.timescale = Some(timescale);
+
+                if timescale != 0 {
+                    track.duration = Some(duration as f64 / timescale as f64);
+                }
+            }
+
+            // ISO-639-2/T language code packed as 5 bit characters.
+            if let Ok(language) = reader.read_u16() {
+                track.language = Some(
+                    [
+                        (language >> 10) & 0x1f,
+                        (language >> 5) & 0x1f,
+                        language & 0x1f,
+                    ]
+                    .iter()
+                    .map(|x| (*x as u8 + 0x60) as char)
+                    .collect(),
+                );
+            }
+        }
+        "pssh" => {
+            let mut pssh_box = PsshBox {
+                data: base64::engine::general_purpose::STANDARD.encode(data),
+                offset: _box.start,
+                system_id: reader
+                    .read_bytes_u8(16)
+                    .map(hex::encode)
+                    .unwrap_or_default(),
+                ..Default::default()
+            };
+            pssh_box.system_type = pssh_box.system_id.to_owned();
+
+            if let Some(parsed) = Pssh::new(data).ok().and_then(|x| x.boxes.first().cloned()) {
+                pssh_box.key_ids = parsed
+                    .all_key_ids()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|x| x.value)
+                    .collect();
+                pssh_box.system_type = parsed.system_type().to_string();
+                pssh_box.version = parsed.version;
+
+                match parsed.decode() {
+                    Ok(PsshData::PlayReady(x)) => {
+                        pssh_box.algid = x.algid;
+                        pssh_box.la_url = x.la_url;
+                    }
+                    Ok(PsshData::Widevine(x)) => {
+                        pssh_box.content_id = x.content_id.map(hex::encode);
+                        pssh_box.protection_scheme = x.protection_scheme;
+                        pssh_box.provider = x.provider;
+                    }
+                    _ => (),
+                }
+            }
+
+            inspection.pssh.push(pssh_box);
+        }
+        "schm" => {
+            if let (Some(track), Ok(x)) = (inspection.tracks.last_mut(), reader.read_bytes_u8(4)) {
+                track.scheme = Some(fourcc(x));
+            }
+        }
+        "sidx" => {
+            if let Ok(references) = sidx::parse(data, _box.start) {
+                inspection
+                    .sidx
+                    .extend(references.into_iter().map(|x| SidxReference {
+                        end: x.end,
+                        size: x.end - x.start + 1,
+                        start: x.start,
+                    }));
+            }
+        }
+        "tenc" => {
+            // Reserved (1 byte), default crypt and skip byte block (1 byte),
+            // default is protected (1 byte) and default per sample iv size (1 byte).
+            if let (Some(track), Ok(x)) = (
+                inspection.tracks.last_mut(),
+                reader.skip(4).and_then(|_| reader.read_bytes_u8(16)),
+            ) {
+                track.default_kid = Some(hex::encode(x));
+            }
+        }
+        "tkhd" => {
+            let Some(track) = inspection.tracks.last_mut() else {
+                return;
+            };
+
+            // Creation and modification time are u64 in version 1.
+            let skip = if _box.version == Some(1) { 16 } else { 8 };
+
+            if let Ok(id) = reader.skip(skip).and_then(|_| reader.read_u32()) {
+                track.id = Some(id);
+            }
+        }
+        _ => (),
+    }
+}
@@ -1,9 +1,20 @@
-use anyhow::{bail, Result};
+use super::save::keys_parser;
+use crate::{
+    downloader::Decrypter,
+    playlist::{KeyMethod, MediaPlaylist, MediaType, Range},
+    utils,
+};
+use anyhow::{Result, anyhow, bail};
 use clap::{Args, ValueEnum};
+use kdam::term::Colorizer;
+use reqwest::Url;
 use std::{
-    fs,
+    collections::HashMap,
+    env, fs,
     fs::File,
     io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process,
     process::Command,
 };
 
@@ -11,14 +22,39 @@ use std::{
 #[derive(Args, Clone, Debug)]
 pub struct Merge {
     /// List of files (at least 2) to merge together e.g. *.ts, *.m4s etc. .
-    #[arg(required = true)]
+    /// Files matched by a glob pattern are sorted in natural order i.e. 2.m4s before 10.m4s.
+    #[arg(required_unless_present = "playlist", conflicts_with = "playlist")]
     files: Vec<String>,
 
+    /// Path of separate init segment for fmp4 files, which is merged before all files.
+    /// Init segment repeated at the start of files is merged only once.
+    #[arg(long, conflicts_with = "playlist")]
+    init: Option<PathBuf>,
+
+    /// Keys for decrypting fmp4 (cenc) segments of --playlist.
+    /// KID:KEY should be specified in hex format.
+    #[arg(long, value_name = "KID:KEY;...", default_value = "", hide_default_value = true, value_parser = keys_parser)]
+    keys: Decrypter,
+
     /// Path for merged output file.
     #[arg(short, long, required = true)]
     output: String,
 
+    /// Path of a saved HLS (.m3u8) or DASH (.mpd) playlist, whose segments are merged in playlist order
+    /// along with their init segments and decryption keys (hls aes-128 keys are read from --segments).
+    /// Every stream having all of its segments inside --segments directory is merged,
+    /// if there are multiple such streams then their type is added to --output file name.
+    #[arg(long)]
+    playlist: Option<PathBuf>,
+
+    /// Directory of segments of --playlist e.g. browser cache or directory created by --no-merge flag.
+    /// Segments are matched by their path relative to playlist, their url path, their file name or their index.
+    /// By default directory of playlist is used.
+    #[arg(long, requires = "playlist")]
+    segments: Option<PathBuf>,
+
     /// Type of merge to be performed.
+    /// Segments of --playlist are always merged byte-wise.
     #[arg(short, long, value_enum, default_value_t = MergeType::Binary)]
     _type: MergeType,
 }
@@ -31,81 +67,436 @@ enum MergeType {
 
 impl Merge {
     pub fn execute(self) -> Result<()> {
+        if let Some(playlist) = &self.playlist {
+            return self.merge_playlist(playlist);
+        }
+
         let mut files = vec![];
 
         for pattern in &self.files {
-            for file in glob::glob(pattern)? {
-                files.push(file?);
-            }
+            let mut matched = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+            matched.sort_by(|a, b| utils::natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+            files.extend(matched);
+        }
+
+        // Output file from a previous run can be matched by the same glob pattern.
+        if let Ok(output) = fs::canonicalize(&self.output) {
+            files.retain(|x| fs::canonicalize(x).ok().as_ref() != Some(&output));
         }
 
-        if 1 >= files.len() {
+        if 2 > files.len() + self.init.is_some() as usize {
             bail!("At least 2 files are required to merge together.")
         }
 
         match self._type {
-            MergeType::Binary => {
-                let mut output = File::create(self.output)?;
-                let buffer_size = 1024 * 1024 * 2; // 2 MiB
+            MergeType::Binary => self.merge_binary(&files, Path::new(&self.output))?,
+            MergeType::Ffmpeg => {
+                // Concat demuxer of ffmpeg can't use a separate init segment,
+                // so files are merged first and then remuxed by ffmpeg.
+                let temp_file = env::temp_dir()
+                    .join(format!("vsd-merge-{}", process::id()))
+                    .with_extension(if self.init.is_some() {
+                        Path::new(&self.output)
+                            .extension()
+                            .unwrap_or("mp4".as_ref())
+                    } else {
+                        "txt".as_ref()
+                    });
+                let result = self.merge_ffmpeg(&files, &temp_file);
 
-                for file in files {
-                    let file_size = fs::metadata(&file)?.len();
+                // Temporary file is removed even if ffmpeg couldn't be spawned or has failed.
+                if temp_file.exists() {
+                    fs::remove_file(&temp_file)?;
+                }
 
-                    if buffer_size >= file_size {
-                        let buf = fs::read(file)?;
-                        output.write_all(&buf)?;
-                    } else {
-                        let file = File::open(file)?;
-                        let mut reader = BufReader::new(file);
+                result?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merge files using ffmpeg, `temp_file` is either the byte-wise merged file
+    /// (when init segment is used) or concat list of files.
+    fn merge_ffmpeg(&self, files: &[PathBuf], temp_file: &Path) -> Result<()> {
+        let input = if self.init.is_some() {
+            self.merge_binary(files, temp_file)?;
+            vec!["-i".to_owned(), temp_file.to_string_lossy().to_string()]
+        } else {
+            let mut concat = File::create(temp_file)?;
+
+            // Paths inside concat list are resolved relative to it.
+            for file in files {
+                let file = fs::canonicalize(file)?;
+                let file = file.to_string_lossy().replace('\'', "'\\''");
+                concat.write_fmt(format_args!("file '{file}'\n"))?;
+            }
+
+            vec![
+                "-f".to_owned(),
+                "concat".to_owned(),
+                "-safe".to_owned(),
+                "0".to_owned(),
+                "-i".to_owned(),
+                temp_file.to_string_lossy().to_string(),
+            ]
+        };
 
-                        loop {
-                            let mut buf = vec![];
-                            reader.by_ref().take(buffer_size).read_to_end(&mut buf)?;
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-y"])
+            .args(&input)
+            .args(["-c", "copy", &self.output])
+            .spawn()?
+            .wait()?;
+
+        if !status.success() {
+            bail!("ffmpeg exited with code {}.", status.code().unwrap_or(1))
+        }
+
+        Ok(())
+    }
 
-                            if buf.is_empty() {
-                                break;
-                            }
+    /// Merge files byte-wise, init segment (`ftyp` and `moov` boxes) at the start of a file
+    /// is skipped if it is same as previously merged init segment.
+    fn merge_binary(&self, files: &[PathBuf], output: &Path) -> Result<()> {
+        let mut output = File::create(output)?;
+        let buffer_size = 1024 * 1024 * 2; // 2 MiB
+        let mut init = None;
 
-                            output.write_all(&buf)?;
+        if let Some(path) = &self.init {
+            write_segment(&mut output, &mut init, &fs::read(path)?)?;
+        }
+
+        for file in files {
+            let mut reader = BufReader::new(File::open(file)?);
+            let mut buf = vec![];
+            reader.by_ref().take(buffer_size).read_to_end(&mut buf)?;
+            write_segment(&mut output, &mut init, &buf)?;
+
+            loop {
+                buf.clear();
+                reader.by_ref().take(buffer_size).read_to_end(&mut buf)?;
+
+                if buf.is_empty() {
+                    break;
+                }
+
+                output.write_all(&buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge_playlist(&self, playlist: &Path) -> Result<()> {
+        let directory = match &self.segments {
+            Some(x) => x.to_owned(),
+            None => playlist
+                .parent()
+                .filter(|x| !x.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_owned(),
+        };
+        let locator = Locator::new(&directory)?;
+        let url = Url::from_file_path(fs::canonicalize(playlist)?)
+            .map_err(|_| anyhow!("couldn't convert {} to file url.", playlist.display()))?;
+        let text = fs::read_to_string(playlist)?;
+        let mut streams = vec![];
+
+        if text.contains("<MPD") {
+            let mpd = dash_mpd::parse(&text)
+                .map_err(|_| anyhow!("couldn't parse {} as dash playlist.", playlist.display()))?;
+
+            for mut stream in crate::dash::parse_as_master(&mpd, url.as_str()).streams {
+                // Sidx box of SegmentBase@indexRange addressing is read from local file.
+                let fetch_range = |x: &Url, range: &Range| match locator.locate(&url, x, None) {
+                    Some(path) => read_range(&path, Some(range)),
+                    None => bail!("{} isn't found inside segments directory.", x),
+                };
+                crate::dash::push_segments(&mpd, &mut stream, url.as_str(), &fetch_range)?;
+                stream.uri = url.to_string();
+                streams.push(stream);
+            }
+        } else {
+            match m3u8_rs::parse_playlist_res(text.as_bytes()) {
+                Ok(m3u8_rs::Playlist::MasterPlaylist(m3u8)) => {
+                    for mut stream in crate::hls::parse_as_master(&m3u8, url.as_str()).streams {
+                        // Closed captions are carried inside video stream, so there is no playlist.
+                        if stream.instream_id.is_some() {
+                            continue;
+                        }
+
+                        let uri = url.join(&stream.uri)?;
+
+                        if let Some(path) = locator.locate(&url, &uri, None) {
+                            let text = fs::read(path)?;
+                            let m3u8 = m3u8_rs::parse_media_playlist_res(&text)
+                                .map_err(|_| anyhow!("couldn't parse {} as hls playlist.", uri))?;
+                            crate::hls::push_segments(&m3u8, &mut stream);
                         }
+
+                        stream.uri = uri.to_string();
+                        streams.push(stream);
                     }
                 }
+                Ok(m3u8_rs::Playlist::MediaPlaylist(m3u8)) => {
+                    let mut stream = MediaPlaylist {
+                        uri: url.to_string(),
+                        ..Default::default()
+                    };
+                    crate::hls::push_segments(&m3u8, &mut stream);
+                    streams.push(stream);
+                }
+                Err(_) => bail!("couldn't parse {} as hls playlist.", playlist.display()),
             }
-            MergeType::Ffmpeg => {
-                let concat_file = "vsd-ffmpeg-concat.txt";
-                let mut concat = File::create(concat_file)?;
+        }
 
-                for file in files {
-                    let file = file.to_string_lossy();
+        // Streams are matched by their segments, so that the one saved locally is merged.
+        let mut selected = vec![];
+        let mut most_found = (0, 0);
 
-                    if file != self.output {
-                        concat.write_fmt(format_args!("file '{file}'\n"))?;
-                    }
+        for stream in streams {
+            if stream.segments.is_empty() || stream.media_type == MediaType::Subtitles {
+                continue;
+            }
+
+            let base_url = stream.uri.parse::<Url>()?;
+            let mut found = 0;
+
+            for (i, segment) in stream.segments.iter().enumerate() {
+                if locator
+                    .locate(&base_url, &base_url.join(&segment.uri)?, Some(i))
+                    .is_some()
+                {
+                    found += 1;
+                }
+            }
+
+            if found == stream.segments.len() {
+                selected.push(stream);
+            } else if found >= most_found.0 {
+                most_found = (found, stream.segments.len());
+            }
+        }
+
+        if most_found.1 == 0 && selected.is_empty() {
+            bail!(
+                "no media playlist or segments are found inside {} directory.",
+                directory.display(),
+            );
+        } else if selected.is_empty() {
+            bail!(
+                "no stream has all of its segments inside {} directory (at most {} of {} segments are found).",
+                directory.display(),
+                most_found.0,
+                most_found.1,
+            );
+        }
+
+        let output = Path::new(&self.output);
+        let extension = output.extension().unwrap_or_default().to_string_lossy();
+
+        for (i, stream) in selected.iter().enumerate() {
+            let path = if selected.len() == 1 {
+                output.to_owned()
+            } else {
+                let same_type = selected
+                    .iter()
+                    .filter(|x| x.media_type == stream.media_type)
+                    .count();
+
+                if same_type == 1 {
+                    output.with_extension(format!("{}.{}", stream.media_type, extension))
+                } else {
+                    output.with_extension(format!("{}.{}.{}", stream.media_type, i, extension))
                 }
+            };
+
+            let display = stream.display_stream();
+            println!(
+                "    {} [{:>5}] {}{}",
+                "Merging".colorize("bold green"),
+                stream.media_type.to_string(),
+                path.display(),
+                if display.is_empty() {
+                    display
+                } else {
+                    format!(" ({display})")
+                },
+            );
+            self.merge_stream(&locator, stream, &path)?;
+        }
+
+        Ok(())
+    }
 
-                let status = Command::new("ffmpeg")
-                    .args([
-                        "-hide_banner",
-                        "-y",
-                        "-f",
-                        "concat",
-                        "-i",
-                        concat_file,
-                        "-c",
-                        "copy",
-                        &self.output,
-                    ])
-                    .spawn()?
-                    .wait()?;
-
-                if !status.success() {
-                    bail!("ffmpeg exited with code {}.", status.code().unwrap_or(1))
+    /// Merge segments of a stream in playlist order, init segments are merged only when they
+    /// change and segments are decrypted if they are encrypted.
+    fn merge_stream(&self, locator: &Locator, stream: &MediaPlaylist, output: &Path) -> Result<()> {
+        let base_url = stream.uri.parse::<Url>()?;
+        let mut output = File::create(output)?;
+        let mut aes_key = None;
+        let mut decrypter = Decrypter::None;
+        let mut init = None;
+        let mut init_changed = false;
+        let mut written_init = None;
+
+        for (i, segment) in stream.segments.iter().enumerate() {
+            if let Some(map) = &segment.map {
+                let url = base_url.join(&map.uri)?;
+
+                // Segments saved by --no-merge flag already have init segment at their start.
+                init = match locator.locate(&base_url, &url, None) {
+                    Some(path) => Some(read_range(&path, map.range.as_ref())?),
+                    None => None,
+                };
+                init_changed = true;
+            }
+
+            if let Some(key) = &segment.key {
+                aes_key = None;
+                decrypter = Decrypter::None;
+
+                match &key.method {
+                    KeyMethod::Aes128 | KeyMethod::SampleAes => {
+                        let url = base_url.join(key.uri.as_deref().unwrap_or_default())?;
+                        let Some(path) = locator.locate(&base_url, &url, None) else {
+                            bail!("key {} isn't found inside segments directory.", url);
+                        };
+                        aes_key = Some((key, key.key(&fs::read(path)?)?));
+                    }
+                    KeyMethod::Mp4Decrypt => {
+                        if !matches!(self.keys, Decrypter::Mp4Decrypt(_)) {
+                            bail!(
+                                "use --keys flag to specify content decryption keys for fmp4 segments."
+                            );
+                        }
+
+                        decrypter = self.keys.clone();
+                    }
+                    KeyMethod::None => (),
+                    KeyMethod::Other(x) => bail!("{} decryption is not supported.", x),
                 }
+            }
+
+            let url = base_url.join(&segment.uri)?;
+            let path = locator.locate(&base_url, &url, Some(i)).unwrap();
+            let mut data = read_range(&path, segment.range.as_ref())?;
 
-                fs::remove_file(concat_file)?;
+            // Iv of hls aes-128 and sample-aes is media sequence of segment if it isn't specified.
+            if let Some((key, key_bytes)) = &aes_key {
+                data = Decrypter::new_hls_aes(
+                    *key_bytes,
+                    key.iv(stream.media_sequence + i as u64)?,
+                    &key.method,
+                )
+                .decrypt(data)?;
             }
+
+            // Fmp4 init segment is needed for decrypting every segment, written copies are skipped.
+            match &init {
+                Some(init) if utils::is_mp4(init) && utils::split_init(&data).0.is_empty() => {
+                    // Boxes after moov box in init range (e.g. sidx) are merged only once.
+                    let init = if init_changed {
+                        init.as_slice()
+                    } else {
+                        utils::split_init(init).0
+                    };
+                    data = [init, &data].concat();
+                }
+                Some(init) if !utils::is_mp4(init) && init_changed => output.write_all(init)?,
+                _ => (),
+            }
+
+            init_changed = false;
+            data = decrypter.decrypt(data)?;
+            write_segment(&mut output, &mut written_init, &data)?;
         }
 
         Ok(())
     }
 }
+
+/// Write data of a segment, init segment (`ftyp` and `moov` boxes) at its start is skipped
+/// if it is same as previously written init segment.
+fn write_segment(output: &mut File, init: &mut Option<Vec<u8>>, data: &[u8]) -> Result<()> {
+    let mut data = data;
+
+    if utils::is_mp4(data) {
+        let (init_data, media) = utils::split_init(data);
+
+        if !init_data.is_empty() {
+            if init.as_deref() == Some(init_data) {
+                data = media;
+            } else {
+                *init = Some(init_data.to_vec());
+            }
+        }
+    }
+
+    output.write_all(data)?;
+    Ok(())
+}
+
+/// Read byte range of a file, whole file is read if it is smaller than range
+/// i.e. only that range was saved.
+fn read_range(path: &Path, range: Option<&Range>) -> Result<Vec<u8>> {
+    let data = fs::read(path)?;
+
+    Ok(match range {
+        Some(range) if (range.end as usize) < data.len() => {
+            data[(range.start as usize)..=(range.end as usize)].to_vec()
+        }
+        _ => data,
+    })
+}
+
+/// Find local files of segments, init segments and keys of a playlist.
+struct Locator {
+    directory: Url,
+    /// Files named by segment index i.e. 0.ts, 1.ts, ...
+    indexed: HashMap<String, PathBuf>,
+}
+
+impl Locator {
+    fn new(directory: &Path) -> Result<Self> {
+        let mut indexed = HashMap::new();
+
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+
+            if let Some(stem) = path.file_stem().and_then(|x| x.to_str())
+                && stem.parse::<u64>().is_ok()
+                && path.is_file()
+            {
+                indexed.insert(stem.to_owned(), path);
+            }
+        }
+
+        Ok(Self {
+            directory: Url::from_directory_path(fs::canonicalize(directory)?)
+                .map_err(|_| anyhow!("couldn't convert {} to file url.", directory.display()))?,
+            indexed,
+        })
+    }
+
+    /// Local file of an url, which is searched by its path relative to playlist url,
+    /// its full path, its file name and then by its index.
+    fn locate(&self, playlist_url: &Url, url: &Url, index: Option<usize>) -> Option<PathBuf> {
+        let relative = playlist_url
+            .make_relative(url)
+            .filter(|x| !x.starts_with("../"));
+        let path = url.path().trim_start_matches('/');
+        let file_name = url
+            .path_segments()
+            .and_then(|mut x| x.next_back())
+            .filter(|x| !x.is_empty());
+
+        [relative.as_deref(), Some(path), file_name]
+            .into_iter()
+            .flatten()
+            .filter_map(|x| self.directory.join(x).ok()?.to_file_path().ok())
+            .find(|x| x.is_file())
+            .or_else(|| self.indexed.get(&index?.to_string()).cloned())
+    }
+}
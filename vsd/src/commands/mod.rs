@@ -1,11 +1,15 @@
+mod decrypt;
 mod extract;
+mod inspect;
 mod merge;
 mod save;
 
 #[cfg(feature = "browser")]
 mod capture;
 
+pub use decrypt::Decrypt;
 pub use extract::Extract;
+pub use inspect::Inspect;
 pub use merge::Merge;
 pub use save::Save;
 
@@ -41,7 +45,9 @@ pub struct Args {
 pub enum Commands {
     #[cfg(feature = "browser")]
     Capture(Capture),
+    Decrypt(Decrypt),
     Extract(Extract),
+    Inspect(Inspect),
     Merge(Merge),
     Save(Box<Save>),
 }
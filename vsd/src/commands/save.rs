@@ -1,9 +1,10 @@
 use crate::{
-    automation::{Prompter, SelectOptions},
+    automation::{Prompter, RangePreference, SelectOptions},
     cookie::{CookieJar, CookieParam},
-    downloader::{self, Decrypter},
+    downloader::{self, Decrypter, ImageSubtitleFormat, SubtitleFormat, SubtitleTiming},
 };
 use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
 use clap::Args;
 use cookie::Cookie;
 use reqwest::{
@@ -37,9 +38,18 @@ pub struct Save {
     #[arg(short, long)]
     pub directory: Option<PathBuf>,
 
+    /// Format for image based subtitles (ttml images), which can't be converted to text formats.
+    /// Png images are saved in a directory along with a bdn xml index and aren't muxed.
+    #[arg(long, value_enum, default_value_t = ImageSubtitleFormat::Sup)]
+    pub image_subs_format: ImageSubtitleFormat,
+
     /// Mux all downloaded streams to a video container (.mp4, .mkv, etc.) using ffmpeg.
     /// Note that existing files will be overwritten and downloaded streams will be deleted.
-    #[arg(short, long)]
+    #[arg(
+        short,
+        long,
+        long_help = "Mux all downloaded streams to a video container (.mp4, .mkv, etc.) using ffmpeg.\nNote that existing files will be overwritten and downloaded streams will be deleted.\n\nOutput path can also be a template filled from stream metadata.\nIf template contains any placeholder other than {title} then every stream is saved separately without muxing.\n\nPLACEHOLDERS: {title}, {id}, {type}, {lang}, {name}, {codecs}, {bandwidth}, {resolution}, {width}, {height}, {ext}\n\nEXAMPLES:\n|> {title}.mkv\n|> {title}/{lang}/{resolution}_{bandwidth}.{ext}"
+    )]
     pub output: Option<PathBuf>,
 
     /// Parse playlist and returns it in json format.
@@ -52,6 +62,14 @@ pub struct Save {
     #[arg(long, default_value = "copy")]
     pub subs_codec: String,
 
+    /// Convert downloaded subtitles to this format.
+    /// By default wvtt subtitles are saved as webvtt and stpp (ttml) subtitles and closed captions as subrip.
+    #[arg(long, value_enum)]
+    pub subs_format: Option<SubtitleFormat>,
+
+    #[command(flatten)]
+    pub subs_timing: SubtitleTiming,
+
     /// Prompt for custom streams selection with modern style input prompts. By default proceed with defaults.
     #[arg(short, long, help_heading = "Automation Options")]
     pub interactive: bool,
@@ -60,17 +78,26 @@ pub struct Save {
     #[arg(long, help_heading = "Automation Options")]
     pub interactive_raw: bool,
 
+    /// List drm systems, key ids and license urls of all streams using `pssh` data embedded inside playlist.
+    #[arg(long, help_heading = "Automation Options")]
+    pub list_drm: bool,
+
     /// List all the streams present inside the playlist.
     #[arg(short, long, help_heading = "Automation Options")]
     pub list_streams: bool,
 
+    /// Video range to be preferred while sorting video streams.
+    /// Sorted streams are also used for choosing best and worst video streams.
+    #[arg(long, help_heading = "Automation Options", value_enum, default_value_t = RangePreference::Any)]
+    pub range_preference: RangePreference,
+
     /// Filters to be applied for automatic stream selection.
     #[arg(
         short,
         long,
         help_heading = "Automation Options",
         default_value = "v=best:s=en",
-        long_help = "Filters to be applied for automatic stream selection.\n\nSYNTAX: `v={}:a={}:s={}` where `{}` (in priority order) can contain\n|> all: select all streams.\n|> skip: skip all streams or select inverter.\n|> 1,2: ids obtained by --list-streams flag.\n|> 1080p,1280x720: stream resolution.\n|> en,fr: stream language.\n\nEXAMPLES:\n|> v=skip:a=skip:s=all (download all sub streams)\n|> a:en:s=en (prefer en lang)\n|> v=1080p:a=all:s=skip (1080p with all audio streams)"
+        long_help = "Filters to be applied for automatic stream selection.\n\nSYNTAX: `v={}:a={}:s={}` where `{}` (in priority order) can contain\n|> all: select all streams.\n|> skip: skip all streams or select inverter.\n|> 1,2: ids obtained by --list-streams flag.\n|> 1080p,1280x720: stream resolution.\n|> sdr,hdr,pq,hlg,hdr10,dv: video range or dynamic range format (v).\n|> range:{}: same as above e.g. range:pq (v).\n|> en,fr: stream language.\n|> default,forced: streams marked as default or forced (a, s).\n|> noforced: never select forced streams (a, s).\n|> char:{}: streams with characteristic (a, s).\n|> group:{}: streams with group id (a, s).\n|> role:{}: streams with dash role e.g. main, commentary (v, a, s).\n|> access:{}: streams with dash accessibility e.g. description, caption (a, s).\n|> view:{}: streams with dash viewpoint (v, a, s).\n\nAudio and subtitle streams paired with selected video stream are preferred.\n\nEXAMPLES:\n|> v=skip:a=skip:s=all (download all sub streams)\n|> a:en:s=en (prefer en lang)\n|> v=1080p:a=all:s=skip (1080p with all audio streams)\n|> v=best,sdr (best sdr video stream)\n|> a=char:public.accessibility.describes-video:s=noforced (audio description)"
     )]
     pub select_streams: String,
 
@@ -117,10 +144,27 @@ pub struct Save {
     pub keys: Decrypter,
 
     /// Download encrypted streams without decrypting them.
-    /// Note that --output flag is ignored if this flag is used.
+    /// Encryption info of every encrypted stream is saved in a <stream>.drm.json sidecar file.
+    /// Note that --output flag is ignored if any aes-128 encrypted stream is selected.
     #[arg(long, help_heading = "Decrypt Options")]
     pub no_decrypt: bool,
 
+    /// Only download segments on or before this wall clock time.
+    /// Segments are mapped using their program date time (EXT-X-PROGRAM-DATE-TIME).
+    /// EXAMPLE: --end 2025-01-01T10:30:00Z
+    #[arg(long, help_heading = "Download Options", value_parser = date_time_parser)]
+    pub end: Option<DateTime<FixedOffset>>,
+
+    /// Export chapters (dash periods or hls EXT-X-DATERANGE tags) of selected streams as json.
+    /// Chapters are always embedded in output when streams are muxed using ffmpeg.
+    #[arg(long, help_heading = "Download Options", value_name = "PATH")]
+    pub export_chapters: Option<PathBuf>,
+
+    /// Record live hls streams from the live edge until playlist ends or ctrl+c is pressed.
+    /// Low-latency (LL-HLS) streams are recorded part by part using blocking playlist reloads.
+    #[arg(long, help_heading = "Download Options")]
+    pub live: bool,
+
     /// Maximum number of retries to download an individual segment.
     #[arg(long, help_heading = "Download Options", default_value_t = 15)]
     pub retries: u8,
@@ -130,6 +174,24 @@ pub struct Save {
     #[arg(long, help_heading = "Download Options")]
     pub no_merge: bool,
 
+    /// Skip segments inside ad breaks marked by EXT-X-CUE-OUT/EXT-X-CUE-IN
+    /// or EXT-X-DATERANGE (SCTE35-OUT/SCTE35-IN) tags.
+    /// Timestamps of segments after skipped ads are reset while muxing (--output) and subtitle cues
+    /// are moved back by duration of skipped ads, so that output stays contiguous.
+    #[arg(long, help_heading = "Download Options")]
+    pub skip_ads: bool,
+
+    /// Download every discontinuity block (EXT-X-DISCONTINUITY or dash period) of a stream
+    /// into its own file instead of merging them together.
+    #[arg(long, help_heading = "Download Options")]
+    pub split_discontinuities: bool,
+
+    /// Only download segments on or after this wall clock time.
+    /// Segments are mapped using their program date time (EXT-X-PROGRAM-DATE-TIME).
+    /// EXAMPLE: --start 2025-01-01T10:00:00+05:30
+    #[arg(long, help_heading = "Download Options", value_parser = date_time_parser)]
+    pub start: Option<DateTime<FixedOffset>>,
+
     /// Total number of threads for parllel downloading of segments.
     /// Number of threads should be in range 1-16 (inclusive).
     #[arg(short, long, help_heading = "Download Options", default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..=16))]
@@ -198,7 +260,11 @@ impl Save {
         )?;
 
         if self.list_streams {
-            downloader::list_all_streams(&meta)?;
+            downloader::list_all_streams(&meta, &self.range_preference)?;
+        } else if self.list_drm {
+            let playlist =
+                downloader::parse_all_streams(self.base_url.clone(), &client, &meta, &self.query)?;
+            downloader::list_drm(&playlist.streams);
         } else if self.parse {
             let playlist =
                 downloader::parse_all_streams(self.base_url.clone(), &client, &meta, &self.query)?;
@@ -210,6 +276,7 @@ impl Save {
                 &meta,
                 &prompter,
                 &self.query,
+                &self.range_preference,
                 SelectOptions::parse(&self.select_streams),
             )?;
 
@@ -218,12 +285,21 @@ impl Save {
                 client,
                 self.keys,
                 self.directory,
+                self.end,
+                self.export_chapters,
+                self.image_subs_format,
+                self.live,
                 self.no_decrypt,
                 self.no_merge,
                 self.output,
                 self.query,
                 streams,
+                self.skip_ads,
+                self.split_discontinuities,
+                self.start,
                 self.subs_codec,
+                self.subs_format,
+                self.subs_timing,
                 self.retries,
                 self.threads,
             )?;
@@ -253,7 +329,12 @@ fn cookie_parser(s: &str) -> Result<CookieParams, String> {
     }
 }
 
-fn keys_parser(s: &str) -> Result<Decrypter, String> {
+fn date_time_parser(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map_err(|_| format!("could not parse {s} as rfc 3339 date time."))
+}
+
+pub(super) fn keys_parser(s: &str) -> Result<Decrypter, String> {
     if s.is_empty() {
         return Ok(Decrypter::None);
     }
@@ -5,4 +5,4 @@ mod template;
 use locator::DashUrl;
 use template::Template;
 
-pub(crate) use playlist::{parse_as_master, push_segments};
+pub(crate) use playlist::{parse_as_master, parse_content_steering, push_segments};
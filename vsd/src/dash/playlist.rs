@@ -7,16 +7,28 @@
 */
 
 use super::{DashUrl, Template};
-use crate::playlist::{
-    Key, KeyMethod, Map, MasterPlaylist, MediaPlaylist, MediaType, PlaylistType, Range, Segment,
+use crate::{
+    playlist::{
+        Chapter, ContentSteering, Key, KeyMethod, Map, MasterPlaylist, MediaPlaylist, MediaType,
+        PlaylistType, Range, Segment,
+    },
+    utils,
 };
 use anyhow::{Result, anyhow, bail};
-use dash_mpd::MPD;
-use reqwest::{Url, blocking::Client, header};
+use dash_mpd::{Accessibility, AdaptationSet, BaseURL, MPD, Representation};
+use regex::Regex;
+use reqwest::Url;
 use std::collections::HashMap;
 
 pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
     let mut streams = vec![];
+    let title = mpd
+        .ProgramInformation
+        .iter()
+        .find_map(|x| x.Title.as_ref().and_then(|y| y.content.clone()))
+        .map(|x| x.trim().to_owned())
+        .filter(|x| !x.is_empty())
+        .or(crate::playlist::title_from_uri(uri));
 
     if let Some(period) = mpd.periods.first() {
         let period_index = 0;
@@ -57,8 +69,52 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
                             _ => media_type,
                         };
                     }
-                
+
+                let mut roles: Vec<String> = vec![];
+
+                for role in &adaptation_set.Role {
+                    if let Some(value) = &role.value
+                        && !roles.contains(value)
+                    {
+                        roles.push(value.to_owned());
+                    }
+                }
+
+                let mut accessibility = vec![];
+
+                for descriptor in &adaptation_set.Accessibility {
+                    if let Some(value) = accessibility_value(descriptor)
+                        && !accessibility.contains(&value)
+                    {
+                        accessibility.push(value);
+                    }
+                }
+
+                let supplemental_codecs = representation.scte214_supplemental_codecs.clone();
+                let video_range = if media_type == MediaType::Video {
+                    crate::playlist::video_range(
+                        codecs.as_deref(),
+                        supplemental_codecs.as_deref(),
+                        transfer_characteristics(representation, adaptation_set),
+                    )
+                } else {
+                    None
+                };
+                let dynamic_range = if media_type == MediaType::Video {
+                    crate::playlist::dynamic_range(
+                        codecs.as_deref(),
+                        supplemental_codecs.as_deref(),
+                        video_range.as_deref(),
+                    )
+                } else {
+                    None
+                };
+
                 streams.push(MediaPlaylist {
+                    accessibility,
+                    ad_breaks: vec![],
+                    audio_group: None,
+                    autoselect: false,
                     bandwidth: representation.bandwidth,
                     channels: representation
                         .AudioChannelConfiguration
@@ -70,10 +126,16 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
                             .first()
                             .and_then(|x| x.value.as_ref().map(|y| y.parse::<f32>().ok()))
                             .flatten()),
+                    chapters: vec![],
+                    characteristics: None,
+                    closed_captions_group: None,
                     codecs,
+                    default: media_type != MediaType::Video && roles.iter().any(|x| x == "main"),
+                    dynamic_range,
                     extension: mime_type
                         .as_ref()
                         .and_then(|x| x.split_once('/').map(|x| x.1.to_owned())),
+                    forced: roles.iter().any(|x| x == "forced-subtitle"),
                     frame_rate: if representation.frameRate.is_some() {
                         parse_frame_rate(&representation.frameRate)
                     } else if adaptation_set.frameRate.is_some() {
@@ -81,8 +143,11 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
                     } else {
                         None
                     },
+                    group_id: None,
+                    hdcp_level: None,
                     id: String::new(), // Cannot be comment here
                     i_frame: false,    // Cannot be comment here
+                    instream_id: None,
                     language: adaptation_set.lang.clone(),
                     live: if let Some(mpdtype) = &mpd.mpdtype {
                         mpdtype == "dynamic"
@@ -91,6 +156,14 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
                     },
                     media_sequence: 0,
                     media_type,
+                    name: representation
+                        .Label
+                        .first()
+                        .or(adaptation_set.Label.first())
+                        .map(|x| x.content.trim().to_owned())
+                        .filter(|x| !x.is_empty()),
+                    pathway_id: None,
+                    pathway_uris: Default::default(),
                     playlist_type: PlaylistType::Dash,
                     resolution: if let (Some(width), Some(height)) =
                         (representation.width, representation.height)
@@ -99,9 +172,19 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
                     } else {
                         None
                     },
+                    roles,
                     segments: vec![], // Cannot be comment here
+                    steering: None,
+                    subtitles_group: None,
+                    supplemental_codecs,
+                    title: title.clone(),
                     uri: DashUrl::new(period_index, adaptation_index, representation_index)
                         .to_string(),
+                    video_range,
+                    viewpoint: adaptation_set
+                        .Viewpoint
+                        .first()
+                        .and_then(|x| x.value.clone()),
                 });
             }
         }
@@ -109,21 +192,142 @@ pub(crate) fn parse_as_master(mpd: &MPD, uri: &str) -> MasterPlaylist {
 
     MasterPlaylist {
         playlist_type: PlaylistType::Dash,
+        steering: None,
         uri: uri.to_owned(),
         streams,
     }
 }
 
+/// ContentSteering element isn't parsed by dash-mpd, so it is extracted from raw xml.
+/// <ContentSteering defaultServiceLocation="alpha" queryBeforeStart="true">https://steeering.example.com/dash.dcsm</ContentSteering>
+pub(crate) fn parse_content_steering(mpd: &MPD, xml: &str, uri: &str) -> Option<ContentSteering> {
+    let re = Regex::new(r"<(?:\w+:)?ContentSteering\b([^>]*)>([^<]+)<").unwrap();
+    let captures = re.captures(xml)?;
+    let default_pathway = Regex::new(r#"defaultServiceLocation="([^"]*)""#)
+        .unwrap()
+        .captures(&captures[1])
+        .map(|x| x[1].to_owned());
+    let mut pathways = vec![];
+
+    for period in &mpd.periods {
+        for adaptation_set in &period.adaptations {
+            let base_urls = mpd
+                .base_url
+                .iter()
+                .chain(&period.BaseURL)
+                .chain(&adaptation_set.BaseURL)
+                .chain(
+                    adaptation_set
+                        .representations
+                        .iter()
+                        .flat_map(|x| &x.BaseURL),
+                );
+
+            for base_url in base_urls {
+                if let Some(location) = &base_url.serviceLocation
+                    && !pathways.contains(location)
+                {
+                    pathways.push(location.to_owned());
+                }
+            }
+        }
+    }
+
+    Some(ContentSteering {
+        default_pathway,
+        pathway: None,
+        pathway_priority: vec![],
+        pathways,
+        reload_uri: Url::parse(uri)
+            .and_then(|x| x.join(captures[2].trim()))
+            .ok()?
+            .to_string(),
+        ttl: crate::steering::DEFAULT_TTL,
+    })
+}
+
+/// BaseURL of service location (content steering pathway), otherwise first one.
+fn select_base_url<'a>(base_urls: &'a [BaseURL], location: Option<&str>) -> Option<&'a BaseURL> {
+    base_urls
+        .iter()
+        .find(|x| location.is_some() && x.serviceLocation.as_deref() == location)
+        .or(base_urls.first())
+}
+
+/// Video range signalled by transfer characteristics property.
+/// https://dashif.org/docs/DASH-IF-IOP-v4.3.pdf (6.2.5.1)
+fn transfer_characteristics(
+    representation: &Representation,
+    adaptation_set: &AdaptationSet,
+) -> Option<&'static str> {
+    representation
+        .essential_property
+        .iter()
+        .map(|x| (&x.schemeIdUri, &x.value))
+        .chain(
+            representation
+                .supplemental_property
+                .iter()
+                .map(|x| (&x.schemeIdUri, &x.value)),
+        )
+        .chain(
+            adaptation_set
+                .essential_property
+                .iter()
+                .map(|x| (&x.schemeIdUri, &x.value)),
+        )
+        .chain(
+            adaptation_set
+                .supplemental_property
+                .iter()
+                .map(|x| (&x.schemeIdUri, &x.value)),
+        )
+        .filter(|(scheme_id_uri, _)| {
+            scheme_id_uri.as_str() == "urn:mpeg:mpegB:cicp:TransferCharacteristics"
+        })
+        .find_map(|(_, value)| match value.as_deref() {
+            Some("1") | Some("6") | Some("13") | Some("14") | Some("15") => Some("SDR"),
+            Some("16") => Some("PQ"),
+            Some("18") => Some("HLG"),
+            _ => None,
+        })
+}
+
+/// Readable value of an accessibility descriptor.
+/// https://dashif.org/identifiers/role_and_accessibility
+fn accessibility_value(accessibility: &Accessibility) -> Option<String> {
+    match (
+        accessibility.schemeIdUri.as_str(),
+        accessibility.value.as_deref(),
+    ) {
+        ("urn:tva:metadata:cs:AudioPurposeCS:2007", Some("1")) => Some("description".to_owned()),
+        ("urn:tva:metadata:cs:AudioPurposeCS:2007", Some("2")) => {
+            Some("hard-of-hearing".to_owned())
+        }
+        ("urn:scte:dash:cc:cea-608:2015", _) => Some("cea-608".to_owned()),
+        ("urn:scte:dash:cc:cea-708:2015", _) => Some("cea-708".to_owned()),
+        (_, Some(x)) => Some(x.to_owned()),
+        _ => None,
+    }
+}
+
+/// Byte range of sidx box (SegmentBase@indexRange) is fetched using `fetch_range`,
+/// so that segments of local playlists can be read from files.
 pub(crate) fn push_segments(
     mpd: &MPD,
     playlist: &mut MediaPlaylist,
     base_url: &str,
-    client: &Client,
-    query: &HashMap<String, String>,
+    fetch_range: &dyn Fn(&Url, &Range) -> Result<Vec<u8>>,
 ) -> Result<()> {
     let location = playlist.uri.parse::<DashUrl>().map_err(|x| anyhow!(x))?;
+    let mut period_start = 0.0;
+
+    for (period_index, period) in mpd.periods.iter().enumerate() {
+        // Period without start attribute begins where previous period ends.
+        if let Some(start) = &period.start {
+            period_start = start.as_secs_f32();
+        }
 
-    for period in mpd.periods.iter() {
         for (adaptation_index, adaptation_set) in period.adaptations.iter().enumerate() {
             for (representation_index, representation) in
                 adaptation_set.representations.iter().enumerate()
@@ -131,6 +335,7 @@ pub(crate) fn push_segments(
                 if adaptation_index == location.adaptation_set
                     && representation_index == location.representation
                 {
+                    let period_segments_start = playlist.segments.len();
                     let mut period_duration_secs = 0.0;
 
                     if let Some(duration) = &mpd.mediaPresentationDuration {
@@ -141,26 +346,32 @@ pub(crate) fn push_segments(
                         period_duration_secs = duration.as_secs_f32();
                     }
 
-                    let mut base_url = base_url.parse::<Url>().unwrap();
-
-                    if let Some(mpd_baseurl) = mpd.base_url.first().map(|x| x.base.as_ref()) {
-                        base_url = base_url.join(mpd_baseurl)?;
-                    }
+                    let resolve_base_url = |location: Option<&str>| -> Result<Url> {
+                        let mut url = base_url.parse::<Url>().unwrap();
+
+                        for base_urls in [
+                            &mpd.base_url,
+                            &period.BaseURL,
+                            &adaptation_set.BaseURL,
+                            &representation.BaseURL,
+                        ] {
+                            if let Some(x) = select_base_url(base_urls, location) {
+                                url = url.join(&x.base)?;
+                            }
+                        }
 
-                    if let Some(period_baseurl) = period.BaseURL.first().map(|x| x.base.as_ref()) {
-                        base_url = base_url.join(period_baseurl)?;
-                    }
+                        Ok(url)
+                    };
 
-                    if let Some(adaptation_set_baseurl) =
-                        adaptation_set.BaseURL.first().map(|x| x.base.as_ref())
-                    {
-                        base_url = base_url.join(adaptation_set_baseurl)?;
-                    }
+                    let base_url = resolve_base_url(playlist.pathway_id.as_deref())?;
 
-                    if let Some(representation_baseurl) =
-                        representation.BaseURL.first().map(|x| x.base.as_ref())
-                    {
-                        base_url = base_url.join(representation_baseurl)?;
+                    if let Some(steering) = &playlist.steering {
+                        for location in &steering.pathways {
+                            playlist.pathway_uris.insert(
+                                location.to_owned(),
+                                resolve_base_url(Some(location))?.to_string(),
+                            );
+                        }
                     }
 
                     let mut init_map = None;
@@ -366,9 +577,9 @@ pub(crate) fn push_segments(
                                 );
                             }
 
-                            let mut number = segment_template.startNumber.unwrap_or(1) as i64;
+                            let number = segment_template.startNumber.unwrap_or(1) as i64;
                             let total_number =
-                                number + (period_duration_secs / segment_duration).round() as i64;
+                                (period_duration_secs / segment_duration).round() as i64;
 
                             // // For a live manifest (dynamic MPD), we look at the time elapsed since now
                             // // and the mpd.availabilityStartTime to determine the correct value for
@@ -394,7 +605,7 @@ pub(crate) fn push_segments(
                             //     }
                             // }
 
-                            for _ in 1..=total_number {
+                            for number in number..(number + total_number) {
                                 template.insert("Number", number.to_string());
 
                                 playlist.segments.push(Segment {
@@ -402,8 +613,6 @@ pub(crate) fn push_segments(
                                     uri: base_url.join(&template.resolve(&media))?.to_string(),
                                     ..Default::default()
                                 });
-
-                                number += 1;
                             }
                         }
                     } else if let Some(segment_base) = &representation.SegmentBase {
@@ -425,12 +634,7 @@ pub(crate) fn push_segments(
                         }
 
                         if let Some(index_range) = parse_range(&segment_base.indexRange) {
-                            let request = client
-                                .get(base_url.as_str())
-                                .query(query)
-                                .header(header::RANGE, index_range.as_header_value());
-                            let response = request.send()?;
-                            let bytes = response.bytes()?;
+                            let bytes = fetch_range(&base_url, &index_range)?;
 
                             if let Some(init_map) = &mut init_map {
                                 init_map.range = Some(Range {
@@ -464,6 +668,38 @@ pub(crate) fn push_segments(
                         });
                     }
 
+                    // Media time of segments begins from presentation time offset at period start.
+                    let presentation_time_offset = representation
+                        .SegmentTemplate
+                        .as_ref()
+                        .or(adaptation_set.SegmentTemplate.as_ref())
+                        .and_then(|x| Some((x.presentationTimeOffset?, x.timescale)))
+                        .or(representation
+                            .SegmentBase
+                            .as_ref()
+                            .and_then(|x| Some((x.presentationTimeOffset?, x.timescale))))
+                        .map(|(offset, timescale)| offset as f32 / timescale.unwrap_or(1) as f32)
+                        .unwrap_or(0.0);
+                    let period_offset = period_start - presentation_time_offset;
+
+                    for segment in &mut playlist.segments[period_segments_start..] {
+                        segment.discontinuity = period_index as u64;
+
+                        if period_offset != 0.0 {
+                            segment.period_offset = Some(period_offset);
+                        }
+                    }
+
+                    if mpd.periods.len() > 1 {
+                        playlist.chapters.push(Chapter {
+                            start: period_segments_start,
+                            title: period
+                                .id
+                                .clone()
+                                .unwrap_or(format!("Period {}", period_index + 1)),
+                        });
+                    }
+
                     if playlist.segments.is_empty() {
                         bail!("no usable addressing mode identified for representation.");
                     }
@@ -504,13 +740,43 @@ pub(crate) fn push_segments(
 
                         first_segment.key = match encryption_type {
                             KeyMethod::None => None,
-                            x => Some(Key {
-                                default_kid,
-                                iv: None,
-                                key_format: None,
-                                method: x,
-                                uri: None,
-                            }),
+                            x => {
+                                let mut key = Key {
+                                    default_kid,
+                                    iv: None,
+                                    key_format: None,
+                                    method: x,
+                                    pssh: vec![],
+                                    uri: None,
+                                };
+
+                                for content_protection in representation
+                                    .ContentProtection
+                                    .iter()
+                                    .chain(&adaptation_set.ContentProtection)
+                                {
+                                    for pssh in &content_protection.cenc_pssh {
+                                        if let Some(data) = pssh
+                                            .content
+                                            .as_ref()
+                                            .and_then(|x| utils::decode_base64(x.trim()).ok())
+                                        {
+                                            key.push_pssh(&data, false);
+                                        }
+                                    }
+
+                                    if let Some(data) = content_protection
+                                        .msprpro
+                                        .as_ref()
+                                        .and_then(|x| x.content.as_ref())
+                                        .and_then(|x| utils::decode_base64(x.trim()).ok())
+                                    {
+                                        key.push_pssh(&data, true);
+                                    }
+                                }
+
+                                Some(key)
+                            }
                         };
 
                         first_segment.map = init_map;
@@ -518,6 +784,10 @@ pub(crate) fn push_segments(
                 }
             }
         }
+
+        if let Some(duration) = &period.duration {
+            period_start += duration.as_secs_f32();
+        }
     }
 
     Ok(())
@@ -552,3 +822,45 @@ fn parse_range(range: &Option<String>) -> Option<Range> {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_template_duration_start_number() {
+        let mpd = dash_mpd::parse(
+            r#"<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+<Period>
+<AdaptationSet mimeType="video/mp4">
+<SegmentTemplate media="$RepresentationID$/$Number$.m4s" initialization="$RepresentationID$/init.mp4" duration="2" startNumber="5"/>
+<Representation id="v" bandwidth="1000" codecs="avc1.64001f"/>
+</AdaptationSet>
+</Period>
+</MPD>"#,
+        )
+        .unwrap();
+        let uri = "https://example.com/manifest.mpd";
+        let mut stream = parse_as_master(&mpd, uri).streams.remove(0);
+        push_segments(&mpd, &mut stream, uri, &|_, _| {
+            bail!("no ranges are fetched.")
+        })
+        .unwrap();
+
+        let uris = stream
+            .segments
+            .iter()
+            .map(|x| x.uri.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uris,
+            [
+                "https://example.com/v/5.m4s",
+                "https://example.com/v/6.m4s",
+                "https://example.com/v/7.m4s",
+                "https://example.com/v/8.m4s",
+                "https://example.com/v/9.m4s",
+            ]
+        );
+    }
+}
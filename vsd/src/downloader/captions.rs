@@ -0,0 +1,195 @@
+use super::{SubtitleFormat, SubtitleTiming, mux::Stream};
+use crate::{output::OutputTemplate, playlist::MediaPlaylist};
+use anyhow::Result;
+use kdam::{BarExt, RichProgress, term::Colorizer};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use vsd_mp4::text::{CeaDecoder, Mp4CeaParser, Subtitles, TsCeaParser};
+
+/// Extract closed captions of selected caption streams from downloaded video stream.
+#[allow(clippy::too_many_arguments)]
+pub fn extract_captions(
+    captions: &[MediaPlaylist],
+    directory: Option<&PathBuf>,
+    output_template: Option<&OutputTemplate>,
+    pb: &Arc<Mutex<RichProgress>>,
+    subs_format: Option<&SubtitleFormat>,
+    subs_timing: &SubtitleTiming,
+    temp_files: &mut Vec<Stream>,
+    video: &Path,
+) -> Result<()> {
+    pb.lock().unwrap().write(format!(
+        " {} closed captions from {}",
+        "Extracting".colorize("cyan"),
+        video.to_string_lossy(),
+    ))?;
+
+    let (mut channels, skipped) = match decode(video) {
+        Ok(x) => x,
+        Err(e) => {
+            pb.lock().unwrap().write(format!(
+                "    {} skipping closed captions (couldn't decode {}: {})",
+                "Warning".colorize("yellow"),
+                video.to_string_lossy(),
+                e,
+            ))?;
+            return Ok(());
+        }
+    };
+
+    if skipped > 0 {
+        pb.lock().unwrap().write(format!(
+            "    {} skipped {} fragments of {} which couldn't be parsed for closed captions",
+            "Warning".colorize("yellow"),
+            skipped,
+            video.to_string_lossy(),
+        ))?;
+    }
+
+    // Closed captions are positioned by rows, which subrip supports better than webvtt.
+    let subs_format = subs_format.unwrap_or(&SubtitleFormat::Subrip);
+
+    for stream in captions {
+        let instream_id = stream.instream_id.as_deref().unwrap_or_default();
+
+        let Some(mut subs) = channels.remove(instream_id) else {
+            pb.lock().unwrap().write(format!(
+                "    {} skipping stream {} (no closed captions found in {} channel)",
+                "Warning".colorize("yellow"),
+                stream.id,
+                instream_id,
+            ))?;
+            continue;
+        };
+
+        subs_timing.apply(&mut subs);
+        let path = stream.path(
+            directory,
+            OsStr::new(subs_format.extension()),
+            output_template,
+        )?;
+        fs::write(&path, subs_format.build(&subs))?;
+        temp_files.push(Stream {
+            discontinuous: false,
+            language: stream.language.clone(),
+            media_type: stream.media_type.clone(),
+            path: path.clone(),
+            sidecar: None,
+        });
+        pb.lock().unwrap().write(format!(
+            "  {} {} ({})",
+            "Extracted".colorize("bold green"),
+            path.to_string_lossy(),
+            instream_id,
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Decode closed captions of every channel present inside a transport stream or mp4 file.
+/// Segments are read in order when stream is downloaded as a directory (`--no-merge`).
+/// Number of mp4 fragments which couldn't be parsed is returned along with captions.
+fn decode(path: &Path) -> Result<(BTreeMap<String, Subtitles>, usize)> {
+    let mut files = vec![];
+
+    if path.is_dir() {
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+
+        for i in 0.. {
+            let file = path.join(format!("{i}.{ext}"));
+
+            if !file.exists() {
+                break;
+            }
+
+            files.push(file);
+        }
+    } else {
+        files.push(path.to_owned());
+    }
+
+    let mut reader: Box<dyn Read> = Box::new(io::empty());
+
+    for file in files {
+        reader = Box::new(reader.chain(BufReader::new(File::open(file)?)));
+    }
+
+    let mut decoder = CeaDecoder::new();
+    let mut header = [0; 8];
+
+    if reader.read_exact(&mut header).is_err() {
+        return Ok((decoder.decode(), 0));
+    }
+
+    if header[0] == 0x47 {
+        let mut parser = TsCeaParser::new();
+        let mut buf = vec![0; 188 * 1024];
+        parser.parse(&header, &mut decoder);
+
+        loop {
+            let size = reader.read(&mut buf)?;
+
+            if size == 0 {
+                break;
+            }
+
+            parser.parse(&buf[..size], &mut decoder);
+        }
+
+        parser.flush(&mut decoder);
+        return Ok((decoder.decode(), 0));
+    }
+
+    let mut parser = None;
+    let mut moof = vec![];
+    let mut skipped = 0;
+
+    // Mp4 boxes are read one at a time, media data is parsed along with its preceding moof box.
+    loop {
+        let mut data = header.to_vec();
+        let mut size = u32::from_be_bytes(header[..4].try_into().unwrap()) as u64;
+
+        if size == 1 {
+            let mut large_size = [0; 8];
+            reader.read_exact(&mut large_size)?;
+            data.extend_from_slice(&large_size);
+            size = u64::from_be_bytes(large_size);
+        }
+
+        if size == 0 {
+            reader.read_to_end(&mut data)?;
+        } else {
+            let remaining = size.saturating_sub(data.len() as u64);
+            reader.by_ref().take(remaining).read_to_end(&mut data)?;
+        }
+
+        match &header[4..] {
+            b"moov" => parser = Mp4CeaParser::parse_init(&data).ok(),
+            b"moof" => moof = data,
+            b"mdat" => {
+                if let Some(parser) = &parser {
+                    moof.append(&mut data);
+                    if parser.parse_media(&moof, &mut decoder).is_err() {
+                        skipped += 1;
+                    }
+                }
+
+                moof.clear();
+            }
+            _ => (),
+        }
+
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+    }
+
+    Ok((decoder.decode(), skipped))
+}
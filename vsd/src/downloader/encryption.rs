@@ -4,7 +4,7 @@ use anyhow::{Result, anyhow, bail};
 use kdam::term::Colorizer;
 use reqwest::{Url, blocking::Client, header};
 use std::collections::{HashMap, HashSet};
-use vsd_mp4::pssh::Pssh;
+use vsd_mp4::pssh::{KeyIdSystemType, Pssh, PsshData};
 
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
@@ -59,7 +59,8 @@ impl Decrypter {
                 }
             },
             Decrypter::Mp4Decrypt(kid_key_pairs) => {
-                mp4decrypt::mp4decrypt(&data, kid_key_pairs, None).map_err(|x| anyhow!(x))?
+                let keys = matching_keys(kid_key_pairs, &data)?;
+                mp4decrypt::mp4decrypt(&data, &keys, None).map_err(|x| anyhow!(x))?
             }
             Decrypter::None => data,
         })
@@ -76,6 +77,44 @@ impl Decrypter {
     }
 }
 
+/// Keys for every kid found in init and media segment (per track `tenc` boxes and key rotation
+/// `seig` sample groups) which has a key. Fragments using a kid without any key are reported as an error.
+fn matching_keys(
+    kid_key_pairs: &HashMap<String, String>,
+    data: &[u8],
+) -> Result<HashMap<String, String>> {
+    let kids = vsd_mp4::pssh::encryption_kids(data).map_err(|x| anyhow!(x))?;
+
+    if kids.is_empty() {
+        return Ok(kid_key_pairs.clone());
+    }
+
+    let missing_kids = vsd_mp4::pssh::fragment_kids(data)
+        .map_err(|x| anyhow!(x))?
+        .into_iter()
+        .filter(|x| !kid_key_pairs.contains_key(x))
+        .collect::<Vec<_>>();
+
+    if !missing_kids.is_empty() {
+        bail!(
+            "no key found for key ids ({}) used by segment, use --keys flag to specify them.",
+            missing_kids.join(", ")
+        );
+    }
+
+    let keys = kid_key_pairs
+        .iter()
+        .filter(|(kid, _)| kids.contains(kid))
+        .map(|(kid, key)| (kid.to_owned(), key.to_owned()))
+        .collect::<HashMap<_, _>>();
+
+    if keys.is_empty() {
+        return Ok(kid_key_pairs.clone());
+    }
+
+    Ok(keys)
+}
+
 pub fn check_key_exists_for_kid(
     decrypter: &Decrypter,
     default_kids: &HashSet<String>,
@@ -136,11 +175,30 @@ pub fn extract_default_kids(
     let mut parsed_kids = HashSet::new();
 
     for stream in streams {
-        let stream_base_url = base_url
-            .clone()
-            .unwrap_or(stream.uri.parse::<Url>().unwrap());
+        let embedded_kids = match stream.segments.first() {
+            Some(Segment { key: Some(x), .. }) => x
+                .pssh_boxes()
+                .iter()
+                .filter_map(|x| x.all_key_ids().ok())
+                .flatten()
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        };
 
-        if let Some(Segment { map: Some(x), .. }) = stream.segments.first() {
+        // Key ids embedded inside playlist are preferred over fetching init segment.
+        let (default_kid, key_ids) = if !embedded_kids.is_empty() {
+            (
+                Some(
+                    stream
+                        .default_kid()
+                        .unwrap_or("00000000000000000000000000000000".to_owned()),
+                ),
+                embedded_kids,
+            )
+        } else if let Some(Segment { map: Some(x), .. }) = stream.segments.first() {
+            let stream_base_url = base_url
+                .clone()
+                .unwrap_or(stream.uri.parse::<Url>().unwrap());
             let url = stream_base_url.join(&x.uri)?;
             let mut request = client.get(url).query(query);
 
@@ -151,33 +209,105 @@ pub fn extract_default_kids(
             let response = request.send()?;
             let bytes = response.bytes()?;
 
-            let default_kid = vsd_mp4::pssh::default_kid(&bytes)?;
-            let pssh = Pssh::new(&bytes).map_err(|x| anyhow!(x))?;
+            (
+                vsd_mp4::pssh::default_kid(&bytes)?,
+                Pssh::new(&bytes).map_err(|x| anyhow!(x))?.key_ids,
+            )
+        } else {
+            continue;
+        };
 
-            for kid in pssh.key_ids {
-                if default_kid == Some("00000000000000000000000000000000".to_owned())
-                    && matches!(kid.system_type, vsd_mp4::pssh::KeyIdSystemType::WideVine)
-                {
-                    default_kids.insert(kid.value.clone());
+        for kid in key_ids {
+            if default_kid == Some("00000000000000000000000000000000".to_owned())
+                && matches!(kid.system_type, KeyIdSystemType::WideVine)
+            {
+                default_kids.insert(kid.value.clone());
+            }
+
+            if !parsed_kids.contains(&kid.value) {
+                parsed_kids.insert(kid.value.clone());
+                println!(
+                    "      {} [{:>9}] {} {}",
+                    "KeyId".colorize("bold red"),
+                    kid.system_type.to_string(),
+                    kid.uuid(),
+                    if default_kids.contains(&kid.value) {
+                        "(required)"
+                    } else {
+                        ""
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(default_kids)
+}
+
+/// List drm systems, key ids and license urls of streams from `pssh` boxes embedded inside playlist.
+pub fn list_drm(streams: &[MediaPlaylist]) {
+    println!("{}", "------------ DRM -------------".colorize("cyan"));
+
+    for (i, stream) in streams.iter().enumerate() {
+        println!(
+            "{}",
+            format!(
+                "{:>2}) [{:>5}] {}",
+                i + 1,
+                stream.media_type.to_string(),
+                stream.display_stream()
+            )
+            .trim_end()
+        );
+
+        let Some(Segment { key: Some(key), .. }) = stream.segments.first() else {
+            println!("    not encrypted");
+            continue;
+        };
+
+        println!(
+            "    method: {}, default kid: {}",
+            key.method,
+            stream.default_kid().as_deref().unwrap_or("none")
+        );
+
+        let pssh_boxes = key.pssh_boxes();
+
+        if pssh_boxes.is_empty() {
+            println!("    no pssh data embedded in playlist");
+        }
+
+        for pssh in pssh_boxes {
+            println!(
+                "    {} v{} ({})",
+                pssh.system_type().to_string().colorize("bold green"),
+                pssh.version,
+                pssh.system_id
+            );
+
+            for kid in pssh.all_key_ids().unwrap_or_default() {
+                println!("      kid: {}", kid.uuid());
+            }
+
+            match pssh.decode() {
+                Ok(PsshData::PlayReady(x)) => {
+                    if let Some(la_url) = x.la_url {
+                        println!("      la url: {la_url}");
+                    }
                 }
+                Ok(PsshData::Widevine(x)) => {
+                    if let Some(provider) = x.provider {
+                        println!("      provider: {provider}");
+                    }
 
-                if !parsed_kids.contains(&kid.value) {
-                    parsed_kids.insert(kid.value.clone());
-                    println!(
-                        "      {} [{:>9}] {} {}",
-                        "KeyId".colorize("bold red"),
-                        kid.system_type.to_string(),
-                        kid.uuid(),
-                        if default_kids.contains(&kid.value) {
-                            "(required)"
-                        } else {
-                            ""
-                        },
-                    );
+                    if let Some(content_id) = x.content_id {
+                        println!("      content id: {}", hex::encode(content_id));
+                    }
                 }
+                _ => (),
             }
         }
     }
 
-    Ok(default_kids)
+    println!("{}", "------------------------------".colorize("cyan"));
 }
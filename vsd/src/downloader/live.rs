@@ -0,0 +1,324 @@
+use crate::{
+    downloader::mux::Stream,
+    hls::{LivePart, LivePlaylist, parse_live_playlist},
+    output::OutputTemplate,
+    playlist::MediaPlaylist,
+    utils,
+};
+use anyhow::{Result, bail};
+use kdam::term::Colorizer;
+use reqwest::{Url, blocking::Client, header};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Record live hls streams from the live edge until playlist ends or ctrl+c is pressed.
+/// Low-latency playlists are recorded part by part, parts are appended in order so that
+/// they are merged back into full segments.
+/// Sidecar files aren't written for live streams as their keys can change while recording,
+/// so encrypted live streams recorded with `--no-decrypt` can't be decrypted later by vsd.
+#[allow(clippy::too_many_arguments)]
+pub fn record_streams(
+    base_url: &Option<Url>,
+    client: &Client,
+    directory: Option<&PathBuf>,
+    no_decrypt: bool,
+    output_template: Option<&OutputTemplate>,
+    query: &HashMap<String, String>,
+    retries: u8,
+    streams: Vec<MediaPlaylist>,
+    temp_files: &mut Vec<Stream>,
+) -> Result<()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = stop.clone();
+    ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst))?;
+
+    println!(
+        "       {} press ctrl+c to stop recording",
+        "Note".colorize("cyan")
+    );
+
+    let mut recorders = vec![];
+
+    for stream in streams {
+        let path = stream.path(directory, stream.extension(), output_template)?;
+
+        println!(
+            "  {} [{:>5}] {}",
+            "Recording".colorize("bold green"),
+            stream.media_type.to_string(),
+            path.to_string_lossy(),
+        );
+
+        temp_files.push(Stream {
+            discontinuous: false,
+            language: stream.language.clone(),
+            media_type: stream.media_type.clone(),
+            path: path.clone(),
+            sidecar: None,
+        });
+
+        let uri = stream.uri.parse::<Url>()?;
+
+        recorders.push(Recorder {
+            base_url: base_url.clone().unwrap_or(uri.clone()),
+            client,
+            file: File::create(&path)?,
+            label: stream.media_type.to_string(),
+            next: (0, 0),
+            no_decrypt,
+            partial: 0,
+            query,
+            retries,
+            stop: stop.clone(),
+            stored: 0,
+            uri,
+        });
+    }
+
+    thread::scope(|s| {
+        let handles = recorders
+            .into_iter()
+            .map(|mut x| s.spawn(move || x.record()))
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        Ok(())
+    })
+}
+
+struct Recorder<'a> {
+    base_url: Url,
+    client: &'a Client,
+    file: File,
+    label: String,
+    /// Media sequence number and part index to be downloaded next.
+    next: (u64, usize),
+    no_decrypt: bool,
+    /// Bytes of parts already written for segment to be downloaded next.
+    partial: usize,
+    query: &'a HashMap<String, String>,
+    retries: u8,
+    stop: Arc<AtomicBool>,
+    stored: usize,
+    uri: Url,
+}
+
+impl Recorder<'_> {
+    fn record(&mut self) -> Result<()> {
+        let mut playlist = parse_live_playlist(&self.fetch_playlist(self.uri.clone())?);
+
+        if playlist.encrypted && !self.no_decrypt {
+            bail!(
+                "recording encrypted live streams isn't supported, use --no-decrypt flag to record them without decrypting."
+            );
+        }
+
+        if playlist.encrypted {
+            println!(
+                "    {} [{:>5}] encryption info of live streams isn't saved in a sidecar file",
+                "Warning".colorize("yellow"),
+                self.label,
+            );
+        }
+
+        if let Some(map) = &playlist.map {
+            let bytes = self.fetch(map, self.retries)?;
+            self.write(&bytes)?;
+        }
+
+        // Start from the live edge, LL-HLS playlists are started from the segment which is
+        // currently being published and others are started three segments behind.
+        if let Some(segment) = playlist.segments.last() {
+            self.next.0 = if playlist.is_low_latency() {
+                segment.media_sequence
+            } else {
+                playlist.segments[playlist.segments.len().saturating_sub(3)].media_sequence
+            };
+        }
+
+        loop {
+            self.download_available(&playlist)?;
+
+            if playlist.end_list || self.stop.load(Ordering::SeqCst) {
+                break;
+            }
+
+            self.download_preload_hint(&playlist)?;
+            playlist = self.reload(&playlist)?;
+        }
+
+        self.file.flush()?;
+        println!(
+            "   {} [{:>5}] recording ({})",
+            "Finished".colorize("bold green"),
+            self.label,
+            utils::format_bytes(self.stored, 2).2,
+        );
+        Ok(())
+    }
+
+    fn download_available(&mut self, playlist: &LivePlaylist) -> Result<()> {
+        for segment in &playlist.segments {
+            if self.stop.load(Ordering::SeqCst) {
+                break;
+            }
+
+            if segment.media_sequence < self.next.0 {
+                continue;
+            }
+
+            if segment.media_sequence > self.next.0 {
+                println!(
+                    "    {} [{:>5}] skipped {} segments (fell behind live edge)",
+                    "Warning".colorize("yellow"),
+                    self.label,
+                    segment.media_sequence - self.next.0,
+                );
+                self.next = (segment.media_sequence, 0);
+                self.partial = 0;
+            }
+
+            let full = segment.uri.as_ref().map(|x| LivePart {
+                range: segment.range.clone(),
+                uri: x.to_owned(),
+            });
+
+            match &full {
+                Some(full) if self.next.1 == 0 => {
+                    let bytes = self.fetch(full, self.retries)?;
+                    self.write(&bytes)?;
+                }
+                // Parts of a partly recorded segment are removed from playlist once it is
+                // complete, so rest of it is taken from the full segment.
+                Some(full) if segment.parts.is_empty() => {
+                    let bytes = self.fetch(full, self.retries)?;
+                    self.write(bytes.get(self.partial..).unwrap_or_default())?;
+                }
+                _ if playlist.is_low_latency() => {
+                    for part in segment.parts.iter().skip(self.next.1) {
+                        let bytes = self.fetch(part, self.retries)?;
+                        self.write(&bytes)?;
+                        self.next.1 += 1;
+                        self.partial += bytes.len();
+                    }
+                }
+                _ => (),
+            }
+
+            if full.is_some() {
+                self.next = (segment.media_sequence + 1, 0);
+                self.partial = 0;
+                println!(
+                    "   {} [{:>5}] segment #{} ({})",
+                    "Recorded".colorize("bold green"),
+                    self.label,
+                    segment.media_sequence,
+                    utils::format_bytes(self.stored, 2).2,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Preload hint points to the part which is going to be published next,
+    /// server holds the request until it is available.
+    fn download_preload_hint(&mut self, playlist: &LivePlaylist) -> Result<()> {
+        let Some(hint) = &playlist.preload_hint else {
+            return Ok(());
+        };
+
+        let is_next = match playlist.segments.last() {
+            Some(segment) if segment.uri.is_none() => {
+                segment.media_sequence == self.next.0 && segment.parts.len() == self.next.1
+            }
+            Some(segment) => segment.media_sequence + 1 == self.next.0 && self.next.1 == 0,
+            None => false,
+        };
+
+        if is_next && let Ok(bytes) = self.fetch(hint, 1) {
+            self.write(&bytes)?;
+            self.next.1 += 1;
+            self.partial += bytes.len();
+        }
+
+        Ok(())
+    }
+
+    /// Blocking playlist reload (`_HLS_msn` and `_HLS_part`) if server supports it,
+    /// otherwise reload after part target or target duration.
+    fn reload(&self, playlist: &LivePlaylist) -> Result<LivePlaylist> {
+        if playlist.can_block_reload {
+            let mut url = self.uri.clone();
+
+            {
+                let mut pairs = url.query_pairs_mut();
+                pairs.append_pair("_HLS_msn", &self.next.0.to_string());
+
+                if playlist.is_low_latency() {
+                    pairs.append_pair("_HLS_part", &self.next.1.to_string());
+                }
+            }
+
+            if let Ok(text) = self.fetch_playlist(url) {
+                return Ok(parse_live_playlist(&text));
+            }
+        }
+
+        let wait = playlist.part_target.unwrap_or(playlist.target_duration);
+        thread::sleep(Duration::from_secs_f32(wait.max(0.2)));
+        Ok(parse_live_playlist(&self.fetch_playlist(self.uri.clone())?))
+    }
+
+    fn fetch_playlist(&self, url: Url) -> Result<String> {
+        let response = self.client.get(url.clone()).query(self.query).send()?;
+        let status = response.status();
+
+        if !status.is_success() {
+            bail!("failed to fetch live playlist {} (HTTP {}).", url, status);
+        }
+
+        Ok(response.text()?)
+    }
+
+    fn fetch(&self, part: &LivePart, retries: u8) -> Result<Vec<u8>> {
+        let url = self.base_url.join(&part.uri)?;
+
+        for _ in 0..retries {
+            let mut request = self.client.get(url.clone()).query(self.query);
+
+            if let Some(range) = &part.range {
+                request = request.header(header::RANGE, range.as_header_value());
+            }
+
+            if let Ok(response) = request.send()
+                && response.status().is_success()
+            {
+                return Ok(response.bytes()?.to_vec());
+            }
+
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        bail!("reached max retries to download {}.", url);
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes)?;
+        self.stored += bytes.len();
+        Ok(())
+    }
+}
@@ -1,23 +1,37 @@
+mod captions;
 mod encryption;
 mod fetch;
+mod live;
 mod mux;
 mod parse;
+mod sidecar;
 mod stream;
 mod subtitle;
 
-pub use encryption::Decrypter;
+pub use encryption::{Decrypter, list_drm};
 pub use fetch::fetch_playlist;
 pub use parse::{list_all_streams, parse_all_streams, parse_selected_streams};
-pub use subtitle::download_subtitle_streams;
+pub use sidecar::SidecarSegments;
+pub use subtitle::{
+    ImageSubtitleFormat, SubtitleFormat, SubtitleTiming, SubtitleType, download_subtitle_streams,
+};
 
 use crate::{
-    playlist::{MediaPlaylist, MediaType},
+    output::OutputTemplate,
+    playlist::{MediaPlaylist, MediaType, PlaylistType},
     utils,
 };
 use anyhow::{Result, bail};
-use kdam::{Column, RichProgress, tqdm};
+use chrono::{DateTime, FixedOffset};
+use kdam::{Column, RichProgress, term::Colorizer, tqdm};
+use rayon::ThreadPoolBuilder;
 use reqwest::{Url, blocking::Client};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 #[allow(clippy::too_many_arguments)]
 pub fn download(
@@ -25,23 +39,155 @@ pub fn download(
     client: Client,
     decrypter: Decrypter,
     directory: Option<PathBuf>,
+    end: Option<DateTime<FixedOffset>>,
+    export_chapters: Option<PathBuf>,
+    image_subs_format: ImageSubtitleFormat,
+    live: bool,
     no_decrypt: bool,
     no_merge: bool,
-    output: Option<PathBuf>,
+    mut output: Option<PathBuf>,
     query: HashMap<String, String>,
     mut streams: Vec<MediaPlaylist>,
+    skip_ads: bool,
+    split_discontinuities: bool,
+    start: Option<DateTime<FixedOffset>>,
     subs_codec: String,
+    subs_format: Option<SubtitleFormat>,
+    subs_timing: SubtitleTiming,
     retries: u8,
     threads: u8,
 ) -> Result<()> {
+    let mut output_template = None;
+
+    if let Some(template) = output.as_ref().map(|x| x.to_string_lossy().to_string())
+        && template.contains('{')
+    {
+        let template = OutputTemplate::new(&template)?;
+
+        if template.is_per_stream() {
+            output = None;
+            output_template = Some(template);
+        } else if let Some(stream) = streams.first() {
+            output = Some(template.render(stream, ""));
+        }
+    }
+
+    // Live streams are recorded separately, they are split from others only after
+    // checks which apply to all selected streams.
+    let recordable = |x: &MediaPlaylist| {
+        live && x.live
+            && matches!(x.playlist_type, PlaylistType::Hls)
+            && x.media_type != MediaType::Subtitles
+    };
+
+    // Subtitle cues are moved back by skipped duration, while audio and video streams
+    // depend on muxing for it.
+    let mut skipped_ads = false;
+
+    if skip_ads {
+        for stream in streams.iter_mut().filter(|x| !recordable(x)) {
+            let skipped = stream.skip_ads();
+
+            if skipped > 0 {
+                skipped_ads |= stream.media_type != MediaType::Subtitles;
+                println!(
+                    "       {} skipped {} ad segments of {} stream ({})",
+                    "Note".colorize("cyan"),
+                    skipped,
+                    stream.media_type,
+                    stream.id,
+                );
+            }
+        }
+    }
+
+    if start.is_some() || end.is_some() {
+        for stream in streams.iter_mut().filter(|x| !recordable(x)) {
+            if !stream.trim_to_wall_clock(start.as_ref(), end.as_ref()) {
+                println!(
+                    "    {} --start and --end are ignored for {} stream ({}) as it doesn't have program date time",
+                    "Warning".colorize("yellow"),
+                    stream.media_type,
+                    stream.id,
+                );
+            }
+        }
+    }
+
+    if split_discontinuities {
+        streams = streams
+            .into_iter()
+            .flat_map(|x| {
+                if x.media_type == MediaType::Subtitles || recordable(&x) {
+                    vec![x]
+                } else {
+                    x.split_discontinuities()
+                }
+            })
+            .collect();
+    }
+
+    // Chapters are taken from video stream, or from any other stream if video stream doesn't have them.
+    let chapters = streams
+        .iter()
+        .filter(|x| !x.chapters.is_empty())
+        .max_by_key(|x| x.media_type == MediaType::Video)
+        .map(mux::chapters)
+        .unwrap_or_default();
+
+    if let Some(path) = &export_chapters {
+        if chapters.is_empty() {
+            println!(
+                "    {} --export-chapters is ignored as selected streams don't have any chapters",
+                "Warning".colorize("yellow")
+            );
+        } else {
+            serde_json::to_writer_pretty(fs::File::create(path)?, &chapters)?;
+            println!(
+                "  {} {} chapters to {}",
+                "Exporting".colorize("bold green"),
+                chapters.len(),
+                path.to_string_lossy()
+            );
+        }
+    }
+
     let should_mux = mux::should_mux(no_decrypt, no_merge, output.as_ref(), &streams);
 
+    if skipped_ads && !should_mux && !split_discontinuities {
+        println!(
+            "    {} streams aren't muxed, so audio and video streams keep a timestamp gap where ads were skipped (use --output or --split-discontinuities to avoid it)",
+            "Warning".colorize("yellow"),
+        );
+    }
+
     if should_mux && utils::find_ffmpeg().is_none() {
         bail!("ffmpeg couldn't be found, it is required to continue further.");
     }
 
     if !no_decrypt {
         encryption::check_unsupported_encryptions(&streams)?;
+    }
+
+    let mut live_streams = vec![];
+
+    if live {
+        let (recordable, others) = streams.into_iter().partition::<Vec<_>, _>(recordable);
+
+        for stream in others.iter().filter(|x| x.live) {
+            println!(
+                "    {} live {} stream ({}) can't be recorded, only available segments will be downloaded",
+                "Warning".colorize("yellow"),
+                stream.media_type,
+                stream.id,
+            );
+        }
+
+        live_streams = recordable;
+        streams = others;
+    }
+
+    if !no_decrypt {
         let default_kids = encryption::extract_default_kids(&base_url, &client, &streams, &query)?;
         encryption::check_key_exists_for_kid(&decrypter, &default_kids)?;
     }
@@ -57,7 +203,7 @@ pub fn download(
         }
     }
 
-    let mut pb = RichProgress::new(
+    let pb = RichProgress::new(
         tqdm!(
             dynamic_ncols = true,
             total = streams.iter().map(|x| x.segments.len()).sum(),
@@ -77,15 +223,26 @@ pub fn download(
             Column::Rate,
         ],
     );
+    let pb = Arc::new(Mutex::new(pb));
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads as usize)
+        .build()
+        .unwrap();
     let mut temp_files = vec![];
 
     download_subtitle_streams(
         &base_url,
         &client,
         directory.as_ref(),
+        &image_subs_format,
+        output_template.as_ref(),
         &streams,
-        &mut pb,
+        &pb,
+        &pool,
         &query,
+        retries,
+        subs_format.as_ref(),
+        &subs_timing,
         &mut temp_files,
     )?;
 
@@ -96,17 +253,54 @@ pub fn download(
         directory.as_ref(),
         no_decrypt,
         no_merge,
+        output_template.as_ref(),
         pb,
+        &pool,
         &query,
         retries,
         streams,
-        threads,
+        subs_format.as_ref(),
+        &subs_timing,
         &mut temp_files,
     )?;
 
+    if !live_streams.is_empty() {
+        live::record_streams(
+            &base_url,
+            &client,
+            directory.as_ref(),
+            no_decrypt,
+            output_template.as_ref(),
+            &query,
+            retries,
+            live_streams,
+            &mut temp_files,
+        )?;
+    }
+
     if should_mux {
-        mux::ffmpeg(output.as_ref(), &subs_codec, &temp_files)?;
-        mux::delete_temp_files(directory.as_ref(), &temp_files)?;
+        mux::retain_muxable(output.as_ref(), &mut temp_files);
+        mux::ffmpeg(&chapters, output.as_ref(), &subs_codec, &temp_files)?;
+
+        // Encrypted streams are kept along with their sidecar files.
+        let (encrypted, others) = temp_files
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| x.sidecar.is_some());
+
+        if !encrypted.is_empty() {
+            for temp_file in &encrypted {
+                if let (Some(sidecar), Some(output)) = (&temp_file.sidecar, &output) {
+                    sidecar::set_muxed(sidecar, output)?;
+                }
+            }
+
+            println!(
+                "       {} encrypted streams are kept so that they can be decrypted later",
+                "Note".colorize("cyan")
+            );
+        }
+
+        mux::delete_temp_files(directory.as_ref(), &others)?;
     }
 
     Ok(())
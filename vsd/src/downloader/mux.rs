@@ -1,9 +1,10 @@
 use crate::{
-    playlist::{MediaPlaylist, MediaType},
+    playlist::{KeyMethod, MediaPlaylist, MediaType},
     utils,
 };
 use anyhow::{Result, bail};
 use kdam::term::Colorizer;
+use serde::Serialize;
 use std::{
     ffi::OsStr,
     fs,
@@ -12,9 +13,72 @@ use std::{
 };
 
 pub struct Stream {
+    /// Stream contains discontinuities or skipped ad breaks and timestamps are reset or jump in between.
+    pub discontinuous: bool,
     pub language: Option<String>,
     pub media_type: MediaType,
     pub path: PathBuf,
+    /// Sidecar file containing encryption info, written when stream isn't decrypted.
+    pub sidecar: Option<PathBuf>,
+}
+
+/// Chapter with its start and end time in seconds.
+#[derive(Serialize)]
+pub struct ChapterTime {
+    pub end: f64,
+    pub start: f64,
+    pub title: String,
+}
+
+pub fn chapters(stream: &MediaPlaylist) -> Vec<ChapterTime> {
+    let mut elapsed = vec![0.0];
+
+    for segment in &stream.segments {
+        elapsed.push(elapsed.last().unwrap() + segment.duration as f64);
+    }
+
+    stream
+        .chapters
+        .iter()
+        .enumerate()
+        .map(|(i, x)| ChapterTime {
+            end: stream
+                .chapters
+                .get(i + 1)
+                .map(|y| elapsed[y.start])
+                .unwrap_or(*elapsed.last().unwrap()),
+            start: elapsed[x.start],
+            title: x.title.to_owned(),
+        })
+        .collect()
+}
+
+/// https://ffmpeg.org/ffmpeg-formats.html#Metadata-2
+fn write_ffmetadata(path: &PathBuf, chapters: &[ChapterTime]) -> Result<()> {
+    let escape = |x: &str| {
+        x.chars()
+            .fold(String::new(), |mut escaped, c| {
+                if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                    escaped.push('\\');
+                }
+
+                escaped.push(c);
+                escaped
+            })
+    };
+    let mut metadata = ";FFMETADATA1\n".to_owned();
+
+    for chapter in chapters {
+        metadata += &format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (chapter.end * 1000.0).round() as u64,
+            escape(&chapter.title),
+        );
+    }
+
+    fs::write(path, metadata)?;
+    Ok(())
 }
 
 pub fn delete_temp_files(directory: Option<&PathBuf>, temp_files: &[Stream]) -> Result<()> {
@@ -41,7 +105,12 @@ pub fn delete_temp_files(directory: Option<&PathBuf>, temp_files: &[Stream]) ->
     Ok(())
 }
 
-pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream]) -> Result<()> {
+pub fn ffmpeg(
+    chapters: &[ChapterTime],
+    output: Option<&PathBuf>,
+    subs_codec: &str,
+    temp_files: &[Stream],
+) -> Result<()> {
     let output = output.unwrap();
 
     let sub_streams_present = temp_files
@@ -73,10 +142,28 @@ pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream])
     let mut args = Vec::new();
 
     for temp_file in &temp_files {
+        // Timestamp resets and jumps (longer than -dts_delta_threshold i.e. 10 seconds) in mpeg-ts
+        // inputs are corrected by ffmpeg itself, whereas fragmented mp4 inputs should ignore
+        // tfdt boxes and derive timestamps from sample durations.
+        if temp_file.discontinuous && temp_file.path.extension() == Some(OsStr::new("m4s")) {
+            args.extend_from_slice(&["-use_tfdt".to_owned(), "0".to_owned()]);
+        }
+
         args.extend_from_slice(&["-i".to_owned(), temp_file.path.to_string_lossy().into()]);
     }
 
-    if args.len() == 2 {
+    let metadata_file = output.with_extension("ffmetadata");
+
+    if !chapters.is_empty() {
+        args.extend_from_slice(&[
+            "-i".to_owned(),
+            metadata_file.to_string_lossy().into(),
+            "-map_chapters".to_owned(),
+            temp_files.len().to_string(),
+        ]);
+    }
+
+    if temp_files.len() == 1 {
         // Working on single stream
         args.extend_from_slice(&[
             "-c:v".to_owned(),
@@ -143,6 +230,13 @@ pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream])
 
     args.push(output.to_string_lossy().into());
 
+    if let Some(parent) = output.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
+    }
+
     if output.exists() {
         println!(
             "   {} {}",
@@ -152,6 +246,10 @@ pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream])
         fs::remove_file(output)?;
     }
 
+    if !chapters.is_empty() {
+        write_ffmetadata(&metadata_file, chapters)?;
+    }
+
     println!(
         "  {} ffmpeg {}",
         "Executing".colorize("cyan"),
@@ -168,8 +266,15 @@ pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream])
     let code = Command::new(utils::find_ffmpeg().unwrap())
         .args(args)
         .stderr(Stdio::null())
-        .spawn()?
-        .wait()?;
+        .spawn()
+        .and_then(|mut x| x.wait());
+
+    // Metadata file is removed even if ffmpeg couldn't be spawned.
+    if !chapters.is_empty() {
+        fs::remove_file(&metadata_file)?;
+    }
+
+    let code = code?;
 
     if !code.success() {
         bail!("ffmpeg exited with code {}", code.code().unwrap_or(1));
@@ -178,6 +283,31 @@ pub fn ffmpeg(output: Option<&PathBuf>, subs_codec: &str, temp_files: &[Stream])
     Ok(())
 }
 
+/// Pgs (sup) subtitles can only be muxed into matroska, otherwise they are removed from
+/// temp files so that they are kept alongside output instead of being deleted.
+pub fn retain_muxable(output: Option<&PathBuf>, temp_files: &mut Vec<Stream>) {
+    let is_matroska = output
+        .and_then(|x| x.extension())
+        .is_some_and(|x| x == "mkv" || x == "mks");
+
+    if is_matroska {
+        return;
+    }
+
+    temp_files.retain(|x| {
+        if x.path.extension() != Some(OsStr::new("sup")) {
+            return true;
+        }
+
+        println!(
+            "    {} {} isn't muxed (pgs subtitles can only be muxed into mkv)",
+            "Warning".colorize("yellow"),
+            x.path.to_string_lossy(),
+        );
+        false
+    });
+}
+
 pub fn should_mux(
     no_decrypt: bool,
     no_merge: bool,
@@ -188,9 +318,16 @@ pub fn should_mux(
         return false;
     }
 
-    if no_decrypt {
+    // Whole segments are encrypted by aes-128, so ffmpeg can't even parse them.
+    if no_decrypt
+        && streams.iter().any(|x| {
+            x.segments
+                .iter()
+                .any(|y| matches!(&y.key, Some(z) if z.method == KeyMethod::Aes128))
+        })
+    {
         println!(
-            "    {} --output is ignored when --no-decrypt is used",
+            "    {} --output is ignored when --no-decrypt is used with aes-128 encrypted streams",
             "Warning".colorize("yellow")
         );
         return false;
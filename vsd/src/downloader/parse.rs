@@ -1,27 +1,27 @@
 use super::fetch::Metadata;
 use crate::{
-    automation::{Prompter, SelectOptions},
-    playlist::{MasterPlaylist, MediaPlaylist, PlaylistType},
+    automation::{Prompter, RangePreference, SelectOptions},
+    playlist::{MasterPlaylist, MediaPlaylist, PlaylistType, Range},
     utils,
 };
 use anyhow::{Result, anyhow, bail};
 use kdam::term::Colorizer;
-use reqwest::{Url, blocking::Client};
+use reqwest::{Url, blocking::Client, header};
 use std::collections::HashMap;
 
-pub fn list_all_streams(meta: &Metadata) -> Result<()> {
+pub fn list_all_streams(meta: &Metadata, range_preference: &RangePreference) -> Result<()> {
     match meta.pl_type {
         Some(PlaylistType::Dash) => {
             let mpd = dash_mpd::parse(&meta.text)
                 .map_err(|_| anyhow!("couldn't parse response ({}) as dash playlist.", meta.url))?;
             crate::dash::parse_as_master(&mpd, meta.url.as_ref())
-                .sort_streams()
+                .sort_streams(range_preference)
                 .list_streams();
         }
         Some(PlaylistType::Hls) => match m3u8_rs::parse_playlist_res(meta.text.as_bytes()) {
             Ok(m3u8_rs::Playlist::MasterPlaylist(m3u8)) => {
                 crate::hls::parse_as_master(&m3u8, meta.url.as_ref())
-                    .sort_streams()
+                    .sort_streams(range_preference)
                     .list_streams()
             }
 
@@ -47,14 +47,16 @@ pub fn parse_all_streams(
             let mpd = dash_mpd::parse(&meta.text)
                 .map_err(|_| anyhow!("couldn't parse response ({}) as dash playlist.", meta.url))?;
             let mut playlist = crate::dash::parse_as_master(&mpd, meta.url.as_ref());
+            playlist.steering =
+                crate::dash::parse_content_steering(&mpd, &meta.text, meta.url.as_ref());
+            crate::steering::apply(&mut playlist, client, query)?;
 
             for stream in playlist.streams.iter_mut() {
                 crate::dash::push_segments(
                     &mpd,
                     stream,
                     base_url.as_ref().unwrap_or(&meta.url).as_str(),
-                    client,
-                    query,
+                    &|url, range| fetch_range(client, query, url, range),
                 )?;
                 stream.id = blake3::hash((meta.url.as_ref().to_owned() + &stream.uri).as_bytes())
                     .to_hex()[..7]
@@ -67,13 +69,25 @@ pub fn parse_all_streams(
         Some(PlaylistType::Hls) => match m3u8_rs::parse_playlist_res(meta.text.as_bytes()) {
             Ok(m3u8_rs::Playlist::MasterPlaylist(m3u8)) => {
                 let mut playlist = crate::hls::parse_as_master(&m3u8, meta.url.as_ref());
+                crate::steering::apply(&mut playlist, client, query)?;
 
                 for stream in playlist.streams.iter_mut() {
-                    stream.uri = base_url
-                        .as_ref()
-                        .unwrap_or(&meta.url)
-                        .join(&stream.uri)?
-                        .to_string();
+                    // Closed captions are carried inside video stream, so there is no playlist to fetch.
+                    if let Some(instream_id) = &stream.instream_id {
+                        stream.id =
+                            blake3::hash((meta.url.as_ref().to_owned() + instream_id).as_bytes())
+                                .to_hex()[..7]
+                                .to_owned();
+                        continue;
+                    }
+
+                    let base_url = base_url.as_ref().unwrap_or(&meta.url);
+                    stream.uri = base_url.join(&stream.uri)?.to_string();
+
+                    for uri in stream.pathway_uris.values_mut() {
+                        *uri = base_url.join(uri)?.to_string();
+                    }
+
                     stream.id = blake3::hash(stream.uri.as_bytes()).to_hex()[..7].to_owned();
 
                     let text;
@@ -100,12 +114,14 @@ pub fn parse_all_streams(
             Ok(m3u8_rs::Playlist::MediaPlaylist(m3u8)) => {
                 let mut media_playlist = crate::playlist::MediaPlaylist {
                     id: blake3::hash(meta.url.as_ref().as_bytes()).to_hex()[..7].to_owned(),
+                    title: crate::playlist::title_from_uri(meta.url.as_ref()),
                     uri: meta.url.as_ref().to_owned(),
                     ..Default::default()
                 };
                 crate::hls::push_segments(&m3u8, &mut media_playlist);
                 Ok(MasterPlaylist {
                     playlist_type: PlaylistType::Hls,
+                    steering: None,
                     streams: vec![media_playlist],
                     uri: meta.url.as_ref().to_owned(),
                 })
@@ -127,14 +143,19 @@ pub fn parse_selected_streams(
     meta: &Metadata,
     prompter: &Prompter,
     query: &HashMap<String, String>,
+    range_preference: &RangePreference,
     mut select_opts: SelectOptions,
 ) -> Result<Vec<MediaPlaylist>> {
     match meta.pl_type {
         Some(PlaylistType::Dash) => {
             let mpd = dash_mpd::parse(&meta.text)
                 .map_err(|_| anyhow!("couldn't parse response ({}) as dash playlist.", meta.url))?;
-            let mut streams = crate::dash::parse_as_master(&mpd, meta.url.as_ref())
-                .sort_streams()
+            let mut playlist = crate::dash::parse_as_master(&mpd, meta.url.as_ref());
+            playlist.steering =
+                crate::dash::parse_content_steering(&mpd, &meta.text, meta.url.as_ref());
+            crate::steering::apply(&mut playlist, client, query)?;
+            let mut streams = playlist
+                .sort_streams(range_preference)
                 .select_streams(prompter, &mut select_opts)?;
 
             for stream in &mut streams {
@@ -142,8 +163,7 @@ pub fn parse_selected_streams(
                     &mpd,
                     stream,
                     base_url.as_ref().unwrap_or(&meta.url).as_str(),
-                    client,
-                    query,
+                    &|url, range| fetch_range(client, query, url, range),
                 )?;
                 stream.id = blake3::hash((meta.url.as_ref().to_owned() + &stream.uri).as_bytes())
                     .to_hex()[..7]
@@ -155,16 +175,29 @@ pub fn parse_selected_streams(
         }
         Some(PlaylistType::Hls) => match m3u8_rs::parse_playlist_res(meta.text.as_bytes()) {
             Ok(m3u8_rs::Playlist::MasterPlaylist(m3u8)) => {
-                let mut streams = crate::hls::parse_as_master(&m3u8, meta.url.as_str())
-                    .sort_streams()
+                let mut playlist = crate::hls::parse_as_master(&m3u8, meta.url.as_str());
+                crate::steering::apply(&mut playlist, client, query)?;
+                let mut streams = playlist
+                    .sort_streams(range_preference)
                     .select_streams(prompter, &mut select_opts)?;
 
                 for stream in &mut streams {
-                    stream.uri = base_url
-                        .as_ref()
-                        .unwrap_or(&meta.url)
-                        .join(&stream.uri)?
-                        .to_string();
+                    // Closed captions are carried inside video stream, so there is no playlist to fetch.
+                    if let Some(instream_id) = &stream.instream_id {
+                        stream.id =
+                            blake3::hash((meta.url.as_ref().to_owned() + instream_id).as_bytes())
+                                .to_hex()[..7]
+                                .to_owned();
+                        continue;
+                    }
+
+                    let base_url = base_url.as_ref().unwrap_or(&meta.url);
+                    stream.uri = base_url.join(&stream.uri)?.to_string();
+
+                    for uri in stream.pathway_uris.values_mut() {
+                        *uri = base_url.join(uri)?.to_string();
+                    }
+
                     stream.id = blake3::hash(stream.uri.as_bytes()).to_hex()[..7].to_owned();
 
                     let text;
@@ -196,6 +229,7 @@ pub fn parse_selected_streams(
             Ok(m3u8_rs::Playlist::MediaPlaylist(m3u8)) => {
                 let mut media_playlist = MediaPlaylist {
                     id: blake3::hash(meta.url.as_ref().as_bytes()).to_hex()[..7].to_owned(),
+                    title: crate::playlist::title_from_uri(meta.url.as_ref()),
                     uri: meta.url.as_ref().to_owned(),
                     ..Default::default()
                 };
@@ -207,3 +241,17 @@ pub fn parse_selected_streams(
         _ => bail!("couldn't determine playlist type, only DASH and HLS playlists are supported."),
     }
 }
+
+fn fetch_range(
+    client: &Client,
+    query: &HashMap<String, String>,
+    url: &Url,
+    range: &Range,
+) -> Result<Vec<u8>> {
+    let response = client
+        .get(url.as_str())
+        .query(query)
+        .header(header::RANGE, range.as_header_value())
+        .send()?;
+    Ok(response.bytes()?.to_vec())
+}
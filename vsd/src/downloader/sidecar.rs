@@ -0,0 +1,194 @@
+use crate::playlist::{KeyMethod, MediaPlaylist, MediaType};
+use anyhow::{Result, anyhow};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use vsd_mp4::pssh::Pssh;
+
+/// Encryption info of a stream downloaded without decrypting it,
+/// which is saved alongside the stream so that it can be decrypted later.
+#[derive(Serialize)]
+pub struct Sidecar {
+    /// In hex.
+    default_kid: Option<String>,
+    /// Path of init segment saved separately.
+    init: Option<PathBuf>,
+    /// Keys and range of segment indexes (inclusive) encrypted with them.
+    keys: Vec<SidecarKey>,
+    /// Key ids found in playlist and init segment, in hex.
+    kids: Vec<String>,
+    /// Used as iv by hls aes-128 and sample-aes keys without iv.
+    media_sequence: u64,
+    media_type: MediaType,
+    /// Path of file in which this stream is muxed.
+    muxed: Option<PathBuf>,
+    /// Path of downloaded (still encrypted) stream.
+    path: PathBuf,
+    /// Base64 encoded `pssh` boxes found in playlist and init segment.
+    pssh: Vec<String>,
+    /// Byte size of every segment, used to decrypt segments of merged
+    /// hls aes-128 and sample-aes streams separately.
+    segments: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct SidecarKey {
+    end: usize,
+    iv: Option<String>,
+    key_format: Option<String>,
+    method: KeyMethod,
+    start: usize,
+    uri: Option<String>,
+}
+
+impl Sidecar {
+    pub fn new(
+        base_url: &Url,
+        init: Option<&[u8]>,
+        path: &Path,
+        segments: &[usize],
+        stream: &MediaPlaylist,
+    ) -> Result<Self> {
+        let mut keys: Vec<SidecarKey> = vec![];
+        let mut kids = vec![];
+        let mut pssh = vec![];
+
+        for (i, segment) in stream.segments.iter().enumerate() {
+            // Key is only present on segment from where it starts being used.
+            let Some(key) = &segment.key else {
+                if let Some(last) = keys.last_mut() {
+                    last.end = i;
+                }
+                continue;
+            };
+
+            let uri = match &key.uri {
+                Some(x) if !x.starts_with("data:") => Some(base_url.join(x)?.to_string()),
+                x => x.clone(),
+            };
+
+            match keys.last_mut() {
+                Some(last) if last.iv == key.iv && last.method == key.method && last.uri == uri => {
+                    last.end = i;
+                }
+                _ => keys.push(SidecarKey {
+                    end: i,
+                    iv: key.iv.clone(),
+                    key_format: key.key_format.clone(),
+                    method: key.method.clone(),
+                    start: i,
+                    uri,
+                }),
+            }
+
+            for pssh_box in key.pssh_boxes() {
+                kids.extend(
+                    pssh_box
+                        .all_key_ids()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|x| x.value),
+                );
+            }
+
+            pssh.extend(key.pssh.iter().cloned());
+        }
+
+        kids.extend(stream.default_kid());
+
+        let init_path = if let Some(init) = init {
+            kids.extend(vsd_mp4::pssh::encryption_kids(init).map_err(|x| anyhow!(x))?);
+
+            if let Ok(init_pssh) = Pssh::new(init) {
+                kids.extend(init_pssh.key_ids.into_iter().map(|x| x.value));
+
+                for pssh_box in init_pssh.boxes {
+                    pssh.push(pssh_box.to_base64().map_err(|x| anyhow!(x))?);
+                }
+            }
+
+            let init_path = path.with_extension("init.mp4");
+            fs::write(&init_path, init)?;
+            Some(init_path)
+        } else {
+            None
+        };
+
+        let mut unique_kids = vec![];
+
+        for kid in kids {
+            if !unique_kids.contains(&kid) {
+                unique_kids.push(kid);
+            }
+        }
+
+        let mut unique_pssh = vec![];
+
+        for pssh in pssh {
+            if !unique_pssh.contains(&pssh) {
+                unique_pssh.push(pssh);
+            }
+        }
+
+        Ok(Self {
+            default_kid: stream.default_kid(),
+            init: init_path,
+            keys,
+            kids: unique_kids,
+            media_sequence: stream.media_sequence,
+            media_type: stream.media_type.clone(),
+            muxed: None,
+            path: path.to_owned(),
+            pssh: unique_pssh,
+            segments: segments.to_vec(),
+        })
+    }
+
+    /// Write as `<stream path>.drm.json` file.
+    pub fn write(&self) -> Result<PathBuf> {
+        let path = sidecar_path(&self.path);
+        serde_json::to_writer_pretty(fs::File::create(&path)?, self)?;
+        Ok(path)
+    }
+}
+
+/// Path of muxed output file is added to already written sidecar file.
+pub fn set_muxed(sidecar: &Path, output: &Path) -> Result<()> {
+    let mut value = serde_json::from_slice::<serde_json::Value>(&fs::read(sidecar)?)?;
+    value["muxed"] = serde_json::Value::String(output.to_string_lossy().to_string());
+    serde_json::to_writer_pretty(fs::File::create(sidecar)?, &value)?;
+    Ok(())
+}
+
+/// Part of sidecar file read back while decrypting its stream.
+#[derive(Deserialize)]
+pub struct SidecarSegments {
+    pub media_sequence: u64,
+    #[serde(default)]
+    pub segments: Vec<usize>,
+}
+
+impl SidecarSegments {
+    /// Read from sidecar file of a stream, if it has one.
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let sidecar = sidecar_path(path);
+
+        if !sidecar.is_file() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_slice(&fs::read(sidecar)?)?))
+    }
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    // Trailing separator of directories is removed.
+    let path = path.components().as_path();
+
+    let mut path = path.as_os_str().to_owned();
+    path.push(".drm.json");
+    PathBuf::from(path)
+}
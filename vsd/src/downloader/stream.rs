@@ -1,15 +1,20 @@
 use crate::{
-    downloader::{encryption::Decrypter, mux::Stream},
+    downloader::{
+        SubtitleFormat, SubtitleTiming, captions, encryption::Decrypter, mux::Stream,
+        sidecar::Sidecar,
+    },
     merger::Merger,
+    output::OutputTemplate,
     playlist::{KeyMethod, MediaPlaylist, MediaType},
+    steering::Steering,
     utils,
 };
 use anyhow::{Result, bail};
 use kdam::{BarExt, Column, RichProgress, term::Colorizer};
-use rayon::{ThreadPool, ThreadPoolBuilder};
+use rayon::ThreadPool;
 use reqwest::{
     StatusCode, Url,
-    blocking::{Client, RequestBuilder},
+    blocking::{Client, RequestBuilder, Response},
     header,
 };
 use std::{
@@ -27,13 +32,21 @@ pub fn download_streams(
     directory: Option<&PathBuf>,
     no_decrypt: bool,
     no_merge: bool,
-    mut pb: RichProgress,
+    output_template: Option<&OutputTemplate>,
+    pb: Arc<Mutex<RichProgress>>,
+    pool: &ThreadPool,
     query: &HashMap<String, String>,
     retries: u8,
     streams: Vec<MediaPlaylist>,
-    threads: u8,
+    subs_format: Option<&SubtitleFormat>,
+    subs_timing: &SubtitleTiming,
     temp_files: &mut Vec<Stream>,
 ) -> Result<()> {
+    let mut captions = streams
+        .iter()
+        .filter(|x| x.instream_id.is_some())
+        .cloned()
+        .collect::<Vec<_>>();
     let mut streams = streams
         .into_iter()
         .filter(|x| x.media_type != MediaType::Subtitles)
@@ -46,15 +59,10 @@ pub fn download_streams(
         estimated_bytes.push_back(stream.estimate_size(base_url, client, query)?);
     }
 
-    pb.columns.extend_from_slice(&[
+    pb.lock().unwrap().columns.extend_from_slice(&[
         Column::Text("•".to_owned()),
         Column::Text("[yellow]?".to_owned()), // download speed
     ]);
-    let pb = Arc::new(Mutex::new(pb));
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(threads as usize)
-        .build()
-        .unwrap();
 
     for stream in streams {
         pb.lock().unwrap().write(format!(
@@ -72,12 +80,23 @@ pub fn download_streams(
             continue;
         }
 
-        let temp_file = stream.path(directory, stream.extension());
+        // Closed captions are extracted from first downloaded video stream of their group.
+        let mut stream_captions = vec![];
+
+        if stream.media_type == MediaType::Video {
+            (stream_captions, captions) = captions.into_iter().partition(|x| {
+                stream.closed_captions_group.is_none() || x.group_id == stream.closed_captions_group
+            });
+        }
+
+        let temp_file = stream.path(directory, stream.extension(), output_template)?;
 
         temp_files.push(Stream {
+            discontinuous: stream.has_discontinuities(),
             language: stream.language.clone(),
             media_type: stream.media_type.clone(),
             path: temp_file.clone(),
+            sidecar: None,
         });
 
         let _ = estimated_bytes.pop_front();
@@ -87,7 +106,7 @@ pub fn download_streams(
             "Downloading".colorize("bold green"),
             temp_file.to_string_lossy(),
         ))?;
-        download_stream(
+        let sidecar = download_stream(
             base_url,
             client,
             &mut downloaded_bytes,
@@ -96,12 +115,37 @@ pub fn download_streams(
             no_decrypt,
             no_merge,
             pb.clone(),
-            &pool,
+            pool,
             query,
             retries,
             stream,
             &temp_file,
         )?;
+
+        if let Some(temp_file) = temp_files.last_mut() {
+            temp_file.sidecar = sidecar;
+        }
+
+        if !stream_captions.is_empty() {
+            captions::extract_captions(
+                &stream_captions,
+                directory,
+                output_template,
+                &pb,
+                subs_format,
+                subs_timing,
+                temp_files,
+                &temp_file,
+            )?;
+        }
+    }
+
+    for stream in captions {
+        pb.lock().unwrap().write(format!(
+            "    {} skipping stream {} (video stream carrying its closed captions isn't downloaded)",
+            "Warning".colorize("yellow"),
+            stream.id,
+        ))?;
     }
 
     eprintln!();
@@ -123,8 +167,9 @@ fn download_stream(
     retries: u8,
     stream: MediaPlaylist,
     temp_file: &PathBuf,
-) -> Result<()> {
+) -> Result<Option<PathBuf>> {
     let mut init_seg = None;
+    let mut first_init_seg = None;
     let merger = Arc::new(Mutex::new(if no_merge {
         Merger::new_directory(stream.segments.len(), temp_file)?
     } else {
@@ -138,12 +183,27 @@ fn download_stream(
     let timer = Arc::new(Instant::now());
 
     let mut default_kid = None;
+    let mut init_kids = vec![];
     let mut widevine_kid = None;
     let mut stream_decrypter = decrypter.clone();
+    let steering = Steering::new(client, query, &stream).map(|x| Arc::new(Mutex::new(x)));
+
+    if let Some(steering) = &steering {
+        pb.lock().unwrap().write(format!(
+            "    {} {} (content steering)",
+            "Pathway".colorize("bold cyan"),
+            steering.lock().unwrap().pathway(),
+        ))?;
+    }
+
+    let rebase = |url: Url| match &steering {
+        Some(steering) => Steering::rebase(steering, &url),
+        None => url,
+    };
 
     for (i, segment) in stream.segments.iter().enumerate() {
         if let Some(map) = &segment.map {
-            let url = base_url.join(&map.uri)?;
+            let url = rebase(base_url.join(&map.uri)?);
             let mut request = client.get(url).query(query);
 
             if let Some(range) = &map.range {
@@ -154,6 +214,13 @@ fn download_stream(
             let bytes = response.bytes()?;
 
             default_kid = vsd_mp4::pssh::default_kid(&bytes)?.or(stream.default_kid());
+            init_kids = vsd_mp4::pssh::encryption_kids(&bytes)?;
+
+            if let Some(default_kid) = &default_kid
+                && !init_kids.contains(default_kid)
+            {
+                init_kids.push(default_kid.to_owned());
+            }
             widevine_kid = vsd_mp4::pssh::Pssh::new(&bytes)?
                 .key_ids
                 .into_iter()
@@ -162,7 +229,11 @@ fn download_stream(
                     _ => None,
                 });
 
-            init_seg = Some(bytes.to_vec())
+            init_seg = Some(bytes.to_vec());
+
+            if first_init_seg.is_none() {
+                first_init_seg = init_seg.clone();
+            }
         }
 
         if !no_decrypt {
@@ -173,7 +244,7 @@ fn download_stream(
             if let Some(key) = &segment.key {
                 match key.method {
                     KeyMethod::Aes128 | KeyMethod::SampleAes => {
-                        let url = base_url.join(key.uri.as_ref().unwrap())?;
+                        let url = rebase(base_url.join(key.uri.as_ref().unwrap())?);
                         let request = client.get(url).query(query);
                         let response = request.send()?;
                         let bytes = response.bytes()?;
@@ -190,32 +261,37 @@ fn download_stream(
                     }
                     KeyMethod::Mp4Decrypt => {
                         if let Decrypter::Mp4Decrypt(kid_key_pairs) = &decrypter {
-                            // We already checked this before hand so unwraping is safe.
-                            if let Some(default_kid) = &default_kid {
-                                let key = if default_kid == "00000000000000000000000000000000" {
-                                    if widevine_kid.is_none() {
-                                        bail!(
-                                            "couldn't determine which widevine key to be mapped for this stream's zero kid."
-                                        );
-                                    }
-
-                                    kid_key_pairs.get(widevine_kid.as_ref().unwrap()).unwrap()
-                                } else {
-                                    kid_key_pairs.get(default_kid).unwrap()
+                            // Every key is passed on, only keys of kids used by a segment
+                            // are used while decrypting it.
+                            let mut keys = kid_key_pairs.clone();
+
+                            if default_kid.as_deref() == Some("00000000000000000000000000000000") {
+                                let Some(key) =
+                                    widevine_kid.as_ref().and_then(|x| kid_key_pairs.get(x))
+                                else {
+                                    bail!(
+                                        "couldn't determine which widevine key to be mapped for this stream's zero kid."
+                                    );
                                 };
 
-                                stream_decrypter = Decrypter::Mp4Decrypt(HashMap::from([(
-                                    default_kid.to_owned(),
-                                    key.to_owned(),
-                                )]));
-
-                                pb.lock().unwrap().write(format!(
-                                    "        {} {}:{}",
-                                    "Key".colorize("bold red"),
-                                    default_kid,
-                                    key,
-                                ))?;
+                                keys.insert(default_kid.clone().unwrap(), key.to_owned());
+                            }
+
+                            if !matches!(&stream_decrypter, Decrypter::Mp4Decrypt(x) if *x == keys)
+                            {
+                                for kid in &init_kids {
+                                    if let Some(key) = keys.get(kid) {
+                                        pb.lock().unwrap().write(format!(
+                                            "        {} {}:{}",
+                                            "Key".colorize("bold red"),
+                                            kid,
+                                            key,
+                                        ))?;
+                                    }
+                                }
                             }
+
+                            stream_decrypter = Decrypter::Mp4Decrypt(keys);
                         } else {
                             bail!("custom keys (KID:KEY;...) are required to continue further.",);
                         }
@@ -242,6 +318,7 @@ fn download_stream(
             pb: pb.clone(),
             request,
             retries,
+            steering: steering.clone(),
             timer: timer.clone(),
         });
 
@@ -276,7 +353,29 @@ fn download_stream(
         "Downloaded".colorize("bold green"),
     ))?;
 
-    Ok(())
+    if no_decrypt
+        && stream
+            .segments
+            .iter()
+            .any(|x| matches!(&x.key, Some(y) if y.method != KeyMethod::None))
+    {
+        let sidecar = Sidecar::new(
+            &base_url,
+            first_init_seg.as_deref(),
+            temp_file,
+            merger.sizes(),
+            &stream,
+        )?
+        .write()?;
+        pb.lock().unwrap().write(format!(
+            "     {} {}",
+            "Sidecar".colorize("bold green"),
+            sidecar.to_string_lossy(),
+        ))?;
+        return Ok(Some(sidecar));
+    }
+
+    Ok(None)
 }
 
 struct Thread {
@@ -289,6 +388,7 @@ struct Thread {
     pb: Arc<Mutex<RichProgress>>,
     request: RequestBuilder,
     retries: u8,
+    steering: Option<Arc<Mutex<Steering>>>,
     timer: Arc<Instant>,
 }
 
@@ -327,45 +427,67 @@ impl Thread {
         Ok(())
     }
 
+    /// Send segment request to currently preferred content steering pathway.
+    fn send(&self) -> reqwest::Result<Response> {
+        let request = self.request.try_clone().unwrap();
+
+        if let Some(steering) = &self.steering {
+            let (client, request) = request.build_split();
+            let mut request = request?;
+            let url = Steering::rebase(steering, request.url());
+            *request.url_mut() = url;
+            return client.execute(request);
+        }
+
+        request.send()
+    }
+
     fn segment(&self) -> Result<Vec<u8>> {
-        for _ in 0..self.retries {
-            let response = match self.request.try_clone().unwrap().send() {
-                Ok(response) => response,
-                Err(error) => {
-                    // TODO - Only print this info on verbose logging
-                    self.pb
-                        .lock()
-                        .unwrap()
-                        .write(check_reqwest_error(&error)?)?;
-                    continue;
-                }
-            };
+        let data = fetch(&self.pb, self.retries, || self.send())?;
+        let elapsed_time = self.timer.elapsed().as_secs() as usize;
+
+        let stored = self.merger.lock().unwrap().stored() + data.len();
+
+        if let Some(speed) = stored.checked_div(elapsed_time) {
+            self.pb.lock().unwrap().replace(
+                12,
+                Column::Text(format!(
+                    "[yellow]{}/s",
+                    utils::format_bytes(speed, 2).2
+                )),
+            );
+        }
 
-            let status = response.status();
+        Ok(data)
+    }
+}
 
-            if status.is_client_error() || status.is_server_error() {
-                bail!("failed to fetch segments.");
+/// Fetch response body of a request, request is sent again on connection errors and timeouts.
+pub(super) fn fetch(
+    pb: &Mutex<RichProgress>,
+    retries: u8,
+    send: impl Fn() -> reqwest::Result<Response>,
+) -> Result<Vec<u8>> {
+    for _ in 0..retries {
+        let response = match send() {
+            Ok(response) => response,
+            Err(error) => {
+                // TODO - Only print this info on verbose logging
+                pb.lock().unwrap().write(check_reqwest_error(&error)?)?;
+                continue;
             }
+        };
 
-            let data = response.bytes()?.to_vec();
-            let elapsed_time = self.timer.elapsed().as_secs() as usize;
-
-            if elapsed_time != 0 {
-                let stored = self.merger.lock().unwrap().stored() + data.len();
-                self.pb.lock().unwrap().replace(
-                    12,
-                    Column::Text(format!(
-                        "[yellow]{}/s",
-                        utils::format_bytes(stored / elapsed_time, 2).2
-                    )),
-                );
-            }
+        let status = response.status();
 
-            return Ok(data);
+        if status.is_client_error() || status.is_server_error() {
+            bail!("failed to fetch segments.");
         }
 
-        bail!("reached max retries to download a segment.");
+        return Ok(response.bytes()?.to_vec());
     }
+
+    bail!("reached max retries to download a segment.");
 }
 
 fn check_reqwest_error(error: &reqwest::Error) -> Result<String> {
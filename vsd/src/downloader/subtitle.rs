@@ -1,51 +1,269 @@
-use super::mux::Stream;
+use super::{mux::Stream, stream};
 use crate::{
+    output::OutputTemplate,
     playlist::{MediaPlaylist, MediaType},
     utils,
 };
 use anyhow::{Result, anyhow};
+use clap::{Args, ValueEnum};
 use kdam::{BarExt, Column, RichProgress, term::Colorizer};
-use reqwest::{Url, blocking::Client, header};
-use std::{collections::HashMap, ffi::OsStr, fs::File, io::Write, path::PathBuf};
-use vsd_mp4::text::{Mp4TtmlParser, Mp4VttParser, ttml_text_parser};
+use rayon::ThreadPool;
+use reqwest::{
+    Url,
+    blocking::{Client, RequestBuilder},
+    header,
+};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use vsd_mp4::text::{
+    CueTiming, ImageSubtitles, Mp4TtmlParser, Mp4VttParser, Subtitles, VttTextParser,
+    sami_text_parser, srt_text_parser, ttml_text_parser,
+};
+
+/// Output format of subtitles.
+#[derive(Clone, Debug, ValueEnum)]
+pub enum SubtitleFormat {
+    #[value(alias = "ssa")]
+    Ass,
+    #[value(alias = "srt")]
+    Subrip,
+    #[value(alias = "dfxp")]
+    Ttml,
+    #[value(alias = "vtt")]
+    Webvtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Ass => "ass",
+            Self::Subrip => "srt",
+            Self::Ttml => "ttml",
+            Self::Webvtt => "vtt",
+        }
+    }
+
+    pub fn build(&self, subtitles: &Subtitles) -> String {
+        match self {
+            Self::Ass => subtitles.as_ass(),
+            Self::Subrip => subtitles.as_srt(),
+            Self::Ttml => subtitles.as_ttml(),
+            Self::Webvtt => subtitles.as_vtt(),
+        }
+    }
+}
+
+/// Output format of image based subtitles.
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum ImageSubtitleFormat {
+    /// Directory of png images along with their bdn xml index (index.xml).
+    Png,
+    /// Presentation graphic stream (pgs), which can be muxed into mkv.
+    #[default]
+    #[value(alias = "pgs")]
+    Sup,
+}
+
+impl ImageSubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "bdn",
+            Self::Sup => "sup",
+        }
+    }
+
+    pub fn write(&self, subtitles: &ImageSubtitles, path: &Path) -> Result<()> {
+        match self {
+            Self::Png => {
+                fs::create_dir_all(path)?;
+
+                for (i, cue) in subtitles.cues().iter().enumerate() {
+                    fs::write(path.join(format!("{:04}.png", i + 1)), cue.bitmap.to_png())?;
+                }
+
+                // Timings are converted to timecodes of 25 fps as subtitles aren't tied to any video.
+                fs::write(path.join("index.xml"), subtitles.as_bdn_xml(25.0))?;
+            }
+            Self::Sup => fs::write(path, subtitles.as_sup())?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Timing changes applied to subtitles before saving them.
+#[derive(Args, Clone, Debug, Default)]
+pub struct SubtitleTiming {
+    /// Clip subtitles to a time range (in seconds), either end of range can be left empty.
+    /// Cues are clipped before changing their frame rate and offset.
+    /// EXAMPLE: --subs-clip 60-300
+    #[arg(long = "subs-clip", allow_hyphen_values = true, value_name = "START-END", value_parser = Self::parse_clip)]
+    pub clip: Option<(Option<f32>, Option<f32>)>,
+
+    /// Convert timing of subtitles from one frame rate to another.
+    /// EXAMPLE: --subs-fps 25:23.976
+    #[arg(long = "subs-fps", value_name = "FROM:TO", value_parser = Self::parse_fps)]
+    pub fps: Option<(f32, f32)>,
+
+    /// Shift subtitles by given seconds, which can be negative.
+    #[arg(
+        long = "subs-offset",
+        allow_hyphen_values = true,
+        default_value_t = 0.0,
+        value_name = "SECONDS"
+    )]
+    pub offset: f32,
+}
+
+impl SubtitleTiming {
+    /// Cues are first clipped, then scaled to new frame rate and at last shifted by offset.
+    pub fn apply(&self, subtitles: &mut impl CueTiming) {
+        if let Some((start, end)) = self.clip {
+            subtitles.clip(start, end);
+        }
+
+        if let Some((from, to)) = self.fps {
+            subtitles.scale(from / to);
+        }
 
-enum SubtitleType {
+        if self.offset != 0.0 {
+            subtitles.shift(self.offset);
+        }
+    }
+
+    pub fn parse_clip(s: &str) -> Result<(Option<f32>, Option<f32>), String> {
+        let Some((start, end)) = s.split_once('-') else {
+            return Err("expected START-END format (either of them can be empty).".to_owned());
+        };
+        let parse = |x: &str| -> Result<Option<f32>, String> {
+            if x.trim().is_empty() {
+                return Ok(None);
+            }
+
+            x.trim()
+                .parse::<f32>()
+                .map(Some)
+                .map_err(|_| format!("couldn't parse {x} as seconds."))
+        };
+
+        Ok((parse(start)?, parse(end)?))
+    }
+
+    pub fn parse_fps(s: &str) -> Result<(f32, f32), String> {
+        let Some((from, to)) = s.split_once(':') else {
+            return Err("expected FROM:TO format e.g. 25:23.976".to_owned());
+        };
+        let parse = |x: &str| -> Result<f32, String> {
+            x.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|x| *x > 0.0)
+                .ok_or_else(|| format!("couldn't parse {x} as frame rate."))
+        };
+
+        Ok((parse(from)?, parse(to)?))
+    }
+}
+
+/// Codec of subtitles detected from their contents.
+pub enum SubtitleType {
     Mp4Vtt,
     Mp4Ttml,
+    SamiText,
     SrtText,
     TtmlText,
     Unknown,
     VttText,
 }
 
+impl SubtitleType {
+    /// Detect codec from contents of first segment, [`SubtitleType::Unknown`] is never returned.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let text = utils::decode_text(data);
+        let text = text.trim_start();
+        let lowercase = text.get(..5).unwrap_or_default().to_ascii_lowercase();
+
+        if text.starts_with("WEBVTT") {
+            Some(Self::VttText)
+        } else if lowercase == "<sami" {
+            Some(Self::SamiText)
+        } else if is_srt(text) {
+            Some(Self::SrtText)
+        } else if text.starts_with("<?xml") || text.starts_with("<tt") {
+            Some(Self::TtmlText)
+        } else if Mp4VttParser::parse_init(data).is_ok() {
+            Some(Self::Mp4Vtt)
+        } else if Mp4TtmlParser::parse_init(data).is_ok() {
+            Some(Self::Mp4Ttml)
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn download_subtitle_streams(
     base_url: &Option<Url>,
     client: &Client,
     directory: Option<&PathBuf>,
+    image_subs_format: &ImageSubtitleFormat,
+    output_template: Option<&OutputTemplate>,
     streams: &[MediaPlaylist],
-    pb: &mut RichProgress,
+    pb: &Arc<Mutex<RichProgress>>,
+    pool: &ThreadPool,
     query: &HashMap<String, String>,
+    retries: u8,
+    subs_format: Option<&SubtitleFormat>,
+    subs_timing: &SubtitleTiming,
     temp_files: &mut Vec<Stream>,
 ) -> Result<()> {
+    // Closed captions are extracted later from video streams.
     for stream in streams {
-        if stream.media_type == MediaType::Subtitles {
-            download_subtitle_stream(base_url, client, directory, stream, pb, query, temp_files)?;
+        if stream.media_type == MediaType::Subtitles && stream.instream_id.is_none() {
+            download_subtitle_stream(
+                base_url,
+                client,
+                directory,
+                image_subs_format,
+                output_template,
+                stream,
+                pb,
+                pool,
+                query,
+                retries,
+                subs_format,
+                subs_timing,
+                temp_files,
+            )?;
         }
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn download_subtitle_stream(
     base_url: &Option<Url>,
     client: &Client,
     directory: Option<&PathBuf>,
+    image_subs_format: &ImageSubtitleFormat,
+    output_template: Option<&OutputTemplate>,
     stream: &MediaPlaylist,
-    pb: &mut RichProgress,
+    pb: &Arc<Mutex<RichProgress>>,
+    pool: &ThreadPool,
     query: &HashMap<String, String>,
+    retries: u8,
+    subs_format: Option<&SubtitleFormat>,
+    subs_timing: &SubtitleTiming,
     temp_files: &mut Vec<Stream>,
 ) -> Result<()> {
-    pb.write(format!(
+    pb.lock().unwrap().write(format!(
         " {} [{:>5}] {}",
         "Processing".colorize("cyan"),
         stream.media_type.to_string(),
@@ -53,7 +271,7 @@ fn download_subtitle_stream(
     ))?;
 
     if stream.segments.is_empty() {
-        pb.write(format!(
+        pb.lock().unwrap().write(format!(
             "    {} skipping stream (no segments)",
             "Warning".colorize("yellow"),
         ))?;
@@ -73,7 +291,8 @@ fn download_subtitle_stream(
                 ext = OsStr::new("vtt");
                 codec = Some(SubtitleType::Mp4Vtt);
             }
-            "stpp" | "stpp.ttml" | "stpp.ttml.im1t" | "stpp.TTML.im1t" => {
+            "stpp" | "stpp.ttml" | "stpp.ttml.im1t" | "stpp.TTML.im1t" | "stpp.ttml.im1i"
+            | "stpp.TTML.im1i" => {
                 ext = OsStr::new("srt");
                 codec = Some(SubtitleType::Mp4Ttml);
             }
@@ -81,15 +300,15 @@ fn download_subtitle_stream(
         }
     }
 
-    let mut temp_file = PathBuf::new();
-    let mut first_run = true;
-    let mut subs_data = vec![];
-
     let stream_base_url = base_url
         .clone()
         .unwrap_or(stream.uri.parse::<Url>().unwrap());
+    // Init segment (if any) and media segment requests of every segment.
+    let mut requests = vec![];
 
     for segment in &stream.segments {
+        let mut map_request = None;
+
         if let Some(map) = &segment.map {
             let url = stream_base_url.join(&map.uri)?;
             let mut request = client.get(url).query(query);
@@ -98,9 +317,7 @@ fn download_subtitle_stream(
                 request = request.header(header::RANGE, range.as_header_value());
             }
 
-            let response = request.send()?;
-            let bytes = response.bytes()?;
-            subs_data.extend_from_slice(&bytes);
+            map_request = Some(request);
         }
 
         let url = stream_base_url.join(&segment.uri)?;
@@ -110,79 +327,240 @@ fn download_subtitle_stream(
             request = request.header(header::RANGE, range.as_header_value());
         }
 
-        let response = request.send()?;
-        let bytes = response.bytes()?;
-        subs_data.extend_from_slice(&bytes);
-
-        if first_run {
-            first_run = false;
-
-            if codec.is_none() {
-                if subs_data.starts_with(b"WEBVTT") || ext == "vtt" {
-                    ext = OsStr::new("vtt");
-                    codec = Some(SubtitleType::VttText);
-                } else if subs_data.starts_with(b"1") || ext == "srt" {
-                    ext = OsStr::new("srt");
-                    codec = Some(SubtitleType::SrtText);
-                } else if subs_data.starts_with(b"<?xml") || subs_data.starts_with(b"<tt") || ext == "ttml" {
-                    ext = OsStr::new("srt");
-                    codec = Some(SubtitleType::TtmlText);
-                } else if Mp4VttParser::parse_init(&subs_data).is_ok() {
-                    ext = OsStr::new("vtt");
-                    codec = Some(SubtitleType::Mp4Vtt);
-                } else if Mp4TtmlParser::parse_init(&subs_data).is_ok() {
-                    ext = OsStr::new("srt");
-                    codec = Some(SubtitleType::Mp4Ttml);
-                } else {
-                    pb.write(format!(
-                        "    {} unknown subtitle codec used",
-                        "Warning".colorize("yellow"),
-                    ))?;
-                    ext = OsStr::new("txt");
-                    codec = Some(SubtitleType::Unknown);
-                }
-            }
+        requests.push((map_request, request));
+    }
 
-            temp_file = stream.path(directory, ext);
-            temp_files.push(Stream {
-                language: stream.language.clone(),
-                media_type: stream.media_type.clone(),
-                path: temp_file.clone(),
+    let fetch_segment = |(map_request, request): &(Option<RequestBuilder>, RequestBuilder)| {
+        let mut data = vec![];
+
+        if let Some(map_request) = map_request {
+            data.extend(stream::fetch(pb, retries, || {
+                map_request.try_clone().unwrap().send()
+            })?);
+        }
+
+        data.extend(stream::fetch(pb, retries, || {
+            request.try_clone().unwrap().send()
+        })?);
+        Ok::<_, anyhow::Error>(data)
+    };
+
+    // First segment is downloaded before others to detect subtitles codec.
+    let mut subs_data = fetch_segment(&requests[0])?;
+
+    if codec.is_none() {
+        // Contents are checked before extension, which can be wrong.
+        let detected = SubtitleType::detect(&subs_data).or(match ext.to_str() {
+            Some("vtt") => Some(SubtitleType::VttText),
+            Some("smi" | "x-sami") => Some(SubtitleType::SamiText),
+            Some("srt" | "x-subrip") => Some(SubtitleType::SrtText),
+            Some("ttml") => Some(SubtitleType::TtmlText),
+            _ => None,
+        });
+
+        if let Some(detected) = detected {
+            ext = OsStr::new(match detected {
+                SubtitleType::Mp4Vtt | SubtitleType::VttText => "vtt",
+                _ => "srt",
             });
-            pb.write(format!(
-                "{} {}",
-                "Downloading".colorize("bold green"),
-                temp_file.to_string_lossy()
+            codec = Some(detected);
+        } else {
+            pb.lock().unwrap().write(format!(
+                "    {} unknown subtitle codec used",
+                "Warning".colorize("yellow"),
             ))?;
+            ext = OsStr::new("txt");
+            codec = Some(SubtitleType::Unknown);
+        }
+    }
+
+    if let Some(subs_format) = subs_format {
+        match codec {
+            Some(SubtitleType::Unknown) => pb.lock().unwrap().write(format!(
+                "    {} --subs-format is ignored, {} subtitles are saved as it is",
+                "Warning".colorize("yellow"),
+                ext.to_string_lossy(),
+            ))?,
+            _ => ext = OsStr::new(subs_format.extension()),
         }
+    }
+
+    let temp_file = stream.path(directory, ext, output_template)?;
+    temp_files.push(Stream {
+        discontinuous: false,
+        language: stream.language.clone(),
+        media_type: stream.media_type.clone(),
+        path: temp_file.clone(),
+        sidecar: None,
+    });
+    pb.lock().unwrap().write(format!(
+        "{} {}",
+        "Downloading".colorize("bold green"),
+        temp_file.to_string_lossy()
+    ))?;
 
+    let notify = |stored: usize| -> Result<()> {
+        let mut pb = pb.lock().unwrap();
         pb.replace(
             0,
-            Column::Text(format!(
-                "[bold blue]{}",
-                utils::format_bytes(subs_data.len(), 2).2
-            )),
+            Column::Text(format!("[bold blue]{}", utils::format_bytes(stored, 2).2)),
         );
         pb.update(1)?;
+        Ok(())
+    };
+
+    notify(subs_data.len())?;
+
+    // Rest of the segments are downloaded in parallel and reassembled in order.
+    let segments = Mutex::new((subs_data.len(), vec![None; requests.len()]));
+
+    pool.scope_fifo(|s| {
+        for (i, request) in requests.into_iter().enumerate().skip(1) {
+            let fetch_segment = &fetch_segment;
+            let notify = &notify;
+            let segments = &segments;
+
+            s.spawn_fifo(move |_| {
+                let result = fetch_segment(&request).and_then(|data| {
+                    let mut segments = segments.lock().unwrap();
+                    segments.0 += data.len();
+                    segments.1[i] = Some(data);
+                    notify(segments.0)
+                });
+
+                if let Err(e) = result {
+                    let _lock = pb.lock().unwrap();
+                    println!("\n{}: {}", "error".colorize("bold red"), e);
+                    std::process::exit(1);
+                }
+            });
+        }
+    });
+
+    // End position of every segment inside subs data.
+    let mut segment_ends = vec![subs_data.len()];
+    // End position and offset of every dash period inside subs data.
+    let mut periods: Vec<(usize, Option<f32>)> =
+        vec![(subs_data.len(), stream.segments[0].period_offset)];
+
+    for (segment, data) in stream
+        .segments
+        .iter()
+        .zip(segments.into_inner().unwrap().1)
+        .skip(1)
+    {
+        subs_data.extend_from_slice(&data.unwrap_or_default());
+        segment_ends.push(subs_data.len());
+
+        match periods.last_mut() {
+            Some((end, offset)) if *offset == segment.period_offset => *end = subs_data.len(),
+            _ => periods.push((subs_data.len(), segment.period_offset)),
+        }
     }
 
-    match codec {
+    // Every subtitle source is normalized to same subtitles model, which is then
+    // written in format asked by user.
+    let (mut subs, default_format) = match codec {
         Some(SubtitleType::Mp4Vtt) => {
-            pb.write(format!(" {} wvtt subs", "Extracting".colorize("cyan")))?;
-            let vtt = Mp4VttParser::parse_init(&subs_data)?;
-            let subs = vtt.parse_media(&subs_data, None)?;
-            File::create(&temp_file)?.write_all(subs.as_vtt().as_bytes())?;
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} wvtt subs", "Extracting".colorize("cyan")))?;
+            let mut vtt = None;
+            let mut subs: Option<Subtitles> = None;
+            let mut start = 0;
+
+            // Every period is parsed separately, periods without their own init segment reuse previous one.
+            for (end, offset) in periods {
+                let data = &subs_data[start..end];
+
+                if vtt.is_none() || Mp4VttParser::parse_init(data).is_ok() {
+                    vtt = Some(Mp4VttParser::parse_init(data)?);
+                }
+
+                let period_subs = vtt.as_ref().unwrap().parse_media(data, offset)?;
+
+                match &mut subs {
+                    Some(subs) => subs.merge(period_subs),
+                    None => subs = Some(period_subs),
+                }
+
+                start = end;
+            }
+
+            (subs.unwrap(), SubtitleFormat::Webvtt)
         }
         Some(SubtitleType::Mp4Ttml) => {
-            pb.write(format!(" {} stpp subs", "Extracting".colorize("cyan")))?;
-            let ttml = Mp4TtmlParser::parse_init(&subs_data)?;
-            let subs = ttml.parse_media(&subs_data)?;
-            File::create(&temp_file)?.write_all(subs.as_srt().as_bytes())?;
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} stpp subs", "Extracting".colorize("cyan")))?;
+            let mut ttml = None;
+            let mut subs: Option<Subtitles> = None;
+            let mut images: Option<ImageSubtitles> = None;
+            let mut start = 0;
+
+            for (end, offset) in periods {
+                let data = &subs_data[start..end];
+
+                if ttml.is_none() || Mp4TtmlParser::parse_init(data).is_ok() {
+                    ttml = Some(Mp4TtmlParser::parse_init(data)?);
+                }
+
+                let ttml = ttml.as_ref().unwrap();
+                let mut period_subs = ttml.parse_media(data)?;
+                let mut period_images = ttml.parse_media_images(data)?;
+                period_subs.shift(offset.unwrap_or(0.0));
+                period_images.shift(offset.unwrap_or(0.0));
+
+                match &mut subs {
+                    Some(subs) => subs.merge(period_subs),
+                    None => subs = Some(period_subs),
+                }
+
+                match &mut images {
+                    Some(images) => images.extend(period_images),
+                    None => images = Some(period_images),
+                }
+
+                start = end;
+            }
+
+            let subs = subs.unwrap();
+            let images = images.unwrap();
+
+            if subs.is_empty() && !images.is_empty() {
+                return write_image_subtitles(
+                    directory,
+                    image_subs_format,
+                    images,
+                    output_template,
+                    pb,
+                    stream,
+                    subs_timing,
+                    temp_files,
+                );
+            }
+
+            (subs, SubtitleFormat::Subrip)
+        }
+        Some(SubtitleType::SamiText) => {
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} sami subs", "Extracting".colorize("cyan")))?;
+            let text = utils::decode_text(&subs_data);
+            (sami_text_parser::parse(&text)?, SubtitleFormat::Subrip)
+        }
+        Some(SubtitleType::SrtText) => {
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} subrip subs", "Extracting".colorize("cyan")))?;
+            let text = utils::decode_text(&subs_data);
+            (srt_text_parser::parse(&text)?, SubtitleFormat::Subrip)
         }
         Some(SubtitleType::TtmlText) => {
-            pb.write(format!(" {} ttml+xml subs", "Extracting".colorize("cyan")))?;
-            let xml = String::from_utf8(subs_data)
-                .map_err(|_| anyhow!("cannot decode subs as valid utf-8 data."))?;
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} ttml+xml subs", "Extracting".colorize("cyan")))?;
+            let xml = utils::decode_text(&subs_data);
             let ttml = ttml_text_parser::parse(&xml).map_err(|x| {
                 anyhow!(
                     "couldn't parse xml string as ttml content.\n\n{}\n\n{:#?}",
@@ -190,14 +568,120 @@ fn download_subtitle_stream(
                     x,
                 )
             })?;
-            File::create(&temp_file)?.write_all(ttml.into_subtitles().as_srt().as_bytes())?;
+            let images = ttml.image_subtitles(&[])?;
+            let subs = ttml.into_subtitles();
+
+            if subs.is_empty() && !images.is_empty() {
+                return write_image_subtitles(
+                    directory,
+                    image_subs_format,
+                    images,
+                    output_template,
+                    pb,
+                    stream,
+                    subs_timing,
+                    temp_files,
+                );
+            }
+
+            (subs, SubtitleFormat::Subrip)
+        }
+        Some(SubtitleType::VttText) => {
+            pb.lock()
+                .unwrap()
+                .write(format!(" {} webvtt subs", "Merging".colorize("cyan")))?;
+            let mut parser = VttTextParser::new();
+            let mut start = 0;
+
+            for (segment, end) in stream.segments.iter().zip(segment_ends) {
+                parser.parse_segment(
+                    &utils::decode_text(&subs_data[start..end]),
+                    segment.period_offset,
+                )?;
+                start = end;
+            }
+
+            (parser.subtitles(), SubtitleFormat::Webvtt)
+        }
+        _ => {
+            File::create(&temp_file)?.write_all(&subs_data)?;
+            pb.lock().unwrap().write(format!(
+                " {} stream successfully",
+                "Downloaded".colorize("bold green"),
+            ))?;
+            return Ok(());
         }
-        _ => File::create(&temp_file)?.write_all(&subs_data)?,
     };
 
-    pb.write(format!(
+    // Cues of consecutive periods can overlap or repeat at period boundaries.
+    subs.merge_overlapping();
+    subs_timing.apply(&mut subs);
+    let subs = subs_format.unwrap_or(&default_format).build(&subs);
+    File::create(&temp_file)?.write_all(subs.as_bytes())?;
+
+    pb.lock().unwrap().write(format!(
         " {} stream successfully",
         "Downloaded".colorize("bold green"),
     ))?;
     Ok(())
 }
+
+/// Image based subtitles can't be converted to text formats, so they are saved separately.
+#[allow(clippy::too_many_arguments)]
+fn write_image_subtitles(
+    directory: Option<&PathBuf>,
+    image_subs_format: &ImageSubtitleFormat,
+    mut images: ImageSubtitles,
+    output_template: Option<&OutputTemplate>,
+    pb: &Arc<Mutex<RichProgress>>,
+    stream: &MediaPlaylist,
+    subs_timing: &SubtitleTiming,
+    temp_files: &mut Vec<Stream>,
+) -> Result<()> {
+    // Text subtitles file was expected when stream was detected.
+    temp_files.pop();
+    subs_timing.apply(&mut images);
+
+    let path = stream.path(
+        directory,
+        OsStr::new(image_subs_format.extension()),
+        output_template,
+    )?;
+    image_subs_format.write(&images, &path)?;
+
+    match image_subs_format {
+        ImageSubtitleFormat::Png => pb.lock().unwrap().write(format!(
+            "    {} {} isn't muxed (png images can't be muxed)",
+            "Warning".colorize("yellow"),
+            path.to_string_lossy(),
+        ))?,
+        ImageSubtitleFormat::Sup => temp_files.push(Stream {
+            discontinuous: false,
+            language: stream.language.clone(),
+            media_type: stream.media_type.clone(),
+            path: path.clone(),
+            sidecar: None,
+        }),
+    }
+
+    pb.lock().unwrap().write(format!(
+        " {} {} ({} image subs)",
+        "Downloaded".colorize("bold green"),
+        path.to_string_lossy(),
+        images.cues().len(),
+    ))?;
+    Ok(())
+}
+
+/// First cue of subrip content i.e. optional index followed by timing line.
+fn is_srt(text: &str) -> bool {
+    let mut lines = text.lines().filter(|x| !x.trim().is_empty());
+
+    match lines.next() {
+        Some(x) if x.contains("-->") => true,
+        Some(x) if x.trim().chars().all(|x| x.is_ascii_digit()) => {
+            lines.next().is_some_and(|x| x.contains("-->"))
+        }
+        _ => false,
+    }
+}
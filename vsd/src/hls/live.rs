@@ -0,0 +1,164 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://datatracker.ietf.org/doc/html/draft-pantos-hls-rfc8216bis#section-4.4.4.9
+    2. https://developer.apple.com/documentation/http-live-streaming/enabling-low-latency-http-live-streaming-hls
+
+*/
+
+use super::playlist::parse_attributes;
+use crate::playlist::Range;
+
+/// Live media playlist along with low-latency (LL-HLS) tags which aren't parsed by m3u8-rs.
+#[derive(Default)]
+pub(crate) struct LivePlaylist {
+    /// `EXT-X-SERVER-CONTROL` CAN-BLOCK-RELOAD=YES
+    pub can_block_reload: bool,
+    pub encrypted: bool,
+    pub end_list: bool,
+    pub map: Option<LivePart>,
+    /// `EXT-X-PART-INF` PART-TARGET
+    pub part_target: Option<f32>,
+    /// `EXT-X-PRELOAD-HINT` of TYPE=PART
+    pub preload_hint: Option<LivePart>,
+    /// Last segment doesn't have any uri if it is still being published part by part.
+    pub segments: Vec<LiveSegment>,
+    pub target_duration: f32,
+}
+
+pub(crate) struct LiveSegment {
+    pub media_sequence: u64,
+    pub parts: Vec<LivePart>,
+    pub range: Option<Range>,
+    pub uri: Option<String>,
+}
+
+pub(crate) struct LivePart {
+    pub range: Option<Range>,
+    pub uri: String,
+}
+
+impl LivePlaylist {
+    pub fn is_low_latency(&self) -> bool {
+        self.part_target.is_some()
+    }
+}
+
+/// Parse `n[@o]` byte range, sub range without offset starts after the previous one.
+fn parse_byterange(value: &str, previous_end: &mut Option<u64>) -> Option<Range> {
+    let (length, offset) = match value.split_once('@') {
+        Some((length, offset)) => (length.parse::<u64>().ok()?, offset.parse::<u64>().ok()?),
+        None => (
+            value.parse::<u64>().ok()?,
+            previous_end.map(|x| x + 1).unwrap_or(0),
+        ),
+    };
+
+    if length == 0 {
+        return None;
+    }
+
+    let range = Range {
+        start: offset,
+        end: offset + length - 1,
+    };
+    *previous_end = Some(range.end);
+    Some(range)
+}
+
+pub(crate) fn parse_live_playlist(text: &str) -> LivePlaylist {
+    let mut playlist = LivePlaylist::default();
+    let mut media_sequence = 0;
+    let mut parts = vec![];
+    let mut range = None;
+    let mut previous_end = None;
+    let mut previous_part_end = None;
+
+    for line in text.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let (tag, rest) = line.split_once(':').unwrap_or((line, ""));
+
+        match tag {
+            "#EXT-X-MEDIA-SEQUENCE" => media_sequence = rest.parse().unwrap_or(0),
+            "#EXT-X-TARGETDURATION" => playlist.target_duration = rest.parse().unwrap_or(0.0),
+            "#EXT-X-ENDLIST" => playlist.end_list = true,
+            "#EXT-X-BYTERANGE" => range = parse_byterange(rest, &mut previous_end),
+            "#EXT-X-KEY"
+                if parse_attributes(rest)
+                    .get("METHOD")
+                    .is_some_and(|x| x != "NONE") =>
+            {
+                playlist.encrypted = true;
+            }
+            "#EXT-X-MAP" => {
+                let attributes = parse_attributes(rest);
+
+                if let Some(uri) = attributes.get("URI") {
+                    playlist.map = Some(LivePart {
+                        range: attributes
+                            .get("BYTERANGE")
+                            .and_then(|x| parse_byterange(x, &mut None)),
+                        uri: uri.to_owned(),
+                    });
+                }
+            }
+            "#EXT-X-PART-INF" => {
+                playlist.part_target = parse_attributes(rest)
+                    .get("PART-TARGET")
+                    .and_then(|x| x.parse().ok());
+            }
+            "#EXT-X-SERVER-CONTROL" => {
+                playlist.can_block_reload = parse_attributes(rest)
+                    .get("CAN-BLOCK-RELOAD")
+                    .is_some_and(|x| x == "YES");
+            }
+            "#EXT-X-PART" => {
+                let attributes = parse_attributes(rest);
+
+                if let Some(uri) = attributes.get("URI") {
+                    parts.push(LivePart {
+                        range: attributes
+                            .get("BYTERANGE")
+                            .and_then(|x| parse_byterange(x, &mut previous_part_end)),
+                        uri: uri.to_owned(),
+                    });
+                }
+            }
+            "#EXT-X-PRELOAD-HINT" => {
+                let attributes = parse_attributes(rest);
+
+                // Hints with open ended byte ranges are not used.
+                if attributes.get("TYPE").is_some_and(|x| x == "PART")
+                    && !attributes.contains_key("BYTERANGE-START")
+                    && let Some(uri) = attributes.get("URI")
+                {
+                    playlist.preload_hint = Some(LivePart {
+                        range: None,
+                        uri: uri.to_owned(),
+                    });
+                }
+            }
+            x if !x.starts_with('#') => {
+                playlist.segments.push(LiveSegment {
+                    media_sequence,
+                    parts: std::mem::take(&mut parts),
+                    range: range.take(),
+                    uri: Some(line.to_owned()),
+                });
+                media_sequence += 1;
+            }
+            _ => (),
+        }
+    }
+
+    if !parts.is_empty() {
+        playlist.segments.push(LiveSegment {
+            media_sequence,
+            parts,
+            range: None,
+            uri: None,
+        });
+    }
+
+    playlist
+}
@@ -1,3 +1,5 @@
+mod live;
 mod playlist;
 
+pub(crate) use live::{LivePart, LivePlaylist, parse_live_playlist};
 pub(crate) use playlist::{parse_as_master, push_segments};
@@ -1,24 +1,64 @@
-use crate::playlist;
+use crate::{playlist, steering, utils};
+use regex::Regex;
+use std::{collections::HashMap, sync::LazyLock};
 
 pub(crate) fn parse_as_master(
     m3u8: &m3u8_rs::MasterPlaylist,
     uri: &str,
 ) -> playlist::MasterPlaylist {
     let mut streams = vec![];
+    let title = playlist::title_from_uri(uri);
 
     for video_stream in &m3u8.variants {
+        let attribute = |key: &str| {
+            video_stream
+                .other_attributes
+                .as_ref()
+                .and_then(|x| x.get(key))
+                .map(|x| x.as_str().to_owned())
+        };
+        let supplemental_codecs = attribute("SUPPLEMENTAL-CODECS");
+        let video_range = playlist::video_range(
+            video_stream.codecs.as_deref(),
+            supplemental_codecs.as_deref(),
+            attribute("VIDEO-RANGE").as_deref(),
+        );
+
         streams.push(playlist::MediaPlaylist {
+            accessibility: vec![],
+            ad_breaks: vec![],
+            audio_group: video_stream.audio.to_owned(),
+            autoselect: false,
             bandwidth: Some(video_stream.bandwidth),
             channels: None,
+            chapters: vec![],
+            characteristics: None,
+            closed_captions_group: match &video_stream.closed_captions {
+                Some(m3u8_rs::ClosedCaptionGroupId::GroupId(x)) => Some(x.to_owned()),
+                _ => None,
+            },
             codecs: video_stream.codecs.to_owned(),
+            default: false,
+            dynamic_range: playlist::dynamic_range(
+                video_stream.codecs.as_deref(),
+                supplemental_codecs.as_deref(),
+                video_range.as_deref(),
+            ),
             extension: Some("ts".to_owned()), // Cannot be comment here
+            forced: false,
             frame_rate: video_stream.frame_rate.map(|x| x as f32),
+            group_id: None,
+            hdcp_level: video_stream.hdcp_level.as_ref().map(|x| x.to_string()),
             id: String::new(), // Cannot be comment here
             i_frame: video_stream.is_i_frame,
+            instream_id: None,
             language: None,
             live: false, // Cannot be comment here
             media_sequence: 0,
             media_type: playlist::MediaType::Video,
+            name: None,
+            pathway_id: attribute("PATHWAY-ID"),
+            pathway_uris: Default::default(),
             playlist_type: playlist::PlaylistType::Hls,
             resolution: if let Some(m3u8_rs::Resolution { width, height }) = video_stream.resolution
             {
@@ -26,122 +66,191 @@ pub(crate) fn parse_as_master(
             } else {
                 None
             },
+            roles: vec![],
             segments: vec![], // Cannot be comment here
+            steering: None,
+            subtitles_group: video_stream.subtitles.to_owned(),
+            supplemental_codecs,
+            title: title.clone(),
             uri: video_stream.uri.to_owned(),
+            video_range,
+            viewpoint: None,
         });
     }
 
     for alternative_stream in &m3u8.alternatives {
-        if let Some(uri) = &alternative_stream.uri {
-            match alternative_stream.media_type {
-                m3u8_rs::AlternativeMediaType::Video => streams.push(playlist::MediaPlaylist {
-                    bandwidth: None, // Cannot be comment here
-                    channels: None,
-                    codecs: None,                     // Cannot be comment here
-                    extension: Some("ts".to_owned()), // Cannot be comment here
-                    frame_rate: None,                 // Cannot be comment here
-                    id: String::new(),                // Cannot be comment here
-                    i_frame: false,                   // Cannot be comment here
-                    language: None,
-                    live: false, // Cannot be comment here
-                    media_sequence: 0,
-                    media_type: playlist::MediaType::Video,
-                    playlist_type: playlist::PlaylistType::Hls,
-                    resolution: None, // Cannot be comment here
-                    segments: vec![], // Cannot be comment here
-                    uri: uri.to_owned(),
-                }),
-
-                m3u8_rs::AlternativeMediaType::Audio => streams.push(playlist::MediaPlaylist {
-                    bandwidth: None, // Cannot be comment here
-                    channels: alternative_stream
-                        .channels
-                        .as_ref()
-                        .map(|x| x.parse::<f32>().unwrap()),
-                    codecs: None,                     // Cannot be comment here
-                    extension: Some("ts".to_owned()), // Cannot be comment here
-                    frame_rate: None,
-                    id: String::new(), // Cannot be comment here
-                    i_frame: false,
-                    language: alternative_stream
-                        .language
-                        .to_owned()
-                        .or(alternative_stream.assoc_language.to_owned()),
-                    live: false, // Cannot be comment here
-                    media_sequence: 0,
-                    media_type: playlist::MediaType::Audio,
-                    playlist_type: playlist::PlaylistType::Hls,
-                    resolution: None,
-                    segments: vec![], // Cannot be comment here
-                    uri: uri.to_owned(),
-                }),
+        // Closed captions without uri are carried inside video stream.
+        let instream_id = match (&alternative_stream.media_type, &alternative_stream.uri) {
+            (m3u8_rs::AlternativeMediaType::ClosedCaptions, None) => alternative_stream
+                .instream_id
+                .as_ref()
+                .map(|x| x.to_string()),
+            _ => None,
+        };
 
+        if alternative_stream.uri.is_some() || instream_id.is_some() {
+            let (media_type, extension) = match alternative_stream.media_type {
+                m3u8_rs::AlternativeMediaType::Video => {
+                    (playlist::MediaType::Video, Some("ts".to_owned()))
+                }
+                m3u8_rs::AlternativeMediaType::Audio => {
+                    (playlist::MediaType::Audio, Some("ts".to_owned()))
+                }
                 m3u8_rs::AlternativeMediaType::ClosedCaptions
                 | m3u8_rs::AlternativeMediaType::Subtitles => {
-                    streams.push(playlist::MediaPlaylist {
-                        bandwidth: None,
-                        channels: None,
-                        codecs: None,                      // Cannot be comment here
-                        extension: Some("vtt".to_owned()), // Cannot be comment here
-                        frame_rate: None,
-                        id: String::new(), // Cannot be comment here
-                        i_frame: false,
-                        language: alternative_stream
-                            .language
-                            .to_owned()
-                            .or(alternative_stream.assoc_language.to_owned()),
-                        live: false, // Cannot be comment here
-                        media_sequence: 0,
-                        media_type: playlist::MediaType::Subtitles,
-                        playlist_type: playlist::PlaylistType::Hls,
-                        resolution: None,
-                        segments: vec![], // Cannot be comment here
-                        uri: uri.to_owned(),
-                    })
+                    (playlist::MediaType::Subtitles, Some("vtt".to_owned()))
                 }
+                m3u8_rs::AlternativeMediaType::Other(_) => (playlist::MediaType::Undefined, None),
+            };
 
-                m3u8_rs::AlternativeMediaType::Other(_) => streams.push(playlist::MediaPlaylist {
-                    bandwidth: None,
-                    channels: alternative_stream
-                        .channels
-                        .as_ref()
-                        .map(|x| x.parse::<f32>().unwrap()),
-                    codecs: None,      // Cannot be comment here
-                    extension: None,   // Cannot be comment here
-                    frame_rate: None,  // Cannot be comment here
-                    id: String::new(), // Cannot be comment here
-                    i_frame: false,    // Cannot be comment here
-                    language: alternative_stream
+            streams.push(playlist::MediaPlaylist {
+                accessibility: vec![],
+                ad_breaks: vec![],
+                audio_group: None,
+                autoselect: alternative_stream.autoselect,
+                bandwidth: None, // Cannot be comment here
+                channels: match media_type {
+                    playlist::MediaType::Audio | playlist::MediaType::Undefined => {
+                        alternative_stream
+                            .channels
+                            .as_ref()
+                            .and_then(|x| x.split('/').next())
+                            .and_then(|x| x.parse::<f32>().ok())
+                    }
+                    _ => None,
+                },
+                chapters: vec![],
+                characteristics: alternative_stream.characteristics.to_owned(),
+                closed_captions_group: None,
+                codecs: instream_id.as_ref().map(|x| {
+                    if x.starts_with("CC") {
+                        "cea-608".to_owned()
+                    } else {
+                        "cea-708".to_owned()
+                    }
+                }),
+                default: alternative_stream.default,
+                dynamic_range: None,
+                extension, // Cannot be comment here
+                forced: alternative_stream.forced,
+                frame_rate: None,  // Cannot be comment here
+                group_id: Some(alternative_stream.group_id.to_owned()),
+                hdcp_level: None,
+                id: String::new(), // Cannot be comment here
+                i_frame: false,    // Cannot be comment here
+                instream_id,
+                language: if media_type == playlist::MediaType::Video {
+                    None
+                } else {
+                    alternative_stream
                         .language
                         .to_owned()
-                        .or(alternative_stream.assoc_language.to_owned()),
-                    live: false, // Cannot be comment here
-                    media_sequence: 0,
-                    media_type: playlist::MediaType::Undefined,
-                    playlist_type: playlist::PlaylistType::Hls,
-                    resolution: None, // Cannot be comment here
-                    segments: vec![], // Cannot be comment here
-                    uri: uri.to_owned(),
-                }),
-            }
+                        .or(alternative_stream.assoc_language.to_owned())
+                },
+                live: false, // Cannot be comment here
+                media_sequence: 0,
+                media_type,
+                name: Some(alternative_stream.name.to_owned()),
+                pathway_id: None,
+                pathway_uris: Default::default(),
+                playlist_type: playlist::PlaylistType::Hls,
+                resolution: None, // Cannot be comment here
+                roles: vec![],
+                segments: vec![], // Cannot be comment here
+                steering: None,
+                subtitles_group: None,
+                supplemental_codecs: None,
+                title: title.clone(),
+                // Left empty for closed captions, there is no playlist to fetch.
+                uri: alternative_stream.uri.to_owned().unwrap_or_default(),
+                video_range: None,
+                viewpoint: None,
+            });
         }
     }
 
     playlist::MasterPlaylist {
         playlist_type: playlist::PlaylistType::Hls,
+        steering: parse_content_steering(m3u8, uri, &streams),
         uri: uri.to_owned(),
         streams,
     }
 }
 
+/// Attribute list of a tag e.g. `URI="part.mp4",DURATION=0.5`, quotes are removed from values.
+pub(crate) fn parse_attributes(rest: &str) -> HashMap<String, String> {
+    static RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"([A-Z0-9-]+)=(?:"([^"]*)"|([^,]*))"#).unwrap());
+
+    RE.captures_iter(rest)
+        .map(|x| {
+            (
+                x[1].to_owned(),
+                x.get(2).or(x.get(3)).unwrap().as_str().to_owned(),
+            )
+        })
+        .collect()
+}
+
+/// #EXT-X-CONTENT-STEERING:SERVER-URI="/steering?video=00012",PATHWAY-ID="CDN-A"
+fn parse_content_steering(
+    m3u8: &m3u8_rs::MasterPlaylist,
+    uri: &str,
+    streams: &[playlist::MediaPlaylist],
+) -> Option<playlist::ContentSteering> {
+    let tag = m3u8
+        .unknown_tags
+        .iter()
+        .find(|x| x.tag == "X-CONTENT-STEERING")?;
+    let mut attributes = parse_attributes(tag.rest.as_ref()?);
+    let default_pathway = attributes.remove("PATHWAY-ID");
+    let server_uri = attributes.remove("SERVER-URI")?;
+    let mut pathways = vec![];
+
+    for stream in streams {
+        if stream.group_id.is_none() {
+            let pathway = stream
+                .pathway_id
+                .clone()
+                .unwrap_or(steering::DEFAULT_PATHWAY.to_owned());
+
+            if !pathways.contains(&pathway) {
+                pathways.push(pathway);
+            }
+        }
+    }
+
+    Some(playlist::ContentSteering {
+        default_pathway,
+        pathway: None,
+        pathway_priority: vec![],
+        pathways,
+        reload_uri: reqwest::Url::parse(uri)
+            .and_then(|x| x.join(&server_uri))
+            .ok()?
+            .to_string(),
+        ttl: steering::DEFAULT_TTL,
+    })
+}
+
 pub(crate) fn push_segments(m3u8: &m3u8_rs::MediaPlaylist, stream: &mut playlist::MediaPlaylist) {
     stream.i_frame = m3u8.i_frames_only;
     stream.live = !m3u8.end_list;
     stream.media_sequence = m3u8.media_sequence;
 
     let mut previous_byterange_end = 0;
+    let mut discontinuity = m3u8.discontinuity_sequence;
+    let mut program_date_time = None;
 
     for segment in &m3u8.segments {
+        if segment.discontinuity {
+            discontinuity += 1;
+        }
+
+        if segment.program_date_time.is_some() {
+            program_date_time = segment.program_date_time;
+        }
+
         let map = segment.map.as_ref().map(|x| playlist::Map {
             uri: x.uri.to_owned(),
             range: x.byte_range.as_ref().map(|x| {
@@ -178,6 +287,7 @@ pub(crate) fn push_segments(m3u8: &m3u8_rs::MediaPlaylist, stream: &mut playlist
         });
 
         stream.segments.push(playlist::Segment {
+            discontinuity,
             duration: segment.duration,
             key: if let Some(m3u8_rs::Key {
                 iv,
@@ -219,22 +329,62 @@ pub(crate) fn push_segments(m3u8: &m3u8_rs::MediaPlaylist, stream: &mut playlist
                     };
                 }
 
-                Some(playlist::Key {
+                let mut key = playlist::Key {
                     default_kid: None,
                     iv: iv.clone(),
                     key_format: keyformat.clone(),
                     method,
+                    pssh: vec![],
                     uri: uri.clone(),
-                })
+                };
+
+                // Widevine data uri is a pssh box whereas playready one is a playready object.
+                if let Some(data) = uri
+                    .as_ref()
+                    .filter(|x| x.starts_with("data:"))
+                    .and_then(|x| x.split_once(";base64,"))
+                    .and_then(|x| utils::decode_base64(x.1).ok())
+                {
+                    key.push_pssh(
+                        &data,
+                        keyformat.as_deref() == Some("com.microsoft.playready"),
+                    );
+                }
+
+                Some(key)
             } else {
                 None
             },
             map,
+            period_offset: None,
+            program_date_time,
             range,
             uri: segment.uri.to_owned(),
         });
+
+        program_date_time = program_date_time
+            .map(|x| x + chrono::Duration::milliseconds((segment.duration * 1000.0) as i64));
     }
 
+    // Segments before first EXT-X-PROGRAM-DATE-TIME tag are back filled.
+    if let Some(i) = m3u8
+        .segments
+        .iter()
+        .position(|x| x.program_date_time.is_some())
+    {
+        let offset = stream.segments.len() - m3u8.segments.len();
+
+        for j in (offset..(offset + i)).rev() {
+            let next = stream.segments[j + 1].program_date_time.unwrap();
+            let duration = stream.segments[j].duration;
+            stream.segments[j].program_date_time =
+                Some(next - chrono::Duration::milliseconds((duration * 1000.0) as i64));
+        }
+    }
+
+    stream.ad_breaks = parse_ad_breaks(&m3u8.segments);
+    stream.chapters = parse_chapters(&m3u8.segments, &stream.segments);
+
     if let Some(segment) = stream.segments.first() {
         if let Some(init) = &segment.map
             && init.uri.split('?').next().unwrap().ends_with(".mp4") {
@@ -248,3 +398,220 @@ pub(crate) fn push_segments(m3u8: &m3u8_rs::MediaPlaylist, stream: &mut playlist
         }
     }
 }
+
+/// Ad breaks marked by `EXT-X-CUE-OUT`/`EXT-X-CUE-IN` tags or `EXT-X-DATERANGE` tags
+/// having scte-35 attributes or interstitial class. Ad breaks which aren't closed explicitly
+/// are closed after their declared duration, or at the end of playlist.
+fn parse_ad_breaks(segments: &[m3u8_rs::MediaSegment]) -> Vec<playlist::AdBreak> {
+    let mut ad_breaks: Vec<playlist::AdBreak> = vec![];
+    let mut open: Vec<usize> = vec![];
+
+    let close_by_duration = |ad_break: &mut playlist::AdBreak, limit: usize| {
+        ad_break.end = limit;
+
+        if let Some(duration) = ad_break.duration {
+            let mut elapsed = 0.0;
+
+            for (i, segment) in segments.iter().enumerate().take(limit).skip(ad_break.start) {
+                // Small tolerance for rounded segment durations.
+                if elapsed >= duration - 0.5 {
+                    ad_break.end = i;
+                    break;
+                }
+
+                elapsed += segment.duration;
+            }
+        }
+    };
+
+    let open_break = |ad_breaks: &mut Vec<playlist::AdBreak>,
+                      open: &mut Vec<usize>,
+                      duration: Option<f32>,
+                      id: Option<String>,
+                      marker: playlist::AdMarker,
+                      start: usize| {
+        // Cue ad breaks can't be nested, previous one is closed after its duration.
+        if marker == playlist::AdMarker::Cue
+            && let Some(j) = open
+                .iter()
+                .position(|x| ad_breaks[*x].marker == playlist::AdMarker::Cue)
+        {
+            close_by_duration(&mut ad_breaks[open.remove(j)], start);
+        }
+
+        open.push(ad_breaks.len());
+        ad_breaks.push(playlist::AdBreak {
+            duration,
+            end: start,
+            id,
+            marker,
+            start,
+        });
+    };
+
+    for (i, segment) in segments.iter().enumerate() {
+        for tag in &segment.unknown_tags {
+            let rest = tag.rest.as_deref().unwrap_or_default();
+
+            match tag.tag.as_str() {
+                // #EXT-X-CUE-OUT:30 | #EXT-X-CUE-OUT:DURATION=30
+                "X-CUE-OUT" => {
+                    let duration = rest
+                        .trim_start_matches("DURATION=")
+                        .split(',')
+                        .next()
+                        .and_then(|x| x.parse().ok());
+                    open_break(
+                        &mut ad_breaks,
+                        &mut open,
+                        duration,
+                        None,
+                        playlist::AdMarker::Cue,
+                        i,
+                    );
+                }
+                // Playlist window may start in between an ad break.
+                // #EXT-X-CUE-OUT-CONT:ElapsedTime=10,Duration=30 | #EXT-X-CUE-OUT-CONT:10/30
+                "X-CUE-OUT-CONT"
+                    if !open
+                        .iter()
+                        .any(|x| ad_breaks[*x].marker == playlist::AdMarker::Cue) =>
+                {
+                    let (elapsed, duration) = match rest.split_once('/') {
+                        Some((elapsed, duration)) => {
+                            (elapsed.parse::<f32>().ok(), duration.parse::<f32>().ok())
+                        }
+                        None => {
+                            let value = |key: &str| {
+                                rest.split(',')
+                                    .filter_map(|x| x.split_once('='))
+                                    .find(|(x, _)| x.eq_ignore_ascii_case(key))
+                                    .and_then(|(_, x)| x.parse::<f32>().ok())
+                            };
+                            (value("ElapsedTime"), value("Duration"))
+                        }
+                    };
+                    open_break(
+                        &mut ad_breaks,
+                        &mut open,
+                        duration.map(|x| x - elapsed.unwrap_or(0.0)),
+                        None,
+                        playlist::AdMarker::Cue,
+                        i,
+                    );
+                }
+                "X-CUE-IN" => {
+                    if let Some(j) = open
+                        .iter()
+                        .position(|x| ad_breaks[*x].marker == playlist::AdMarker::Cue)
+                    {
+                        ad_breaks[open.remove(j)].end = i;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let Some(daterange) = &segment.daterange else {
+            continue;
+        };
+
+        let attributes = daterange.other_attributes.as_ref();
+        let has_attribute = |key: &str| attributes.is_some_and(|x| x.contains_key(key));
+
+        if daterange.class.as_deref() == Some("com.apple.hls.interstitial") {
+            ad_breaks.push(playlist::AdBreak {
+                duration: daterange
+                    .duration
+                    .or(daterange.planned_duration)
+                    .map(|x| x as f32),
+                end: i,
+                id: Some(daterange.id.to_owned()),
+                marker: playlist::AdMarker::Interstitial,
+                start: i,
+            });
+        } else if has_attribute("SCTE35-IN") {
+            if let Some(j) = open.iter().position(|x| {
+                ad_breaks[*x].marker == playlist::AdMarker::Scte35
+                    && ad_breaks[*x].id.as_ref() == Some(&daterange.id)
+            }) {
+                ad_breaks[open.remove(j)].end = i;
+            }
+        } else if has_attribute("SCTE35-OUT") {
+            open_break(
+                &mut ad_breaks,
+                &mut open,
+                daterange
+                    .duration
+                    .or(daterange.planned_duration)
+                    .map(|x| x as f32),
+                Some(daterange.id.to_owned()),
+                playlist::AdMarker::Scte35,
+                i,
+            );
+        }
+    }
+
+    for i in open {
+        close_by_duration(&mut ad_breaks[i], segments.len());
+    }
+
+    ad_breaks
+}
+
+/// Chapters from `EXT-X-DATERANGE` tags which aren't ad markers. Chapter starts from the segment
+/// containing its `START-DATE`, or from the segment it is declared on if playlist doesn't have
+/// program date time. Title is taken from `X-*TITLE` attribute, class or id.
+fn parse_chapters(
+    m3u8_segments: &[m3u8_rs::MediaSegment],
+    segments: &[playlist::Segment],
+) -> Vec<playlist::Chapter> {
+    let has_program_date_time = segments.iter().any(|x| x.program_date_time.is_some());
+    let mut chapters: Vec<playlist::Chapter> = vec![];
+    let mut ids = vec![];
+
+    for (i, segment) in m3u8_segments.iter().enumerate() {
+        let Some(daterange) = &segment.daterange else {
+            continue;
+        };
+
+        if ids.contains(&daterange.id)
+            || daterange.class.as_deref() == Some("com.apple.hls.interstitial")
+            || daterange
+                .other_attributes
+                .as_ref()
+                .is_some_and(|x| x.keys().any(|x| x.starts_with("SCTE35")))
+        {
+            continue;
+        }
+
+        let start = if has_program_date_time {
+            let Some(start) = segments.iter().position(|x| {
+                x.program_date_time.is_some_and(|y| {
+                    y + chrono::Duration::milliseconds((x.duration * 1000.0) as i64)
+                        > daterange.start_date
+                })
+            }) else {
+                continue;
+            };
+            start
+        } else {
+            i
+        };
+
+        ids.push(daterange.id.to_owned());
+        chapters.push(playlist::Chapter {
+            start,
+            title: daterange
+                .x_prefixed
+                .as_ref()
+                .and_then(|x| x.iter().find(|(k, _)| k.ends_with("TITLE")))
+                .map(|(_, x)| x.as_str().to_owned())
+                .or(daterange.class.clone())
+                .unwrap_or(daterange.id.to_owned()),
+        });
+    }
+
+    chapters.sort_by_key(|x| x.start);
+    chapters
+}
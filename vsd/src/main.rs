@@ -4,7 +4,9 @@ mod dash;
 mod downloader;
 mod hls;
 mod merger;
+mod output;
 mod playlist;
+mod steering;
 mod utils;
 mod automation;
 
@@ -29,7 +31,9 @@ fn run() -> anyhow::Result<()> {
     match args.command {
         #[cfg(feature = "browser")]
         Commands::Capture(args) => args.execute()?,
+        Commands::Decrypt(args) => args.execute()?,
         Commands::Extract(args) => args.execute()?,
+        Commands::Inspect(args) => args.execute()?,
         Commands::Merge(args) => args.execute()?,
         Commands::Save(args) => args.execute()?,
     }
@@ -6,6 +6,7 @@ pub struct Merger {
     merger_type: MergerType,
     pos: usize,
     size: usize,
+    sizes: Vec<usize>,
     stored_bytes: usize,
 }
 
@@ -21,6 +22,7 @@ impl Merger {
             merger_type: MergerType::File((fs::File::create(path)?, HashMap::new())),
             pos: 0,
             size: size - 1,
+            sizes: vec![0; size],
             stored_bytes: 0,
         })
     }
@@ -36,6 +38,7 @@ impl Merger {
             pos: 0,
             stored_bytes: 0,
             size: size - 1,
+            sizes: vec![0; size],
         })
     }
 
@@ -64,11 +67,15 @@ impl Merger {
     }
 
     pub fn estimate(&self) -> usize {
-        if self.indexed == 0 {
-            0
-        } else {
-            (self.stored_bytes / self.indexed) * (self.size + 1)
-        }
+        self.stored_bytes
+            .checked_div(self.indexed)
+            .map(|x| x * (self.size + 1))
+            .unwrap_or(0)
+    }
+
+    /// Byte size of every written segment in order.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
     }
 
     pub fn stored(&self) -> usize {
@@ -100,6 +107,7 @@ impl Merger {
             }
         };
 
+        self.sizes[pos] = buf.len();
         self.indexed += 1;
         Ok(())
     }
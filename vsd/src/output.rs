@@ -0,0 +1,158 @@
+use crate::playlist::MediaPlaylist;
+use anyhow::{Result, bail};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const PLACEHOLDERS: [&str; 11] = [
+    "bandwidth",
+    "codecs",
+    "ext",
+    "height",
+    "id",
+    "lang",
+    "name",
+    "resolution",
+    "title",
+    "type",
+    "width",
+];
+
+/// Output path template like `{title}/{lang}/{resolution}_{bandwidth}.{ext}`.
+#[derive(Clone, Debug)]
+pub struct OutputTemplate {
+    template: String,
+}
+
+impl OutputTemplate {
+    pub fn new(template: &str) -> Result<Self> {
+        for placeholder in placeholders(template) {
+            if !PLACEHOLDERS.contains(&placeholder) {
+                bail!(
+                    "unknown placeholder {{{}}} used in output template (supported: {}).",
+                    placeholder,
+                    PLACEHOLDERS
+                        .iter()
+                        .map(|x| format!("{{{x}}}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+
+        Ok(Self {
+            template: template.to_owned(),
+        })
+    }
+
+    /// Returns true if template resolves to a different path for every stream.
+    /// Templates which only depend upon `{title}` resolves to a single muxed output.
+    pub fn is_per_stream(&self) -> bool {
+        placeholders(&self.template).any(|x| x != "title")
+    }
+
+    pub fn render(&self, stream: &MediaPlaylist, ext: &str) -> PathBuf {
+        let mut path = String::new();
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|x| start + x) else {
+                break;
+            };
+
+            path.push_str(&rest[..start]);
+            let placeholder = &rest[(start + 1)..end];
+
+            if PLACEHOLDERS.contains(&placeholder) {
+                path.push_str(&sanitize(&value(stream, placeholder, ext)));
+            } else {
+                path.push_str(&rest[start..=end]);
+            }
+
+            rest = &rest[(end + 1)..];
+        }
+
+        path.push_str(rest);
+        PathBuf::from(path)
+    }
+}
+
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|x| x.split_once('}').map(|(x, _)| x))
+}
+
+fn value(stream: &MediaPlaylist, placeholder: &str, ext: &str) -> String {
+    match placeholder {
+        "bandwidth" => stream.bandwidth.map(|x| x.to_string()),
+        "codecs" => stream.codecs.clone(),
+        "ext" => Some(ext.to_owned()),
+        "height" => stream.resolution.map(|(_, h)| h.to_string()),
+        "id" => Some(stream.id.clone()),
+        "lang" => Some(stream.language.clone().unwrap_or("und".to_owned())),
+        "name" => stream.name.clone(),
+        "resolution" => stream.resolution.map(|(w, h)| format!("{w}x{h}")),
+        "title" => stream.title.clone(),
+        "type" => Some(stream.media_type.to_string()),
+        "width" => stream.resolution.map(|(w, _)| w.to_string()),
+        _ => None,
+    }
+    .unwrap_or("unknown".to_owned())
+}
+
+/// Replace characters which are not allowed in file names.
+pub fn sanitize(value: &str) -> String {
+    let value = value
+        .chars()
+        .map(|x| match x {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            x if x.is_control() => '_',
+            x => x,
+        })
+        .collect::<String>();
+    let value = value.trim_matches(|x: char| x == '.' || x.is_whitespace());
+
+    if value.is_empty() {
+        "unknown".to_owned()
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Returns a path which doesn't exists yet by appending `-1`, `-2`, etc. to the file stem.
+/// Parent directories are created if required.
+pub fn unique_path(path: PathBuf) -> Result<PathBuf> {
+    if let Some(parent) = path.parent()
+        && parent != Path::new("")
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
+    }
+
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+    let mut unique_path = path.clone();
+
+    for i in 1.. {
+        unique_path.set_file_name(format!("{stem}-{i}{ext}"));
+
+        if !unique_path.exists() {
+            break;
+        }
+    }
+
+    Ok(unique_path)
+}
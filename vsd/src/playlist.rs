@@ -1,4 +1,6 @@
 use anyhow::{Result, anyhow, bail};
+use base64::Engine;
+use chrono::{DateTime, FixedOffset};
 use kdam::term::Colorizer;
 use requestty::prompt::style::Stylize;
 use reqwest::{
@@ -8,42 +10,130 @@ use reqwest::{
 };
 use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::OsStr,
     fmt::Display,
     io::Write,
     path::PathBuf,
 };
+use vsd_mp4::pssh::{PLAYREADY_SYSTEM_ID, Pssh, PsshBox};
 
-use crate::automation::{Prompter, SelectOptions, VideoPreference};
+use crate::{
+    automation::{Prompter, RangePreference, SelectOptions, VideoPreference},
+    output::{self, OutputTemplate},
+    utils,
+};
 
 #[derive(Serialize)]
 pub struct MasterPlaylist {
     pub playlist_type: PlaylistType,
+    pub steering: Option<ContentSteering>,
     pub uri: String,
     pub streams: Vec<MediaPlaylist>,
 }
 
-#[derive(Default, Serialize)]
+/// HLS `EXT-X-CONTENT-STEERING` or DASH `ContentSteering` information.
+#[derive(Clone, Serialize)]
+pub struct ContentSteering {
+    /// Pathway (service location for DASH) to be used when steering manifest is unavailable.
+    pub default_pathway: Option<String>,
+    /// Pathway chosen from `PATHWAY-PRIORITY` of steering manifest.
+    pub pathway: Option<String>,
+    pub pathway_priority: Vec<String>,
+    /// All the pathways available inside playlist.
+    pub pathways: Vec<String>,
+    pub reload_uri: String,
+    /// Seconds after which steering manifest should be reloaded.
+    pub ttl: u64,
+}
+
+#[derive(Clone, Default, Serialize)]
 pub struct MediaPlaylist {
+    /// DASH `Accessibility` descriptor values e.g. `description`, `caption`.
+    pub accessibility: Vec<String>,
+    /// Ad breaks marked inside hls playlist.
+    pub ad_breaks: Vec<AdBreak>,
+    /// `AUDIO` group id which this variant stream is paired with.
+    pub audio_group: Option<String>,
+    pub autoselect: bool,
     pub bandwidth: Option<u64>,
     pub channels: Option<f32>,
+    /// Chapters from dash periods or hls `EXT-X-DATERANGE` tags.
+    pub chapters: Vec<Chapter>,
+    /// Comma separated uniform type identifiers e.g. `public.accessibility.describes-video`.
+    pub characteristics: Option<String>,
+    /// `CLOSED-CAPTIONS` group id which this variant stream is paired with.
+    pub closed_captions_group: Option<String>,
     pub codecs: Option<String>,
+    pub default: bool,
+    /// Dynamic range format e.g. `SDR`, `HDR10`, `HLG`, `Dolby Vision`.
+    pub dynamic_range: Option<String>,
     pub extension: Option<String>,
+    pub forced: bool,
     pub frame_rate: Option<f32>,
+    pub group_id: Option<String>,
+    pub hdcp_level: Option<String>,
     pub id: String,
     pub i_frame: bool,
+    /// Closed captions channel (`CC1` to `CC4` or `SERVICE1` to `SERVICE63`) carried
+    /// inside video stream, such streams don't have any segments.
+    pub instream_id: Option<String>,
     pub language: Option<String>,
     pub live: bool,
     pub media_sequence: u64,
     pub media_type: MediaType,
+    pub name: Option<String>,
+    /// Content steering pathway id (service location for DASH) of this stream.
+    pub pathway_id: Option<String>,
+    /// Stream uri (base url for DASH) for every pathway this stream is available on.
+    pub pathway_uris: BTreeMap<String, String>,
     pub playlist_type: PlaylistType,
     pub resolution: Option<(u64, u64)>,
+    /// DASH `Role` descriptor values e.g. `main`, `commentary`, `forced-subtitle`.
+    pub roles: Vec<String>,
     pub segments: Vec<Segment>,
+    pub steering: Option<ContentSteering>,
+    /// `SUBTITLES` group id which this variant stream is paired with.
+    pub subtitles_group: Option<String>,
+    pub supplemental_codecs: Option<String>,
+    pub title: Option<String>,
     pub uri: String,
+    /// Video range i.e. `SDR`, `PQ` or `HLG`.
+    pub video_range: Option<String>,
+    /// DASH `Viewpoint` descriptor value, used for alternate camera angles.
+    pub viewpoint: Option<String>,
+}
+
+/// Ad break which spans over `segments[start..end]`.
+#[derive(Clone, Serialize)]
+pub struct AdBreak {
+    /// Declared duration of ad break in seconds.
+    pub duration: Option<f32>,
+    pub end: usize,
+    pub id: Option<String>,
+    pub marker: AdMarker,
+    pub start: usize,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+pub enum AdMarker {
+    /// `EXT-X-CUE-OUT` and `EXT-X-CUE-IN` tags.
+    Cue,
+    /// `EXT-X-DATERANGE` tag with `CLASS="com.apple.hls.interstitial"`.
+    /// Interstitial assets are played separately, so it doesn't span over any segment.
+    Interstitial,
+    /// `EXT-X-DATERANGE` tags with `SCTE35-OUT` and `SCTE35-IN` attributes.
+    Scte35,
 }
 
-#[derive(Default, Serialize)]
+/// Chapter which starts from `segments[start]`.
+#[derive(Clone, Serialize)]
+pub struct Chapter {
+    pub start: usize,
+    pub title: String,
+}
+
+#[derive(Clone, Default, Serialize)]
 pub enum PlaylistType {
     Dash,
     #[default]
@@ -62,9 +152,19 @@ pub enum MediaType {
 #[derive(Clone, Default, Serialize)]
 pub struct Segment {
     pub range: Option<Range>,
+    /// Discontinuity sequence number, incremented after every `EXT-X-DISCONTINUITY` tag
+    /// (hls) or period (dash).
+    pub discontinuity: u64,
     pub duration: f32, // consider changing it to f64
     pub key: Option<Key>,
     pub map: Option<Map>,
+    /// Seconds to be added to media time of segment for getting its presentation time i.e.
+    /// start of its dash period minus `presentationTimeOffset` and duration of skipped
+    /// ad breaks before it.
+    pub period_offset: Option<f32>,
+    /// `EXT-X-PROGRAM-DATE-TIME` of segment, carried forward from previous segments
+    /// when it isn't specified.
+    pub program_date_time: Option<DateTime<FixedOffset>>,
     pub uri: String,
 }
 
@@ -80,6 +180,8 @@ pub struct Key {
     pub iv: Option<String>,
     pub key_format: Option<String>,
     pub method: KeyMethod,
+    /// Base64 encoded `pssh` boxes embedded inside playlist.
+    pub pssh: Vec<String>,
     pub uri: Option<String>,
 }
 
@@ -99,7 +201,7 @@ pub struct Map {
 }
 
 impl MasterPlaylist {
-    pub fn sort_streams(mut self) -> Self {
+    pub fn sort_streams(mut self, range_preference: &RangePreference) -> Self {
         let mut video_streams = vec![];
         let mut audio_streams = vec![];
         let subtitle_streams = vec![];
@@ -126,9 +228,16 @@ impl MasterPlaylist {
             }
         }
 
-        video_streams.sort_by(|x, y| y.1.cmp(&x.1));
-        video_streams.sort_by(|x, y| y.2.cmp(&x.2));
-        audio_streams.sort_by(|x, y| y.1.cmp(&x.1));
+        video_streams.sort_by_key(|x| std::cmp::Reverse(x.1));
+        video_streams.sort_by_key(|x| std::cmp::Reverse(x.2));
+
+        match range_preference {
+            RangePreference::Any => (),
+            RangePreference::Hdr => video_streams.sort_by_key(|x| !x.0.is_hdr()),
+            RangePreference::Sdr => video_streams.sort_by_key(|x| x.0.is_hdr()),
+        }
+
+        audio_streams.sort_by_key(|x| std::cmp::Reverse(x.1));
         audio_streams.sort_by(|x, y| y.2.total_cmp(&x.2));
 
         self.streams = video_streams
@@ -216,21 +325,32 @@ impl MasterPlaylist {
                 }
             }
 
+            let range_streams = video_streams
+                .iter()
+                .filter(|(_, x)| select_opts.video.matches_range(x))
+                .collect::<Vec<_>>();
+
             match &select_opts.video.preference {
                 VideoPreference::Best => {
-                    if let Some((i, _)) = video_streams.first() {
+                    if let Some((i, _)) = range_streams.first() {
                         selected_vstreams.insert(*i);
                     }
                 }
                 VideoPreference::None => (),
                 VideoPreference::Worst => {
-                    if let Some((i, _)) = video_streams.last() {
+                    if let Some((i, _)) = range_streams.last() {
                         selected_vstreams.insert(*i);
                     }
                 }
             };
 
             for (i, stream) in &video_streams {
+                if select_opts.video.matches(stream) {
+                    selected_vstreams.insert(*i);
+                }
+            }
+
+            for (i, stream) in &range_streams {
                 if let Some((w, h)) = &stream.resolution
                     && select_opts
                         .video
@@ -249,7 +369,7 @@ impl MasterPlaylist {
                 }
             } else if !select_opts.video.skip {
                 if selected_vstreams.is_empty()
-                    && let Some((i, _)) = video_streams.first()
+                    && let Some((i, _)) = range_streams.first()
                 {
                     selected_vstreams.insert(*i);
                 }
@@ -289,6 +409,20 @@ impl MasterPlaylist {
                 }
             }
 
+            for (i, stream) in &audio_streams {
+                if select_opts.audio.matches(stream) {
+                    selected_astreams.insert(*i);
+                }
+            }
+
+            if select_opts.audio.no_forced {
+                for (i, stream) in &audio_streams {
+                    if stream.forced {
+                        selected_astreams.remove(i);
+                    }
+                }
+            }
+
             if select_opts.audio.skip && !selected_astreams.is_empty() {
                 for (i, _) in &audio_streams {
                     if !selected_astreams.contains(i) {
@@ -297,9 +431,13 @@ impl MasterPlaylist {
                 }
             } else if !select_opts.audio.skip {
                 if selected_astreams.is_empty()
-                    && let Some((i, _)) = audio_streams.first()
+                    && let Some(i) = default_rendition(
+                        &audio_streams,
+                        &paired_groups(&video_streams, &selected_streams, |x| &x.audio_group),
+                        select_opts.audio.no_forced,
+                    )
                 {
-                    selected_astreams.insert(*i);
+                    selected_astreams.insert(i);
                 }
 
                 for i in selected_astreams {
@@ -337,6 +475,20 @@ impl MasterPlaylist {
                 }
             }
 
+            for (i, stream) in &sub_streams {
+                if select_opts.subs.matches(stream) {
+                    selected_sstreams.insert(*i);
+                }
+            }
+
+            if select_opts.subs.no_forced {
+                for (i, stream) in &sub_streams {
+                    if stream.forced {
+                        selected_sstreams.remove(i);
+                    }
+                }
+            }
+
             if select_opts.subs.skip && !selected_sstreams.is_empty() {
                 for (i, _) in &sub_streams {
                     if !selected_sstreams.contains(i) {
@@ -345,9 +497,13 @@ impl MasterPlaylist {
                 }
             } else if !select_opts.subs.skip {
                 if selected_sstreams.is_empty()
-                    && let Some((i, _)) = sub_streams.first()
+                    && let Some(i) = default_rendition(
+                        &sub_streams,
+                        &paired_groups(&video_streams, &selected_streams, |x| &x.subtitles_group),
+                        select_opts.subs.no_forced,
+                    )
                 {
-                    selected_sstreams.insert(*i);
+                    selected_sstreams.insert(i);
                 }
 
                 for i in selected_sstreams {
@@ -549,6 +705,45 @@ impl MasterPlaylist {
     }
 }
 
+/// Group ids of selected variant streams.
+fn paired_groups(
+    video_streams: &[(usize, MediaPlaylist)],
+    selected_streams: &HashSet<usize>,
+    group: fn(&MediaPlaylist) -> &Option<String>,
+) -> HashSet<String> {
+    video_streams
+        .iter()
+        .filter(|(i, _)| selected_streams.contains(i))
+        .filter_map(|(_, x)| group(x).clone())
+        .collect()
+}
+
+/// Stream to be selected when no filter matches, streams paired with selected variant
+/// streams and then streams marked as default are preferred.
+fn default_rendition(
+    streams: &[(usize, MediaPlaylist)],
+    groups: &HashSet<String>,
+    no_forced: bool,
+) -> Option<usize> {
+    let streams = streams
+        .iter()
+        .filter(|(_, x)| !(no_forced && x.forced))
+        .collect::<Vec<_>>();
+    let paired = streams
+        .iter()
+        .copied()
+        .filter(|(_, x)| x.group_id.as_ref().is_some_and(|y| groups.contains(y)))
+        .collect::<Vec<_>>();
+
+    paired
+        .iter()
+        .find(|(_, x)| x.default)
+        .or(paired.first())
+        .or(streams.iter().find(|(_, x)| x.default))
+        .or(streams.first())
+        .map(|(i, _)| *i)
+}
+
 impl MediaPlaylist {
     pub fn default_kid(&self) -> Option<String> {
         if let Some(Segment {
@@ -586,6 +781,8 @@ impl MediaPlaylist {
             extra += &format!(", channels: {channels}");
         }
 
+        extra += &self.display_rendition();
+
         if self.live {
             extra += ", live";
         }
@@ -603,6 +800,53 @@ impl MediaPlaylist {
             extra += &format!(", codecs: {codecs}");
         }
 
+        if let Some(instream_id) = &self.instream_id {
+            extra += &format!(", instream-id: {instream_id}");
+        }
+
+        extra += &self.display_rendition();
+        extra
+    }
+
+    fn display_rendition(&self) -> String {
+        let mut extra = String::new();
+
+        if let Some(name) = &self.name {
+            extra += &format!(", name: {name}");
+        }
+
+        if let Some(group_id) = &self.group_id {
+            extra += &format!(", group: {group_id}");
+        }
+
+        if let Some(characteristics) = &self.characteristics {
+            extra += &format!(", characteristics: {characteristics}");
+        }
+
+        if !self.roles.is_empty() {
+            extra += &format!(", role: {}", self.roles.join("+"));
+        }
+
+        if !self.accessibility.is_empty() {
+            extra += &format!(", accessibility: {}", self.accessibility.join("+"));
+        }
+
+        if let Some(viewpoint) = &self.viewpoint {
+            extra += &format!(", viewpoint: {viewpoint}");
+        }
+
+        if let Some(pathway_id) = &self.pathway_id {
+            extra += &format!(", pathway: {pathway_id}");
+        }
+
+        if self.default {
+            extra += ", default";
+        }
+
+        if self.forced {
+            extra += ", forced";
+        }
+
         extra
     }
 
@@ -636,10 +880,32 @@ impl MediaPlaylist {
             self.codecs.as_ref().unwrap_or(&"?".to_owned())
         );
 
+        if let Some(supplemental_codecs) = &self.supplemental_codecs {
+            extra += &format!(", supplemental_codecs: {supplemental_codecs}");
+        }
+
+        if let Some(video_range) = &self.video_range {
+            extra += &format!(", range: {video_range}");
+        }
+
+        if let Some(dynamic_range) = &self.dynamic_range {
+            extra += &format!(", dynamic_range: {dynamic_range}");
+        }
+
+        if let Some(hdcp_level) = &self.hdcp_level {
+            extra += &format!(", hdcp: {hdcp_level}");
+        }
+
         if let Some(frame_rate) = self.frame_rate {
             extra += &format!(", frame_rate: {frame_rate}");
         }
 
+        if let Some(audio_group) = &self.audio_group {
+            extra += &format!(", audio: {audio_group}");
+        }
+
+        extra += &self.display_rendition();
+
         if self.i_frame {
             extra += ", iframe";
         }
@@ -656,6 +922,11 @@ impl MediaPlaylist {
         )
     }
 
+    /// Returns true for `PQ` and `HLG` video ranges.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.video_range.as_deref(), Some("PQ") | Some("HLG"))
+    }
+
     pub fn display_stream(&self) -> String {
         match self.media_type {
             MediaType::Audio => self.display_audio_stream(),
@@ -741,37 +1012,196 @@ impl MediaPlaylist {
         OsStr::new(ext)
     }
 
-    pub fn path(&self, directory: Option<&PathBuf>, ext: &OsStr) -> PathBuf {
-        let prefix = match &self.media_type {
-            MediaType::Audio => "vsd-audio",
-            MediaType::Subtitles => "vsd-subtitles",
-            MediaType::Undefined => "vsd-undefined",
-            MediaType::Video => "vsd-video",
+    pub fn path(
+        &self,
+        directory: Option<&PathBuf>,
+        ext: &OsStr,
+        template: Option<&OutputTemplate>,
+    ) -> Result<PathBuf> {
+        let mut path = match template {
+            Some(template) => template.render(self, &ext.to_string_lossy()),
+            None => {
+                let prefix = match &self.media_type {
+                    MediaType::Audio => "vsd-audio",
+                    MediaType::Subtitles => "vsd-subtitles",
+                    MediaType::Undefined => "vsd-undefined",
+                    MediaType::Video => "vsd-video",
+                };
+
+                PathBuf::from(format!("{}-{}.{}", prefix, self.id, ext.to_string_lossy()))
+            }
         };
 
-        let mut path = PathBuf::from(format!("{}-{}.{}", prefix, self.id, ext.to_string_lossy()));
-
-        if let Some(directory) = directory {
+        if let Some(directory) = directory
+            && path.is_relative()
+        {
             path = directory.join(path);
         }
 
-        if path.exists() {
-            for i in 1.. {
-                path.set_file_name(format!(
-                    "{}-{}-{}.{}",
-                    prefix,
-                    self.id,
-                    i,
-                    ext.to_string_lossy()
-                ));
+        output::unique_path(path)
+    }
+
+    pub fn has_discontinuities(&self) -> bool {
+        self.segments.first().map(|x| x.discontinuity)
+            != self.segments.last().map(|x| x.discontinuity)
+    }
 
-                if !path.exists() {
-                    return path;
+    /// Split stream into multiple streams, one for every discontinuity block.
+    /// Ids of splitted streams are suffixed with their discontinuity sequence number.
+    pub fn split_discontinuities(mut self) -> Vec<MediaPlaylist> {
+        if !self.has_discontinuities() {
+            return vec![self];
+        }
+
+        let segments = std::mem::take(&mut self.segments);
+        let mut blocks: Vec<MediaPlaylist> = vec![];
+        let mut key = None;
+        let mut map = None;
+
+        for mut segment in segments {
+            key = segment.key.clone().or(key);
+            map = segment.map.clone().or(map);
+
+            match blocks.last_mut() {
+                Some(block) if block.segments[0].discontinuity == segment.discontinuity => {
+                    block.segments.push(segment)
+                }
+                _ => {
+                    // Key and map are only present on the segment they are declared on.
+                    segment.key = segment.key.or(key.clone());
+                    segment.map = segment.map.or(map.clone());
+                    blocks.push(MediaPlaylist {
+                        ad_breaks: vec![],
+                        chapters: vec![],
+                        id: format!("{}-{}", self.id, segment.discontinuity),
+                        segments: vec![segment],
+                        ..self.clone()
+                    });
                 }
             }
         }
 
-        path
+        blocks
+    }
+
+    /// Only keep segments which overlap with wall clock time range, using their program date time.
+    /// Returns false if stream doesn't have any program date time.
+    pub fn trim_to_wall_clock(
+        &mut self,
+        start: Option<&DateTime<FixedOffset>>,
+        end: Option<&DateTime<FixedOffset>>,
+    ) -> bool {
+        if self.segments.iter().all(|x| x.program_date_time.is_none()) {
+            return false;
+        }
+
+        self.retain_segments(|_, segment| {
+            let Some(pdt) = segment.program_date_time else {
+                return false;
+            };
+            let segment_end =
+                pdt + chrono::Duration::milliseconds((segment.duration * 1000.0) as i64);
+
+            !(start.is_some_and(|x| segment_end <= *x) || end.is_some_and(|x| pdt >= *x))
+        });
+        true
+    }
+
+    /// Remove segments which are inside ad breaks and returns number of removed segments.
+    /// Segments after a removed ad break are moved to a new discontinuity sequence, so that
+    /// their timestamps are reset while muxing, and their period offset is moved back by
+    /// duration of removed segments, so that subtitle cues stay in sync.
+    pub fn skip_ads(&mut self) -> usize {
+        let mut inside = vec![false; self.segments.len()];
+
+        for ad_break in &self.ad_breaks {
+            for x in inside.iter_mut().take(ad_break.end).skip(ad_break.start) {
+                *x = true;
+            }
+        }
+
+        let total = self.segments.len();
+        let mut removed = false;
+        let mut removed_duration = 0.0;
+        let mut shift = 0;
+
+        self.retain_segments(|i, segment| {
+            if inside[i] {
+                removed = true;
+                removed_duration += segment.duration;
+                return false;
+            }
+
+            if removed {
+                removed = false;
+                shift += 1;
+            }
+
+            if shift > 0 {
+                segment.period_offset =
+                    Some(segment.period_offset.unwrap_or(0.0) - removed_duration);
+            }
+
+            segment.discontinuity += shift;
+            true
+        });
+        self.ad_breaks.clear();
+        total - self.segments.len()
+    }
+
+    /// Key and map are only present on the segment they are declared on,
+    /// so they are carried forward to the retained segment after removed ones.
+    /// Ad breaks and chapters are moved to the retained segments.
+    fn retain_segments(&mut self, mut f: impl FnMut(usize, &mut Segment) -> bool) {
+        let segments = std::mem::take(&mut self.segments);
+        let mut key = None;
+        let mut map = None;
+        let mut carry = false;
+        // Number of retained segments before i-th segment.
+        let mut retained_before = vec![0; segments.len() + 1];
+
+        for (i, mut segment) in segments.into_iter().enumerate() {
+            key = segment.key.clone().or(key);
+            map = segment.map.clone().or(map);
+
+            if !f(i, &mut segment) {
+                retained_before[i + 1] = self.segments.len();
+                carry = true;
+                continue;
+            }
+
+            if carry {
+                carry = false;
+                segment.key = segment.key.or(key.clone());
+                segment.map = segment.map.or(map.clone());
+            }
+
+            self.segments.push(segment);
+            retained_before[i + 1] = self.segments.len();
+        }
+
+        for ad_break in &mut self.ad_breaks {
+            ad_break.start = retained_before[ad_break.start];
+            ad_break.end = retained_before[ad_break.end];
+        }
+
+        let total = self.segments.len();
+        let mut chapters: Vec<Chapter> = vec![];
+
+        for mut chapter in std::mem::take(&mut self.chapters) {
+            chapter.start = retained_before[chapter.start];
+
+            // Chapter whose every segment is removed is replaced by next one.
+            if chapters.last().is_some_and(|x| x.start == chapter.start) {
+                chapters.pop();
+            }
+
+            if chapter.start < total {
+                chapters.push(chapter);
+            }
+        }
+
+        self.chapters = chapters;
     }
 
     pub fn split_segment(
@@ -821,6 +1251,80 @@ impl MediaPlaylist {
     }
 }
 
+/// Codecs and supplemental codecs of a variant stream, e.g. `hvc1.2.4.L150.B0`
+/// and `dvh1.08.07/db4h` (brand after `/`).
+fn codec_entries<'a>(
+    codecs: Option<&'a str>,
+    supplemental_codecs: Option<&'a str>,
+) -> impl Iterator<Item = &'a str> {
+    codecs
+        .into_iter()
+        .chain(supplemental_codecs)
+        .flat_map(|x| x.split(','))
+        .map(|x| x.trim())
+}
+
+fn is_dolby_vision(codec: &str) -> bool {
+    matches!(
+        codec.split('.').next(),
+        Some("dav1") | Some("dva1") | Some("dvav") | Some("dvh1") | Some("dvhe")
+    )
+}
+
+/// Video range (`SDR`, `PQ` or `HLG`) either signalled by playlist or inferred from dolby vision codecs.
+pub fn video_range(
+    codecs: Option<&str>,
+    supplemental_codecs: Option<&str>,
+    signalled: Option<&str>,
+) -> Option<String> {
+    if let Some(signalled) = signalled {
+        return Some(signalled.to_uppercase());
+    }
+
+    // https://professionalsupport.dolby.com/s/article/What-is-Dolby-Vision-Profile
+    codec_entries(codecs, supplemental_codecs)
+        .find(|x| is_dolby_vision(x))
+        .map(|x| match x.split_once('/').map(|x| x.1) {
+            Some("db2g") => "SDR".to_owned(),
+            Some("db4h") => "HLG".to_owned(),
+            _ => "PQ".to_owned(),
+        })
+}
+
+/// Dynamic range format (`SDR`, `HDR10`, `HLG` or `Dolby Vision`).
+pub fn dynamic_range(
+    codecs: Option<&str>,
+    supplemental_codecs: Option<&str>,
+    video_range: Option<&str>,
+) -> Option<String> {
+    if codec_entries(codecs, supplemental_codecs).any(is_dolby_vision) {
+        return Some("Dolby Vision".to_owned());
+    }
+
+    match video_range {
+        Some("HLG") => Some("HLG".to_owned()),
+        Some("PQ") => Some("HDR10".to_owned()),
+        Some("SDR") => Some("SDR".to_owned()),
+        _ => None,
+    }
+}
+
+/// Guess a title from the last path segment of playlist url.
+pub fn title_from_uri(uri: &str) -> Option<String> {
+    let url = uri.parse::<Url>().ok()?;
+    let file_name = url.path_segments()?.next_back()?;
+    let title = file_name
+        .rsplit_once('.')
+        .map(|(x, _)| x)
+        .unwrap_or(file_name);
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.to_owned())
+    }
+}
+
 impl Display for MediaType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -836,6 +1340,22 @@ impl Display for MediaType {
     }
 }
 
+impl Display for KeyMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Aes128 => "aes-128",
+                Self::Mp4Decrypt => "mp4decrypt",
+                Self::None => "none",
+                Self::Other(x) => x,
+                Self::SampleAes => "sample-aes",
+            }
+        )
+    }
+}
+
 impl Range {
     pub fn as_header_value(&self) -> HeaderValue {
         HeaderValue::from_str(&format!("bytes={}-{}", self.start, self.end)).unwrap()
@@ -843,6 +1363,39 @@ impl Range {
 }
 
 impl Key {
+    /// Add drm init data embedded inside playlist. Playready objects (`mspr:pro`) are
+    /// wrapped inside a `pssh` box, other data which isn't a `pssh` box is ignored.
+    pub fn push_pssh(&mut self, data: &[u8], playready: bool) {
+        let pssh = if data.get(4..8) == Some(b"pssh") {
+            base64::engine::general_purpose::STANDARD.encode(data)
+        } else if playready {
+            match PsshBox::builder(PLAYREADY_SYSTEM_ID)
+                .data(data.to_vec())
+                .build()
+                .and_then(|x| x.to_base64())
+            {
+                Ok(x) => x,
+                Err(_) => return,
+            }
+        } else {
+            return;
+        };
+
+        if !self.pssh.contains(&pssh) {
+            self.pssh.push(pssh);
+        }
+    }
+
+    /// Parsed `pssh` boxes embedded inside playlist, invalid boxes are skipped.
+    pub fn pssh_boxes(&self) -> Vec<PsshBox> {
+        self.pssh
+            .iter()
+            .filter_map(|x| utils::decode_base64(x).ok())
+            .filter_map(|x| Pssh::new(&x).ok())
+            .flat_map(|x| x.boxes)
+            .collect()
+    }
+
     pub fn key(&self, bytes: &[u8]) -> Result<[u8; 16]> {
         if bytes.len() != 16 {
             bail!("invalid key size.");
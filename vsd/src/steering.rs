@@ -0,0 +1,502 @@
+/*
+    REFERENCES
+    ----------
+
+    1. https://datatracker.ietf.org/doc/html/draft-pantos-hls-rfc8216bis#section-7
+    2. https://dashif.org/docs/DASH-IF-CTS-00XX-Content-Steering-Community-Review.pdf
+
+*/
+
+use crate::playlist::{ContentSteering, MasterPlaylist, MediaPlaylist, PlaylistType};
+use anyhow::{Result, bail};
+use kdam::term::Colorizer;
+use reqwest::{Url, blocking::Client};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Pathway id of HLS variant streams which doesn't specify one.
+pub const DEFAULT_PATHWAY: &str = ".";
+/// Used when steering manifest couldn't be fetched.
+pub const DEFAULT_TTL: u64 = 300;
+
+#[derive(Deserialize)]
+struct SteeringManifest {
+    #[serde(rename = "VERSION")]
+    version: u8,
+    #[serde(rename = "TTL", default = "default_ttl")]
+    ttl: u64,
+    #[serde(rename = "RELOAD-URI")]
+    reload_uri: Option<String>,
+    #[serde(
+        rename = "PATHWAY-PRIORITY",
+        alias = "SERVICE-LOCATION-PRIORITY",
+        default
+    )]
+    pathway_priority: Vec<String>,
+}
+
+fn default_ttl() -> u64 {
+    DEFAULT_TTL
+}
+
+fn fetch_manifest(
+    client: &Client,
+    steering: &ContentSteering,
+    pathway: Option<&str>,
+    playlist_type: &PlaylistType,
+    query: &HashMap<String, String>,
+) -> Result<SteeringManifest> {
+    let mut url = steering.reload_uri.parse::<Url>()?;
+
+    if let Some(pathway) = pathway {
+        let key = match playlist_type {
+            PlaylistType::Dash => "_DASH_pathway",
+            PlaylistType::Hls => "_HLS_pathway",
+        };
+        url.query_pairs_mut().append_pair(key, pathway);
+    }
+
+    let response = client.get(url.clone()).query(query).send()?;
+    let status = response.status();
+
+    if !status.is_success() {
+        bail!(
+            "failed to fetch steering manifest {} (HTTP {}).",
+            url,
+            status
+        );
+    }
+
+    let manifest = serde_json::from_slice::<SteeringManifest>(&response.bytes()?)?;
+
+    if manifest.version != 1 {
+        bail!(
+            "unsupported steering manifest version {}.",
+            manifest.version
+        );
+    }
+
+    Ok(manifest)
+}
+
+fn update(steering: &mut ContentSteering, manifest: SteeringManifest) -> Result<()> {
+    if let Some(reload_uri) = &manifest.reload_uri {
+        steering.reload_uri = steering
+            .reload_uri
+            .parse::<Url>()?
+            .join(reload_uri)?
+            .to_string();
+    }
+
+    steering.pathway_priority = manifest.pathway_priority;
+    steering.ttl = manifest.ttl.max(1);
+    Ok(())
+}
+
+/// Highest priority pathway which is also available, falls back to default pathway.
+fn preferred_pathway(steering: &ContentSteering, available: &[String]) -> Option<String> {
+    steering
+        .pathway_priority
+        .iter()
+        .chain(&steering.default_pathway)
+        .find(|x| available.contains(x))
+        .or(available.first())
+        .cloned()
+}
+
+/// Fetch steering manifest and only keep streams of preferred pathway.
+pub fn apply(
+    playlist: &mut MasterPlaylist,
+    client: &Client,
+    query: &HashMap<String, String>,
+) -> Result<()> {
+    let Some(steering) = &mut playlist.steering else {
+        return Ok(());
+    };
+
+    match fetch_manifest(client, steering, None, &playlist.playlist_type, query) {
+        Ok(manifest) => update(steering, manifest)?,
+        Err(e) => println!(
+            "    {} couldn't fetch steering manifest, using default pathway ({})",
+            "Warning".colorize("yellow"),
+            e
+        ),
+    }
+
+    let Some(pathway) = preferred_pathway(steering, &steering.pathways) else {
+        return Ok(());
+    };
+
+    steering.pathway = Some(pathway.clone());
+    let steering = steering.clone();
+    let streams = std::mem::take(&mut playlist.streams);
+
+    playlist.streams = match playlist.playlist_type {
+        PlaylistType::Dash => streams
+            .into_iter()
+            .map(|mut x| {
+                x.pathway_id = Some(pathway.clone());
+                x
+            })
+            .collect(),
+        PlaylistType::Hls => retain_hls_pathway(streams, &pathway),
+    };
+
+    for stream in &mut playlist.streams {
+        stream.steering = Some(steering.clone());
+    }
+
+    Ok(())
+}
+
+/// Pathways are cloned variant streams, so k-th variant stream of every pathway is expected
+/// to be same. Renditions are matched by name and language within the paired groups.
+fn retain_hls_pathway(streams: Vec<MediaPlaylist>, pathway: &str) -> Vec<MediaPlaylist> {
+    let pathway_of = |x: &MediaPlaylist| x.pathway_id.clone().unwrap_or(DEFAULT_PATHWAY.to_owned());
+
+    let mut variants: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+    for (i, stream) in streams.iter().enumerate() {
+        if stream.group_id.is_none() {
+            variants.entry(pathway_of(stream)).or_default().push(i);
+        }
+    }
+
+    let Some(chosen) = variants.get(pathway).cloned() else {
+        return streams;
+    };
+
+    let mut pathway_uris = HashMap::new();
+    let mut group_map = HashMap::new();
+
+    for (k, i) in chosen.iter().enumerate() {
+        let mut uris = BTreeMap::from([(pathway.to_owned(), streams[*i].uri.clone())]);
+
+        for (other, indexes) in &variants {
+            if other == pathway {
+                continue;
+            }
+
+            if let Some(j) = indexes.get(k) {
+                uris.insert(other.to_owned(), streams[*j].uri.clone());
+
+                for (group, other_group) in [
+                    (&streams[*i].audio_group, &streams[*j].audio_group),
+                    (&streams[*i].subtitles_group, &streams[*j].subtitles_group),
+                ] {
+                    if let (Some(group), Some(other_group)) = (group, other_group) {
+                        group_map.insert((other.to_owned(), group.to_owned()), other_group);
+                    }
+                }
+            }
+        }
+
+        pathway_uris.insert(*i, uris);
+    }
+
+    let groups = |indexes: &[usize]| {
+        indexes
+            .iter()
+            .flat_map(|x| [&streams[*x].audio_group, &streams[*x].subtitles_group])
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>()
+    };
+    let kept_groups = groups(&chosen);
+    let removed_groups = variants
+        .iter()
+        .filter(|(x, _)| *x != pathway)
+        .flat_map(|(_, x)| groups(x))
+        .filter(|x| !kept_groups.contains(x))
+        .collect::<HashSet<_>>();
+
+    for (i, stream) in streams.iter().enumerate() {
+        let Some(group_id) = &stream.group_id else {
+            continue;
+        };
+
+        if removed_groups.contains(group_id) {
+            continue;
+        }
+
+        let mut uris = BTreeMap::from([(pathway.to_owned(), stream.uri.clone())]);
+
+        for other in variants.keys().filter(|x| *x != pathway) {
+            if let Some(other_group) = group_map.get(&(other.to_owned(), group_id.to_owned()))
+                && let Some(rendition) = streams.iter().find(|x| {
+                    x.group_id.as_ref() == Some(*other_group)
+                        && x.name == stream.name
+                        && x.language == stream.language
+                })
+            {
+                uris.insert(other.to_owned(), rendition.uri.clone());
+            }
+        }
+
+        pathway_uris.insert(i, uris);
+    }
+
+    streams
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, mut x)| {
+            x.pathway_uris = pathway_uris.remove(&i)?;
+            x.pathway_id = Some(pathway.to_owned());
+            Some(x)
+        })
+        .collect()
+}
+
+/// Content steering state of a stream which is being downloaded.
+/// Steering manifest is reloaded after every TTL and segment urls are rebased to the pathway
+/// which is preferred at that moment.
+#[derive(Clone)]
+pub struct Steering {
+    client: Client,
+    initial_pathway: String,
+    pathway: String,
+    pathway_uris: BTreeMap<String, String>,
+    playlist_type: PlaylistType,
+    query: HashMap<String, String>,
+    reloaded_at: Instant,
+    steering: ContentSteering,
+}
+
+impl Steering {
+    pub fn new(
+        client: &Client,
+        query: &HashMap<String, String>,
+        stream: &MediaPlaylist,
+    ) -> Option<Self> {
+        let steering = stream.steering.clone()?;
+        let pathway = stream.pathway_id.clone()?;
+
+        if stream.pathway_uris.len() < 2 {
+            return None;
+        }
+
+        Some(Self {
+            client: client.clone(),
+            initial_pathway: pathway.clone(),
+            pathway,
+            pathway_uris: stream.pathway_uris.clone(),
+            playlist_type: stream.playlist_type.clone(),
+            query: query.clone(),
+            reloaded_at: Instant::now(),
+            steering,
+        })
+    }
+
+    pub fn pathway(&self) -> &str {
+        &self.pathway
+    }
+
+    fn reload(&mut self) {
+        let Ok(manifest) = fetch_manifest(
+            &self.client,
+            &self.steering,
+            Some(&self.pathway),
+            &self.playlist_type,
+            &self.query,
+        ) else {
+            return;
+        };
+
+        if update(&mut self.steering, manifest).is_ok() {
+            let available = self.pathway_uris.keys().cloned().collect::<Vec<_>>();
+
+            if let Some(pathway) = preferred_pathway(&self.steering, &available) {
+                self.pathway = pathway;
+            }
+        }
+    }
+
+    /// Rebase url (built for the initial pathway) to currently preferred pathway.
+    /// Steering manifest is reloaded on a copy of the state without holding the lock,
+    /// so that segment downloads of other threads aren't blocked meanwhile.
+    pub fn rebase(steering: &Mutex<Self>, url: &Url) -> Url {
+        let expired = {
+            let mut steering = steering.lock().unwrap();
+
+            if steering.reloaded_at.elapsed() >= Duration::from_secs(steering.steering.ttl) {
+                // Only the first thread to find it expired reloads it.
+                steering.reloaded_at = Instant::now();
+                Some(steering.clone())
+            } else {
+                None
+            }
+        };
+
+        if let Some(mut reloaded) = expired {
+            reloaded.reload();
+            *steering.lock().unwrap() = reloaded;
+        }
+
+        steering.lock().unwrap().rebase_url(url)
+    }
+
+    fn rebase_url(&self, url: &Url) -> Url {
+        if self.pathway == self.initial_pathway {
+            return url.clone();
+        }
+
+        if let (Some(from), Some(to)) = (
+            self.pathway_uris.get(&self.initial_pathway),
+            self.pathway_uris.get(&self.pathway),
+        ) {
+            let from = from.split('?').next().unwrap();
+            let from = &from[..from.rfind('/').map(|x| x + 1).unwrap_or(0)];
+
+            if let Some(path) = url.as_str().strip_prefix(from)
+                && let Ok(to) = to.parse::<Url>()
+                && let Ok(mut url) = to.join(path)
+            {
+                // Query of pathway uri (e.g. cdn token) isn't kept by join, so it is added back
+                // and replaces same query parameters of segment uri.
+                if to.query().is_some() {
+                    let mut pairs = url
+                        .query_pairs()
+                        .into_owned()
+                        .filter(|(x, _)| to.query_pairs().all(|(y, _)| *x != y))
+                        .collect::<Vec<_>>();
+                    pairs.extend(to.query_pairs().into_owned());
+                    url.query_pairs_mut().clear().extend_pairs(pairs);
+                }
+
+                return url;
+            }
+        }
+
+        url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    /// Serve steering manifest from a local server, request lines are sent back through channel.
+    fn serve(manifest: &'static str) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 4096];
+                let size = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..size]);
+                let _ = sender.send(request.lines().next().unwrap_or_default().to_owned());
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    manifest.len(),
+                    manifest
+                );
+            }
+        });
+
+        (format!("http://{address}/steering.json"), receiver)
+    }
+
+    fn content_steering(reload_uri: String) -> ContentSteering {
+        ContentSteering {
+            default_pathway: Some("A".to_owned()),
+            pathway: None,
+            pathway_priority: vec![],
+            pathways: vec!["A".to_owned(), "B".to_owned()],
+            reload_uri,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    fn variant(pathway: &str, uri: &str) -> MediaPlaylist {
+        MediaPlaylist {
+            pathway_id: Some(pathway.to_owned()),
+            uri: uri.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply_pathway_priority() {
+        let (reload_uri, requests) =
+            serve(r#"{"VERSION":1,"TTL":10,"PATHWAY-PRIORITY":["B","A"]}"#);
+        let mut playlist = MasterPlaylist {
+            playlist_type: PlaylistType::Hls,
+            steering: Some(content_steering(reload_uri)),
+            uri: "https://a.example.com/master.m3u8".to_owned(),
+            streams: vec![
+                variant("A", "https://a.example.com/video/main.m3u8"),
+                variant("B", "https://b.example.com/video/main.m3u8"),
+            ],
+        };
+
+        apply(&mut playlist, &Client::new(), &HashMap::new()).unwrap();
+
+        assert_eq!(requests.recv().unwrap(), "GET /steering.json HTTP/1.1");
+        assert_eq!(playlist.streams.len(), 1);
+        assert_eq!(
+            playlist.streams[0].uri,
+            "https://b.example.com/video/main.m3u8"
+        );
+        assert_eq!(playlist.streams[0].pathway_uris.len(), 2);
+
+        let steering = playlist.streams[0].steering.as_ref().unwrap();
+        assert_eq!(steering.pathway.as_deref(), Some("B"));
+        assert_eq!(steering.ttl, 10);
+    }
+
+    #[test]
+    fn rebase_after_ttl() {
+        let (reload_uri, requests) =
+            serve(r#"{"VERSION":1,"TTL":300,"PATHWAY-PRIORITY":["B","A"]}"#);
+        let mut steering = content_steering(reload_uri);
+        steering.ttl = 0;
+        let mut stream = variant("A", "https://a.example.com/video/main.m3u8");
+        stream.steering = Some(steering);
+        stream.pathway_uris = BTreeMap::from([
+            ("A".to_owned(), stream.uri.clone()),
+            (
+                "B".to_owned(),
+                "https://b.example.com/v/main.m3u8?token=1".to_owned(),
+            ),
+        ]);
+
+        let steering = Mutex::new(Steering::new(&Client::new(), &HashMap::new(), &stream).unwrap());
+        let url = "https://a.example.com/video/seg-1.ts"
+            .parse::<Url>()
+            .unwrap();
+
+        assert_eq!(
+            Steering::rebase(&steering, &url).as_str(),
+            "https://b.example.com/v/seg-1.ts?token=1"
+        );
+        assert_eq!(
+            requests.recv().unwrap(),
+            "GET /steering.json?_HLS_pathway=A HTTP/1.1"
+        );
+        assert_eq!(steering.lock().unwrap().pathway(), "B");
+
+        // Manifest isn't reloaded again before its ttl.
+        let url = "https://a.example.com/video/seg-2.ts?part=1&token=0"
+            .parse::<Url>()
+            .unwrap();
+        assert_eq!(
+            Steering::rebase(&steering, &url).as_str(),
+            "https://b.example.com/v/seg-2.ts?part=1&token=1"
+        );
+        assert!(requests.try_recv().is_err());
+    }
+}
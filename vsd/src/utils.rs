@@ -1,6 +1,6 @@
 use anyhow::Result;
 use base64::Engine;
-use std::{env, path::PathBuf};
+use std::{cmp::Ordering, env, path::PathBuf};
 
 pub fn decode_base64<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
     base64::engine::general_purpose::STANDARD
@@ -8,6 +8,55 @@ pub fn decode_base64<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
         .map_err(|x| x.into())
 }
 
+/// Decode text (subtitles) as utf-8.
+/// Utf-16 (with or without bom) and legacy code pages are detected and converted.
+pub fn decode_text(data: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(data) {
+        return encoding
+            .decode_without_bom_handling(&data[bom_length..])
+            .0
+            .into_owned();
+    }
+
+    // Mostly ascii text encoded as utf-16 has null bytes at every other position.
+    let sample = &data[..data.len().min(1024)];
+    let nulls = |skip| {
+        sample
+            .iter()
+            .skip(skip)
+            .step_by(2)
+            .filter(|x| **x == 0)
+            .count()
+    };
+    let (even_nulls, odd_nulls, half) = (nulls(0), nulls(1), sample.len() / 2);
+
+    let utf16 = if sample.len() < 4 {
+        None
+    } else if odd_nulls > half * 2 / 5 && even_nulls < half / 10 {
+        Some(encoding_rs::UTF_16LE)
+    } else if even_nulls > half * 2 / 5 && odd_nulls < half / 10 {
+        Some(encoding_rs::UTF_16BE)
+    } else {
+        None
+    };
+
+    if let Some(encoding) = utf16 {
+        return encoding.decode_without_bom_handling(data).0.into_owned();
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        return text.to_owned();
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(data, true);
+    detector
+        .guess(None, true)
+        .decode_without_bom_handling(data)
+        .0
+        .into_owned()
+}
+
 pub fn find_ffmpeg() -> Option<PathBuf> {
     let bin = if cfg!(target_os = "windows") {
         "ffmpeg.exe"
@@ -80,3 +129,74 @@ pub fn format_download_bytes(downloaded: usize, total: usize) -> String {
         format!("{} / {}", downloaded.2, total.2)
     }
 }
+
+pub fn is_mp4(data: &[u8]) -> bool {
+    matches!(
+        data.get(4..8),
+        Some(b"ftyp" | b"styp" | b"moov" | b"moof" | b"sidx" | b"emsg" | b"prft" | b"free")
+    )
+}
+
+/// Compare strings in natural order i.e. runs of digits are compared numerically,
+/// so that `2.m4s` comes before `10.m4s`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+
+                    while let Some(x) = chars.next_if(|x| x.is_ascii_digit()) {
+                        digits.push(x);
+                    }
+
+                    digits.trim_start_matches('0').to_owned()
+                };
+                let (x, y) = (number(&mut a), number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Split init segment i.e. top level boxes till `moov` box from rest of the data.
+pub fn split_init(data: &[u8]) -> (&[u8], &[u8]) {
+    let mut position = 0;
+
+    while position + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[position..(position + 4)].try_into().unwrap()) as usize;
+
+        if size < 8 {
+            break;
+        }
+
+        position += size;
+
+        if &data[(position - size + 4)..(position - size + 8)] == b"moov" && position <= data.len()
+        {
+            return data.split_at(position);
+        }
+    }
+
+    data.split_at(0)
+}